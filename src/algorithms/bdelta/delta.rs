@@ -0,0 +1,133 @@
+use std::io;
+use std::collections::HashMap;
+
+/// Size of the blocks of the old content looked up in the new one. Shorter matches are
+/// cheaper to insert than to copy.
+const BLOCK_SIZE: usize = 16;
+/// Largest number of literal bytes a single insert instruction carries
+const MAX_INSERT: usize = 0x7f;
+/// Instruction byte introducing a copy, any smaller non-zero byte being an insert length
+const COPY: u8 = 0x80;
+
+/// Delta turning `old` into `new`.
+///
+/// The delta starts with the lengths of both contents as varints, followed by instructions:
+/// a byte from 1 to 127 inserts that many literal bytes, which follow it, and `0x80` copies
+/// a range of `old` given by its offset and length, both varints. Matches are found by
+/// indexing `old` in blocks of 16 bytes, then extended in both directions byte by byte, so
+/// data shifted by insertions or deletions is still copied.
+pub fn encode(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, old.len());
+    write_varint(&mut delta, new.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for offset in (0..old.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        blocks.entry(&old[offset..offset + BLOCK_SIZE]).or_insert(offset);
+    }
+
+    let mut literal_start = 0;
+    let mut position = 0;
+    while position + BLOCK_SIZE <= new.len() {
+        let Some(&found) = blocks.get(&new[position..position + BLOCK_SIZE]) else {
+            position += 1;
+            continue;
+        };
+
+        let mut length = BLOCK_SIZE;
+        while found + length < old.len() && position + length < new.len() && old[found + length] == new[position + length] {
+            length += 1;
+        }
+        // Take back bytes that would otherwise be inserted as literals
+        let (mut start, mut offset) = (position, found);
+        while start > literal_start && offset > 0 && old[offset - 1] == new[start - 1] {
+            start -= 1;
+            offset -= 1;
+            length += 1;
+        }
+
+        push_insert(&mut delta, &new[literal_start..start]);
+        delta.push(COPY);
+        write_varint(&mut delta, offset);
+        write_varint(&mut delta, length);
+
+        position = start + length;
+        literal_start = position;
+    }
+    push_insert(&mut delta, &new[literal_start..]);
+    delta
+}
+
+/// Applies a delta made by `encode` to the content it was computed against
+pub fn decode(old: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid delta: {}", message));
+
+    let mut position = 0;
+    let old_len = read_varint(delta, &mut position).ok_or_else(|| invalid("truncated header"))?;
+    let new_len = read_varint(delta, &mut position).ok_or_else(|| invalid("truncated header"))?;
+    if old_len != old.len() {
+        return Err(invalid("computed against different content"));
+    }
+
+    // The header may be damaged, so it only bounds the output rather than sizing it up front
+    let mut new = Vec::with_capacity(new_len.min(old.len().saturating_add(delta.len())));
+    while position < delta.len() {
+        let instruction = delta[position];
+        position += 1;
+        match instruction {
+            COPY => {
+                let offset = read_varint(delta, &mut position).ok_or_else(|| invalid("truncated copy"))?;
+                let length = read_varint(delta, &mut position).ok_or_else(|| invalid("truncated copy"))?;
+                let range = old.get(offset..offset.saturating_add(length)).ok_or_else(|| invalid("copy outside the base content"))?;
+                new.extend_from_slice(range);
+            }
+            1..=0x7f => {
+                let literal = delta.get(position..position + instruction as usize).ok_or_else(|| invalid("truncated insert"))?;
+                new.extend_from_slice(literal);
+                position += literal.len();
+            }
+            _ => return Err(invalid("unknown instruction")),
+        }
+        if new.len() > new_len {
+            return Err(invalid("result has the wrong length"));
+        }
+    }
+
+    if new.len() != new_len {
+        return Err(invalid("result has the wrong length"));
+    }
+    Ok(new)
+}
+
+fn push_insert(delta: &mut Vec<u8>, literal: &[u8]) {
+    for chunk in literal.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+/// Seven bits per byte, least significant first, the high bit set on all but the last byte
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> Option<usize> {
+    let mut value: usize = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*position)?;
+        *position += 1;
+        if shift >= usize::BITS {
+            return None;
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,15 @@
+use std::io;
+
+pub(super) mod delta;
+#[cfg(test)]
+mod test;
+
+/// Encodes `new` as a delta against `old`, used for binary file revisions
+pub fn make_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    delta::encode(old, new)
+}
+
+/// Rebuilds the content a delta produced by `make_delta` was computed for
+pub fn apply_delta(old: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    delta::decode(old, delta)
+}
@@ -0,0 +1,78 @@
+use super::delta::{decode, encode};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Lcg;
+
+    fn random_bytes(rng: &mut Lcg, len: usize) -> Vec<u8> {
+        (0..len).map(|_| rng.next(256) as u8).collect()
+    }
+
+    #[test]
+    fn test_round_trip_edge_cases() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"", b""),
+            (b"", b"only new"),
+            (b"only old", b""),
+            (b"short", b"short"),
+            (&[0u8; 300], &[0u8; 301]),
+        ];
+        for (old, new) in cases {
+            assert_eq!(decode(old, &encode(old, new)).unwrap(), new);
+        }
+    }
+
+    #[test]
+    fn test_small_edit_of_large_content_stays_small() {
+        let mut rng = Lcg(11);
+        let old = random_bytes(&mut rng, 64 * 1024);
+        let mut new = old.clone();
+        new.splice(1000..1010, random_bytes(&mut rng, 40));
+        new.drain(30_000..30_500);
+        new.extend_from_slice(b"appended");
+
+        let delta = encode(&old, &new);
+        assert!(delta.len() < 200, "A few edits should not cost a full copy, got {} bytes", delta.len());
+        assert_eq!(decode(&old, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn test_random_revisions_round_trip() {
+        let mut rng = Lcg(3);
+        for _ in 0..50 {
+            let len = rng.next(2000) as usize;
+            let old = random_bytes(&mut rng, len);
+            let mut new = old.clone();
+            for _ in 0..rng.next(5) {
+                let at = rng.next(new.len() as u64 + 1) as usize;
+                let removed = (rng.next(100) as usize).min(new.len() - at);
+                let inserted_len = rng.next(100) as usize;
+                let inserted = random_bytes(&mut rng, inserted_len);
+                new.splice(at..at + removed, inserted);
+            }
+            assert_eq!(decode(&old, &encode(&old, &new)).unwrap(), new);
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_deltas() {
+        let delta = encode(b"base content here", b"base content there");
+        assert!(decode(b"other base", &delta).is_err(), "The base length is checked");
+        assert!(decode(b"base content here", &delta[..delta.len() - 1]).is_err());
+        assert!(decode(b"", &[0, 1, 0x80, 5, 1]).is_err(), "Copies must stay inside the base");
+    }
+
+    #[test]
+    fn test_decode_does_not_trust_the_length_header() {
+        // A new length of i64::MAX must be reported, not allocated
+        let delta = [0x03, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        let error = decode(b"abc", &delta).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // Nor may copies run past the length it gives
+        let mut delta = encode(b"abc", b"abc");
+        delta[1] = 2;
+        assert!(decode(b"abc", &delta).is_err());
+    }
+}
@@ -29,10 +29,25 @@ pub fn compress_file(input_path: &Path, output_path: &Path) -> io::Result<()> {
 }
 
 // Helper function to decompress the compressed file and compare it with the original
+#[allow(dead_code)]
 pub fn decompress_file(input_path: &Path, output_path: &Path) -> io::Result<()> {
     let input_file = File::open(input_path)?;
     let mut decoder = ZlibDecoder::new(input_file);
     let mut output_file = File::create(output_path)?;
     io::copy(&mut decoder, &mut output_file)?;
     Ok(())
-}
\ No newline at end of file
+}
+/// Compresses an in-memory buffer with zlib, using the same level as `compress_file`
+pub fn compress_bytes(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut writer = ZlibEncoder::new(Vec::new(), Compression::best());
+    writer.write_all(data)?;
+    writer.finish()
+}
+
+/// Inflates a buffer produced by `compress_bytes`
+pub fn decompress_bytes(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output)?;
+    Ok(output)
+}
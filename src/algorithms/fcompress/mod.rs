@@ -2,9 +2,20 @@ use std::io;
 use std::path::Path;
 
 pub(super) mod compress;
+#[cfg(test)]
 mod test;
 
 #[allow(dead_code)]
 pub fn comp(input_path: &Path, output_path: &Path) -> io::Result<()> {
     compress::compress_file(input_path, output_path)
 }
+
+/// Compresses a buffer in memory, used by the object store
+pub fn comp_bytes(data: &[u8]) -> io::Result<Vec<u8>> {
+    compress::compress_bytes(data)
+}
+
+/// Decompresses a buffer produced by `comp_bytes`
+pub fn decomp_bytes(data: &[u8]) -> io::Result<Vec<u8>> {
+    compress::decompress_bytes(data)
+}
@@ -1,4 +1,4 @@
-use super::compress::{compress_file, decompress_file};
+use super::compress::{compress_file, decompress_file, compress_bytes, decompress_bytes};
 
 #[cfg(test)]
 mod tests {
@@ -48,4 +48,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_compress_bytes_roundtrip() -> io::Result<()> {
+        let input_data = b"Line 1\nHello World\nLine 3\n".repeat(16);
+
+        let compressed = compress_bytes(&input_data)?;
+        assert!(compressed.len() < input_data.len());
+
+        let decompressed = decompress_bytes(&compressed)?;
+        assert_eq!(input_data, decompressed);
+
+        Ok(())
+    }
 }
@@ -1,2 +1,3 @@
 pub mod vcompare;
-pub mod fcompress;
\ No newline at end of file
+pub mod fcompress;
+pub mod bdelta;
\ No newline at end of file
@@ -1,9 +1,39 @@
 use super::edit::Edit;
-use super::difference::myers;
+use super::difference::{diff_ops, myers, DiffOp};
 use super::utils::split_lines;
 
 pub fn compare(old: &str, new: &str) -> Vec<Edit> {
     let old_lines = split_lines(old);
     let new_lines = split_lines(new);
     myers(old_lines, new_lines)
-}
\ No newline at end of file
+}
+
+/// Share of lines the two texts have in common, from 0.0 (nothing) to 1.0 (identical).
+///
+/// Computed as `2 * common / (old_lines + new_lines)`, common lines being those kept by
+/// the shortest edit script. Two empty texts are identical.
+pub fn similarity(old: &str, new: &str) -> f64 {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let total = old_lines.len() + new_lines.len();
+    if total == 0 {
+        return 1.0;
+    }
+
+    let common = diff_ops(&old_lines, &new_lines).iter()
+        .filter(|op| matches!(op, DiffOp::Equal(..)))
+        .count();
+    2.0 * common as f64 / total as f64
+}
+
+/// Number of lines added and removed to turn `old` into `new`
+pub fn count_changes(old: &str, new: &str) -> (usize, usize) {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+
+    diff_ops(&old_lines, &new_lines).iter().fold((0, 0), |(added, removed), op| match op {
+        DiffOp::Insert(_) => (added + 1, removed),
+        DiffOp::Delete(_) => (added, removed + 1),
+        DiffOp::Equal(..) => (added, removed),
+    })
+}
@@ -1,42 +1,230 @@
 use super::edit::Edit;
+use std::ops::{Index, IndexMut, Range};
 
-/// Computes the differences between two sequences of strings using a simplified Myers diff algorithm.
+/// A single step of a shortest edit script, expressed with indices into the two inputs.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DiffOp {
+    /// `old[.0]` and `new[.1]` are the same line.
+    Equal(usize, usize),
+    /// `old[.0]` is removed.
+    Delete(usize),
+    /// `new[.0]` is added.
+    Insert(usize),
+}
+
+/// Computes the differences between two sequences of strings using the Myers diff algorithm.
 ///
 /// # Arguments
 /// * `old` - The original sequence of strings.
 /// * `new` - The new sequence of strings.
 ///
 /// # Returns
-/// A vector of `Edit` operations that transform `old` into `new`.
+/// A vector of `Edit` operations that transform `old` into `new`. The edits are meant to be
+/// applied in order: each index refers to the sequence as left by the previous edits.
+/// A deletion directly followed by an insertion at the same place is reported as a `Replace`.
 pub fn myers(old: Vec<String>, new: Vec<String>) -> Vec<Edit> {
+    to_edits(&diff_ops(&old, &new), &new)
+}
+
+/// Computes a shortest edit script between two sequences in O((N + M) D) time.
+///
+/// Uses the linear space refinement from Myers' paper: the middle snake of the edit graph
+/// is found by searching forward and backward at the same time, then both halves are
+/// solved recursively, so memory stays proportional to N + M even for large files.
+pub fn diff_ops<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffOp> {
+    let max_d = max_d(old.len(), new.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+
+    conquer(old, 0..old.len(), new, 0..new.len(), &mut vf, &mut vb, &mut ops);
+    ops
+}
+
+/// Turns an edit script into sequentially applicable `Edit`s
+fn to_edits(ops: &[DiffOp], new: &[String]) -> Vec<Edit> {
     let mut edits = Vec::new();
-    let mut old_index = 0;
-    let mut new_index = 0;
-
-    while old_index < old.len() && new_index < new.len() {
-        if old[old_index] == new[new_index] {
-            // Lines are the same, move to the next pair
-            old_index += 1;
-            new_index += 1;
-        } else {
-            // Lines are different, push a Replace edit
-            edits.push(Edit::Replace(new_index, new[new_index].clone()));
-            old_index += 1;
-            new_index += 1;
+    let mut position = 0;
+    let mut deleted = 0;
+    let mut inserted = Vec::new();
+
+    for op in ops {
+        match op {
+            DiffOp::Equal(..) => {
+                flush_run(&mut edits, &mut position, &mut deleted, &mut inserted, new);
+                position += 1;
+            }
+            DiffOp::Delete(_) => deleted += 1,
+            DiffOp::Insert(new_index) => inserted.push(*new_index),
         }
     }
+    flush_run(&mut edits, &mut position, &mut deleted, &mut inserted, new);
 
-    // Handle remaining lines in the old sequence (deletions)
-    while old_index < old.len() {
-        edits.push(Edit::Delete(old_index));
-        old_index += 1;
+    edits
+}
+
+/// Emits one block of changed lines, pairing deletions with insertions as replacements
+fn flush_run(edits: &mut Vec<Edit>, position: &mut usize, deleted: &mut usize, inserted: &mut Vec<usize>, new: &[String]) {
+    let replaced = (*deleted).min(inserted.len());
+
+    for &new_index in &inserted[..replaced] {
+        edits.push(Edit::Replace(*position, new[new_index].clone()));
+        *position += 1;
+    }
+    for _ in replaced..*deleted {
+        edits.push(Edit::Delete(*position));
+    }
+    for &new_index in &inserted[replaced..] {
+        edits.push(Edit::Insert(*position, new[new_index].clone()));
+        *position += 1;
     }
 
-    // Handle remaining lines in the new sequence (insertions)
-    while new_index < new.len() {
-        edits.push(Edit::Insert(new_index, new[new_index].clone()));
-        new_index += 1;
+    *deleted = 0;
+    inserted.clear();
+}
+
+/// Furthest reaching x value for each diagonal k, indexable by negative k.
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        V { offset: max_d as isize, v: vec![0; 2 * max_d] }
     }
+}
 
-    edits
-}
\ No newline at end of file
+impl Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// Only half of the worst case edit distance needs to be explored from each side
+fn max_d(old_len: usize, new_len: usize) -> usize {
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+fn common_prefix_len<T: PartialEq>(old: &[T], old_range: Range<usize>, new: &[T], new_range: Range<usize>) -> usize {
+    old[old_range].iter().zip(&new[new_range]).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix_len<T: PartialEq>(old: &[T], old_range: Range<usize>, new: &[T], new_range: Range<usize>) -> usize {
+    old[old_range].iter().rev().zip(new[new_range].iter().rev()).take_while(|(a, b)| a == b).count()
+}
+
+/// Finds the start of the middle snake, the point where the forward and backward searches meet
+fn find_middle_snake<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+) -> Option<(usize, usize)> {
+    let n = old_range.len();
+    let m = new_range.len();
+
+    // The length of the edit script has the same parity as delta
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+
+    vf[1] = 0;
+    vb[1] = 0;
+
+    for d in 0..max_d(n, m) as isize {
+        // Forward search from the top left corner
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+
+            if x < n && y < m {
+                x += common_prefix_len(old, old_range.start + x..old_range.end, new, new_range.start + y..new_range.end);
+            }
+            vf[k] = x;
+
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((x0 + old_range.start, y0 + new_range.start));
+            }
+        }
+
+        // Backward search from the bottom right corner
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+
+            if x < n && y < m {
+                let advance = common_suffix_len(
+                    old, old_range.start..old_range.start + n - x,
+                    new, new_range.start..new_range.start + m - y,
+                );
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((n - x + old_range.start, m - y + new_range.start));
+            }
+        }
+    }
+
+    None
+}
+
+/// Recursively solves both sides of the middle snake, after peeling off common ends
+fn conquer<T: PartialEq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+    ops: &mut Vec<DiffOp>,
+) {
+    let prefix = common_prefix_len(old, old_range.clone(), new, new_range.clone());
+    for i in 0..prefix {
+        ops.push(DiffOp::Equal(old_range.start + i, new_range.start + i));
+    }
+    old_range.start += prefix;
+    new_range.start += prefix;
+
+    let suffix = common_suffix_len(old, old_range.clone(), new, new_range.clone());
+    let suffix_start = (old_range.end - suffix, new_range.end - suffix);
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() {
+        ops.extend(new_range.map(DiffOp::Insert));
+    } else if new_range.is_empty() {
+        ops.extend(old_range.map(DiffOp::Delete));
+    } else if let Some((x, y)) = find_middle_snake(old, old_range.clone(), new, new_range.clone(), vf, vb) {
+        conquer(old, old_range.start..x, new, new_range.start..y, vf, vb, ops);
+        conquer(old, x..old_range.end, new, y..new_range.end, vf, vb, ops);
+    } else {
+        ops.extend(old_range.map(DiffOp::Delete));
+        ops.extend(new_range.map(DiffOp::Insert));
+    }
+
+    for i in 0..suffix {
+        ops.push(DiffOp::Equal(suffix_start.0 + i, suffix_start.1 + i));
+    }
+}
@@ -0,0 +1,183 @@
+use std::ops::Range;
+use super::difference::{diff_ops, DiffOp};
+use super::utils::{join_lines, split_lines};
+
+/// How conflicts are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictStyle {
+    /// Only our and their side
+    #[default]
+    Merge,
+    /// Our side, the common ancestor, then their side
+    Diff3,
+}
+
+/// Names printed after the conflict markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeLabels<'a> {
+    pub ours: &'a str,
+    pub base: &'a str,
+    pub theirs: &'a str,
+}
+
+impl Default for MergeLabels<'_> {
+    fn default() -> Self {
+        MergeLabels { ours: "ours", base: "base", theirs: "theirs" }
+    }
+}
+
+/// A region both sides changed in different ways.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Lines of the common ancestor the region replaces
+    pub base_range: Range<usize>,
+    pub base: Vec<String>,
+    pub ours: Vec<String>,
+    pub theirs: Vec<String>,
+    /// Line of the merged text, starting at 1, holding the opening `<<<<<<<` marker
+    pub marker_line: usize,
+}
+
+/// Outcome of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// Merged text, with conflict markers around the regions that could not be merged
+    pub text: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A block of lines one side changed: lines of the base and what the side has instead.
+#[derive(Debug, Clone)]
+struct Hunk {
+    base: Range<usize>,
+    side: Range<usize>,
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`, like diff3.
+///
+/// Regions only one side touched take that side's version, and regions both sides changed
+/// the same way are taken once. Where both changed overlapping or adjacent lines differently
+/// the result holds both versions between conflict markers, with the base version in
+/// between when `style` is `Diff3`.
+pub fn merge3(base: &str, ours: &str, theirs: &str, labels: &MergeLabels, style: ConflictStyle) -> MergeResult {
+    let base_lines = split_lines(base);
+    let our_lines = split_lines(ours);
+    let their_lines = split_lines(theirs);
+
+    let our_hunks = hunks(&diff_ops(&base_lines, &our_lines));
+    let their_hunks = hunks(&diff_ops(&base_lines, &their_lines));
+
+    let mut output: Vec<String> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut position = 0;
+    let (mut next_ours, mut next_theirs) = (0, 0);
+
+    loop {
+        let group_start = match (our_hunks.get(next_ours), their_hunks.get(next_theirs)) {
+            (Some(a), Some(b)) => a.base.start.min(b.base.start),
+            (Some(a), None) => a.base.start,
+            (None, Some(b)) => b.base.start,
+            (None, None) => break,
+        };
+
+        // Grow the group while a hunk of either side overlaps or touches it
+        let (first_ours, first_theirs) = (next_ours, next_theirs);
+        let mut group_end = group_start;
+        loop {
+            if let Some(hunk) = our_hunks.get(next_ours).filter(|hunk| hunk.base.start <= group_end) {
+                group_end = group_end.max(hunk.base.end);
+                next_ours += 1;
+            } else if let Some(hunk) = their_hunks.get(next_theirs).filter(|hunk| hunk.base.start <= group_end) {
+                group_end = group_end.max(hunk.base.end);
+                next_theirs += 1;
+            } else {
+                break;
+            }
+        }
+
+        output.extend_from_slice(&base_lines[position..group_start]);
+        position = group_end;
+
+        let group = group_start..group_end;
+        let ours_part = side_lines(&our_lines, &our_hunks[first_ours..next_ours], &group);
+        let theirs_part = side_lines(&their_lines, &their_hunks[first_theirs..next_theirs], &group);
+
+        match (ours_part, theirs_part) {
+            (Some(ours_part), None) => output.extend(ours_part),
+            (None, Some(theirs_part)) => output.extend(theirs_part),
+            (Some(ours_part), Some(theirs_part)) if ours_part == theirs_part => output.extend(ours_part),
+            (ours_part, theirs_part) => {
+                let ours_part = ours_part.unwrap_or_default();
+                let theirs_part = theirs_part.unwrap_or_default();
+                let base_part = base_lines[group.clone()].to_vec();
+
+                let marker_line = output.len() + 1;
+                push_marker(&mut output, &format!("<<<<<<< {}", labels.ours));
+                output.extend(ours_part.iter().cloned());
+                if style == ConflictStyle::Diff3 {
+                    push_marker(&mut output, &format!("||||||| {}", labels.base));
+                    output.extend(base_part.iter().cloned());
+                }
+                push_marker(&mut output, "=======");
+                output.extend(theirs_part.iter().cloned());
+                push_marker(&mut output, &format!(">>>>>>> {}", labels.theirs));
+
+                conflicts.push(Conflict { base_range: group, base: base_part, ours: ours_part, theirs: theirs_part, marker_line });
+            }
+        }
+    }
+    output.extend_from_slice(&base_lines[position..]);
+
+    MergeResult { text: join_lines(&output), conflicts }
+}
+
+/// Blocks of consecutive changes in an edit script
+fn hunks(ops: &[DiffOp]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut base, mut side) = (0, 0);
+    let mut in_change = false;
+
+    for op in ops {
+        if !in_change && !matches!(op, DiffOp::Equal(..)) {
+            hunks.push(Hunk { base: base..base, side: side..side });
+        }
+        match op {
+            DiffOp::Equal(..) => { base += 1; side += 1; }
+            DiffOp::Delete(_) => base += 1,
+            DiffOp::Insert(_) => side += 1,
+        }
+        in_change = !matches!(op, DiffOp::Equal(..));
+        if let Some(hunk) = hunks.last_mut().filter(|_| in_change) {
+            hunk.base.end = base;
+            hunk.side.end = side;
+        }
+    }
+    hunks
+}
+
+/// What a side has in place of the base lines in `group`, `None` if it changed none of them.
+/// Between and around its hunks the side still has the base lines, so the offsets of the
+/// first and last hunk locate the whole region.
+fn side_lines(lines: &[String], hunks: &[Hunk], group: &Range<usize>) -> Option<Vec<String>> {
+    let first = hunks.first()?;
+    let last = hunks.last()?;
+    let start = first.side.start - (first.base.start - group.start);
+    let end = last.side.end + (group.end - last.base.end);
+    Some(lines[start..end].to_vec())
+}
+
+/// Markers always sit on their own line, even after a last line without a newline
+fn push_marker(output: &mut Vec<String>, marker: &str) {
+    if let Some(last) = output.last_mut() {
+        if !last.ends_with('\n') {
+            last.push('\n');
+        }
+    }
+    output.push(format!("{}\n", marker));
+}
@@ -1,5 +1,7 @@
+#[cfg(test)]
 mod test;
 pub mod edit;
 pub mod compv;
 pub mod utils;
-pub(super) mod difference;
\ No newline at end of file
+pub mod merge;
+pub mod difference;
\ No newline at end of file
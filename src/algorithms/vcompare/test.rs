@@ -1,9 +1,49 @@
 use super::edit::Edit;
-use super::difference::myers;
+use super::compv::{compare, count_changes, similarity};
+use super::difference::{myers, diff_ops, DiffOp};
+use super::utils::{join_lines, split_lines};
+use super::merge::{merge3, ConflictStyle, MergeLabels, MergeResult};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::Lcg;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    /// Applies edits in order, the same way stored versions are replayed
+    fn apply(old: &[String], edits: &[Edit]) -> Vec<String> {
+        let mut result = old.to_vec();
+        for edit in edits {
+            match edit {
+                Edit::Insert(index, text) => result.insert(*index, text.clone()),
+                Edit::Delete(index) => { result.remove(*index); }
+                Edit::Replace(index, text) => result[*index] = text.clone(),
+            }
+        }
+        result
+    }
+
+    /// Length of the longest common subsequence, by dynamic programming
+    fn lcs_len(a: &[String], b: &[String]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i][j] = if a[i] == b[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table[0][0]
+    }
+
+    fn sequence(rng: &mut Lcg, max_len: u64, alphabet: u64) -> Vec<String> {
+        (0..rng.next(max_len)).map(|_| rng.next(alphabet).to_string()).collect()
+    }
 
     // Helper function to assert the changes in a readable format
     fn assert_changes(expected_changes: Vec<Edit>, actual_changes: Vec<Edit>) {
@@ -67,9 +107,10 @@ mod tests {
 
         let changes = myers(old_version, new_version);
 
+        // Each index refers to the sequence left by the previous edit
         let expected_changes = vec![
             Edit::Delete(1),
-            Edit::Delete(2)
+            Edit::Delete(1)
         ];
 
         assert_changes(expected_changes, changes);
@@ -95,4 +136,196 @@ mod tests {
 
         assert_changes(expected_changes, changes);
     }
+
+    #[test]
+    fn test_insert_at_top_is_a_single_insert() {
+        let old_version = lines("a b c d e");
+        let new_version = lines("new a b c d e");
+
+        let changes = myers(old_version, new_version);
+        assert_changes(vec![Edit::Insert(0, "new".to_string())], changes);
+    }
+
+    #[test]
+    fn test_delete_in_the_middle() {
+        let old_version = lines("a b c d e");
+        let new_version = lines("a b d e");
+
+        let changes = myers(old_version, new_version);
+        assert_changes(vec![Edit::Delete(2)], changes);
+    }
+
+    #[test]
+    fn test_diff_ops_shortest_script() {
+        let old_version = lines("a b c a b b a");
+        let new_version = lines("c b a b a c");
+
+        let ops = diff_ops(&old_version, &new_version);
+        let changed = ops.iter().filter(|op| !matches!(op, DiffOp::Equal(..))).count();
+
+        // The example from Myers' paper has an edit distance of 5
+        assert_eq!(changed, 5);
+    }
+
+    #[test]
+    fn test_randomized_scripts_are_minimal_and_correct() {
+        let mut rng = Lcg(42);
+
+        for _ in 0..500 {
+            let old_version = sequence(&mut rng, 30, 4);
+            let new_version = sequence(&mut rng, 30, 4);
+
+            let ops = diff_ops(&old_version, &new_version);
+            let changed = ops.iter().filter(|op| !matches!(op, DiffOp::Equal(..))).count();
+            let minimal = old_version.len() + new_version.len() - 2 * lcs_len(&old_version, &new_version);
+            assert_eq!(changed, minimal, "Edit script should be minimal for {:?} -> {:?}", old_version, new_version);
+
+            let edits = myers(old_version.clone(), new_version.clone());
+            assert_eq!(apply(&old_version, &edits), new_version);
+        }
+    }
+
+    #[test]
+    fn test_large_inputs() {
+        let old_version: Vec<String> = (0..20_000).map(|i| format!("line {}", i)).collect();
+        let mut new_version = old_version.clone();
+        new_version.insert(0, "header".to_string());
+        new_version.remove(10_000);
+        new_version[15_000] = "changed".to_string();
+
+        let edits = myers(old_version.clone(), new_version.clone());
+        assert_eq!(edits.len(), 3);
+        assert_eq!(apply(&old_version, &edits), new_version);
+    }
+
+    #[test]
+    fn test_split_lines_keeps_whitespace_and_endings() {
+        let text = "  indented\ntrailing   \r\n\n\tlast";
+        let split = split_lines(text);
+
+        assert_eq!(split, vec!["  indented\n", "trailing   \r\n", "\n", "\tlast"]);
+        assert_eq!(join_lines(&split), text);
+        assert!(split_lines("").is_empty());
+    }
+
+    #[test]
+    fn test_compare_detects_whitespace_only_changes() {
+        assert!(compare("a\n  b\n", "a\n  b\n").is_empty());
+        assert_changes(vec![Edit::Replace(1, "    b\n".to_string())], compare("a\n  b\n", "a\n    b\n"));
+        assert_changes(vec![Edit::Replace(0, "a\r\n".to_string())], compare("a\n", "a\r\n"));
+        assert_changes(vec![Edit::Replace(0, "a\n".to_string())], compare("a", "a\n"));
+    }
+
+    #[test]
+    fn test_similarity_ratio() {
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("a\nb\n", "a\nb\n"), 1.0);
+        assert_eq!(similarity("a\nb\n", "c\nd\n"), 0.0);
+        // 3 of the 4 lines survive on each side
+        assert_eq!(similarity("a\nb\nc\nd\n", "a\nb\nX\nd\n"), 0.75);
+        assert_eq!(similarity("a\n", ""), 0.0);
+    }
+
+    #[test]
+    fn test_count_changes() {
+        assert_eq!(count_changes("a\nb\nc\n", "a\nB\nc\nd\n"), (2, 1));
+        assert_eq!(count_changes("same\n", "same\n"), (0, 0));
+        assert_eq!(count_changes("", "x\ny\n"), (2, 0));
+    }
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+        merge3(base, ours, theirs, &MergeLabels::default(), ConflictStyle::Merge)
+    }
+
+    #[test]
+    fn test_merge3_combines_separate_changes() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+
+        let result = merge(base, ours, theirs);
+        assert!(result.is_clean());
+        assert_eq!(result.text, "A\nb\nc\nd\nE\nf\n");
+    }
+
+    #[test]
+    fn test_merge3_identical_changes_are_not_conflicts() {
+        let result = merge("a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\n");
+        assert!(result.is_clean());
+        assert_eq!(result.text, "a\nB\nc\n");
+
+        // One side unchanged takes the other side entirely, including deletions
+        assert_eq!(merge("a\nb\n", "a\nb\n", "").text, "");
+    }
+
+    #[test]
+    fn test_merge3_conflict_markers() {
+        let base = "one\ntwo\nthree\n";
+        let ours = "one\nTWO (ours)\nthree\n";
+        let theirs = "one\nTWO (theirs)\nthree\n";
+
+        let result = merge(base, ours, theirs);
+        assert_eq!(result.text, concat!(
+            "one\n",
+            "<<<<<<< ours\n",
+            "TWO (ours)\n",
+            "=======\n",
+            "TWO (theirs)\n",
+            ">>>>>>> theirs\n",
+            "three\n",
+        ));
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.base_range, 1..2);
+        assert_eq!(conflict.base, vec!["two\n"]);
+        assert_eq!(conflict.ours, vec!["TWO (ours)\n"]);
+        assert_eq!(conflict.theirs, vec!["TWO (theirs)\n"]);
+        assert_eq!(conflict.marker_line, 2);
+
+        let labels = MergeLabels { ours: "HEAD", base: "merge base", theirs: "feature" };
+        let diff3 = merge3(base, ours, theirs, &labels, ConflictStyle::Diff3);
+        assert_eq!(diff3.text, concat!(
+            "one\n",
+            "<<<<<<< HEAD\n",
+            "TWO (ours)\n",
+            "||||||| merge base\n",
+            "two\n",
+            "=======\n",
+            "TWO (theirs)\n",
+            ">>>>>>> feature\n",
+            "three\n",
+        ));
+    }
+
+    #[test]
+    fn test_merge3_overlapping_hunks_form_one_conflict() {
+        let base = "1\n2\n3\n4\n5\n6\n";
+        let ours = "1\nX\nX\n4\n5\n6\n";
+        let theirs = "1\n2\nY\nY\n5\n6\n";
+
+        let result = merge(base, ours, theirs);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].base_range, 1..4);
+        assert_eq!(result.conflicts[0].ours, vec!["X\n", "X\n", "4\n"]);
+        assert_eq!(result.conflicts[0].theirs, vec!["2\n", "Y\n", "Y\n"]);
+    }
+
+    #[test]
+    fn test_merge3_markers_after_missing_final_newline() {
+        let result = merge("end", "end ours", "end theirs");
+        assert_eq!(result.text, "<<<<<<< ours\nend ours\n=======\nend theirs\n>>>>>>> theirs\n");
+    }
+
+    #[test]
+    fn test_merge3_random_one_sided_changes_apply_exactly() {
+        let mut rng = Lcg(7);
+        for _ in 0..200 {
+            let base: String = (0..rng.next(15)).map(|_| format!("{}\n", rng.next(4))).collect();
+            let changed: String = (0..rng.next(15)).map(|_| format!("{}\n", rng.next(4))).collect();
+
+            assert_eq!(merge(&base, &changed, &base).text, changed);
+            assert_eq!(merge(&base, &base, &changed).text, changed);
+            assert!(merge(&base, &changed, &changed).is_clean());
+        }
+    }
 }
@@ -1,5 +1,9 @@
 /// Takes text read from a text file and returns each line as a vector.
 ///
+/// Every line keeps its terminator (`\n` or `\r\n`), and a last line without a final newline
+/// is kept as is, so concatenating the result gives back the exact input. Indentation and
+/// trailing whitespace are part of the line.
+///
 /// # Arguments
 /// * `text` - A string slice that holds the content of the text file.
 ///
@@ -8,10 +12,17 @@
 ///
 /// # Examples
 /// ```
-/// let text = "line one\n line two\n line three";
+/// use grit::algorithms::vcompare::utils::split_lines;
+///
+/// let text = "line one\n  line two\r\nline three";
 /// let lines = split_lines(text);
-/// assert_eq!(lines, vec!["line one", "line two", "line three"]);
+/// assert_eq!(lines, vec!["line one\n", "  line two\r\n", "line three"]);
 /// ```
 pub fn split_lines(text: &str) -> Vec<String> {
-    text.lines().map(|line| line.trim().to_string()).collect()
+    text.split_inclusive('\n').map(str::to_string).collect()
+}
+
+/// Inverse of `split_lines`.
+pub fn join_lines(lines: &[String]) -> String {
+    lines.concat()
 }
@@ -0,0 +1,389 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use grit::systems::config::Scope;
+use grit::systems::gc::parse_grace;
+use grit::systems::identity::Signature;
+
+/// Grit, a lightweight version control system
+#[derive(Debug, Parser)]
+#[command(name = "grit", version, about, propagate_version = true, disable_help_subcommand = true)]
+pub struct Cli {
+    /// Run as if grit was started in <dir>. Each -C is relative to the previous one
+    #[arg(short = 'C', value_name = "dir", action = ArgAction::Append)]
+    pub directory: Vec<PathBuf>,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Options shared by every command
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Only print errors and what the command was asked for
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print more details about what the command does
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Never color the output, as when NO_COLOR is set
+    #[arg(long, global = true)]
+    pub no_color: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create an empty repository, or reinitialize an existing one
+    Init,
+
+    /// Stage files for the next commit
+    Add {
+        /// Stage everything below the current directory
+        #[arg(short = 'A', long = "all")]
+        all: bool,
+
+        /// Files or directories to stage, `.` for the current directory
+        #[arg(value_name = "path", required_unless_present = "all")]
+        paths: Vec<String>,
+    },
+
+    /// Stop tracking files, deleting them unless --cached is given
+    Rm {
+        /// Keep the files on disk
+        #[arg(long)]
+        cached: bool,
+
+        /// Remove files even with changes that were never committed
+        #[arg(short, long)]
+        force: bool,
+
+        #[arg(value_name = "path", required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Put staged entries back to their content at HEAD
+    Reset {
+        #[arg(value_name = "path", required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Record the staged files as a new commit
+    Commit {
+        /// Commit message
+        #[arg(short, long, value_name = "message")]
+        message: String,
+
+        /// Record someone else as the author, you staying the committer
+        #[arg(long, value_name = "name <email>", value_parser = parse_signature)]
+        author: Option<Signature>,
+    },
+
+    /// Show the commit history of HEAD
+    Log {
+        /// List the files each commit changed with their line counts
+        #[arg(long)]
+        stat: bool,
+    },
+
+    /// Show staged, unstaged and untracked changes
+    Status {
+        /// One `XY path` line per file, for scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
+
+    /// Show changes as a unified diff
+    Diff {
+        /// Compare the staged files with HEAD
+        #[arg(long, visible_alias = "cached", conflicts_with = "revisions")]
+        staged: bool,
+
+        /// Per-file summary with a histogram
+        #[arg(long, conflicts_with = "numstat")]
+        stat: bool,
+
+        /// Per-file line counts for scripts
+        #[arg(long)]
+        numstat: bool,
+
+        /// Lines of context around each change, diff.context or 3 by default
+        #[arg(short = 'U', long, value_name = "n")]
+        unified: Option<usize>,
+
+        /// Color the output even when it is not a terminal
+        #[arg(long, conflicts_with = "no_color")]
+        color: bool,
+
+        /// Compare one commit with another
+        #[arg(value_name = "commit", num_args = 2)]
+        revisions: Vec<String>,
+    },
+
+    /// List, create, delete or rename branches
+    Branch(BranchArgs),
+
+    /// Check out a branch
+    Switch {
+        /// Create the branch at HEAD first
+        #[arg(short, long)]
+        create: bool,
+
+        /// Throw away local changes
+        #[arg(short, long)]
+        force: bool,
+
+        #[arg(value_name = "branch")]
+        branch: String,
+    },
+
+    /// Rewrite the working directory to match a commit or branch
+    Checkout {
+        /// Throw away local changes
+        #[arg(short, long)]
+        force: bool,
+
+        #[arg(value_name = "commit|branch")]
+        rev: String,
+    },
+
+    /// Bring back files from the staged tree or a commit
+    Restore {
+        /// Commit to take the files from
+        #[arg(short, long, value_name = "commit")]
+        source: Option<String>,
+
+        #[arg(value_name = "path", required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Merge a branch or commit into HEAD
+    Merge {
+        /// Branch or commit to merge
+        #[arg(value_name = "branch", required_unless_present_any = ["continue_merge", "abort"])]
+        rev: Option<String>,
+
+        /// Commit the merge once conflicts are resolved
+        #[arg(long = "continue", conflicts_with_all = ["rev", "abort"])]
+        continue_merge: bool,
+
+        /// Give up the merge and go back to HEAD
+        #[arg(long, conflicts_with = "rev")]
+        abort: bool,
+    },
+
+    /// Three-way merge of single files, outside any repository
+    MergeFile {
+        /// Print the result instead of overwriting <ours>
+        #[arg(short = 'p', long = "stdout")]
+        stdout: bool,
+
+        /// Show the base version in conflicts
+        #[arg(long)]
+        diff3: bool,
+
+        #[arg(value_name = "ours")]
+        ours: PathBuf,
+        #[arg(value_name = "base")]
+        base: PathBuf,
+        #[arg(value_name = "theirs")]
+        theirs: PathBuf,
+    },
+
+    /// Tell whether paths are ignored, with the matching pattern when verbose
+    CheckIgnore {
+        #[arg(value_name = "path", required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Print an object, or its kind with -t
+    CatFile {
+        /// Print the kind of the object instead of its content
+        #[arg(short = 't')]
+        kind: bool,
+
+        #[arg(value_name = "object")]
+        object: String,
+    },
+
+    /// Move every object into a single delta-compressed pack
+    Repack,
+
+    /// Remove unreachable commits, objects and version chains
+    Gc {
+        /// Only list what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Keep unreachable data younger than this: 30m, 12h, 3d, 2w or now. gc.grace or
+        /// two weeks by default
+        #[arg(long, value_name = "duration", value_parser = parse_duration)]
+        grace: Option<Duration>,
+    },
+
+    /// Verify the objects, commits, refs and version chains
+    Fsck,
+
+    /// Get, set and list settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Print a completion script for a shell
+    Completions {
+        #[arg(value_enum, value_name = "shell")]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct BranchArgs {
+    /// List branches, the default without arguments
+    #[arg(short, long, conflicts_with_all = ["delete", "force_delete", "rename", "force_rename"])]
+    pub list: bool,
+
+    /// Delete a branch merged into HEAD
+    #[arg(short, long, conflicts_with_all = ["force_delete", "rename", "force_rename"])]
+    pub delete: bool,
+
+    /// Delete a branch even if it is not merged
+    #[arg(short = 'D', conflicts_with_all = ["rename", "force_rename"])]
+    pub force_delete: bool,
+
+    /// Rename a branch, the current one if only one name is given
+    #[arg(short = 'm', long = "move", conflicts_with = "force_rename")]
+    pub rename: bool,
+
+    /// Rename a branch, replacing an existing one
+    #[arg(short = 'M')]
+    pub force_rename: bool,
+
+    /// <name> [<start>] to create, <name> to delete, [<old>] <new> to rename
+    #[arg(value_name = "name", num_args = 0..=2)]
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Print the value of a setting
+    Get {
+        #[command(flatten)]
+        scope: ScopeArgs,
+
+        /// Also print the file the value comes from
+        #[arg(long)]
+        show_origin: bool,
+
+        #[arg(value_name = "key")]
+        key: String,
+    },
+
+    /// Change a setting, in the repository's config unless another file is chosen
+    Set {
+        #[command(flatten)]
+        scope: ScopeArgs,
+
+        #[arg(value_name = "key")]
+        key: String,
+
+        #[arg(value_name = "value")]
+        value: String,
+    },
+
+    /// Remove a setting, from the repository's config unless another file is chosen
+    Unset {
+        #[command(flatten)]
+        scope: ScopeArgs,
+
+        #[arg(value_name = "key")]
+        key: String,
+    },
+
+    /// Print every setting, those of later files taking precedence
+    List {
+        #[command(flatten)]
+        scope: ScopeArgs,
+
+        /// Also print the file each setting comes from
+        #[arg(long)]
+        show_origin: bool,
+    },
+}
+
+/// Which configuration file a `grit config` command reads or writes
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+pub struct ScopeArgs {
+    /// The file of every user of the machine
+    #[arg(long)]
+    pub system: bool,
+
+    /// The file of the current user
+    #[arg(long)]
+    pub global: bool,
+
+    /// The repository's `.grit/config`
+    #[arg(long)]
+    pub local: bool,
+
+    /// `.grit/config.worktree`, for this working directory only
+    #[arg(long)]
+    pub worktree: bool,
+}
+
+impl ScopeArgs {
+    /// The chosen file, `None` to read every one of them
+    pub fn scope(&self) -> Option<Scope> {
+        [(self.system, Scope::System), (self.global, Scope::Global), (self.local, Scope::Repository), (self.worktree, Scope::Worktree)]
+            .into_iter()
+            .find_map(|(chosen, scope)| chosen.then_some(scope))
+    }
+}
+
+/// How much a command prints besides its errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl OutputArgs {
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (_, true) => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        }
+    }
+}
+
+impl Command {
+    /// Commands that change the repository, which hold its lock while they run
+    pub fn changes_repository(&self) -> bool {
+        match self {
+            Command::Add { .. } | Command::Rm { .. } | Command::Reset { .. } | Command::Commit { .. }
+            | Command::Checkout { .. } | Command::Restore { .. } | Command::Switch { .. }
+            | Command::Merge { .. } | Command::Repack | Command::Gc { .. } => true,
+            Command::Branch(args) => !args.list && !args.names.is_empty(),
+            Command::Config { action: ConfigAction::Set { scope, .. } | ConfigAction::Unset { scope, .. } } => {
+                scope.scope().is_none_or(|scope| scope >= Scope::Repository)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    parse_grace(text).map_err(|e| e.to_string())
+}
+
+fn parse_signature(text: &str) -> Result<Signature, String> {
+    Signature::parse(text).map_err(|e| e.to_string())
+}
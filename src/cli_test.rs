@@ -0,0 +1,132 @@
+use clap::Parser;
+use crate::cli::{Cli, Command, ConfigAction, Verbosity};
+
+/// Helper parsing a command line, the program name included
+fn parse(line: &str) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(line.split_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use clap::CommandFactory;
+    use clap::error::ErrorKind;
+    use clap_complete::Shell;
+    use grit::systems::config::Scope;
+
+    #[test]
+    fn test_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_options_in_any_order() {
+        for line in ["grit commit -m msg", "grit commit --message msg", "grit -q commit -m msg", "grit commit -m msg -q"] {
+            let cli = parse(line).unwrap();
+            assert!(matches!(&cli.command, Command::Commit { message, .. } if message == "msg"), "{}", line);
+        }
+        assert!(parse("grit -q commit -m msg").unwrap().output.quiet);
+    }
+
+    #[test]
+    fn test_commit_author() {
+        let cli = Cli::try_parse_from(["grit", "commit", "-m", "msg", "--author", "Ada Lovelace <ada@example.com>"]).unwrap();
+        assert!(matches!(&cli.command, Command::Commit { author: Some(author), .. } if author.name == "Ada Lovelace"));
+        let error = Cli::try_parse_from(["grit", "commit", "-m", "msg", "--author", "Ada"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_several_paths() {
+        let cli = parse("grit add a.txt dir b.txt").unwrap();
+        assert!(matches!(&cli.command, Command::Add { all: false, paths } if paths == &["a.txt", "dir", "b.txt"]));
+        assert!(matches!(parse("grit add -A").unwrap().command, Command::Add { all: true, .. }));
+        assert!(matches!(parse("grit rm --cached x y").unwrap().command, Command::Rm { cached: true, paths, .. } if paths.len() == 2));
+        assert_eq!(parse("grit add").unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_directories_accumulate() {
+        let cli = parse("grit -C one -C two status").unwrap();
+        assert_eq!(cli.directory, vec![PathBuf::from("one"), PathBuf::from("two")]);
+    }
+
+    #[test]
+    fn test_verbosity() {
+        assert_eq!(parse("grit status").unwrap().output.verbosity(), Verbosity::Normal);
+        assert_eq!(parse("grit status -q").unwrap().output.verbosity(), Verbosity::Quiet);
+        assert_eq!(parse("grit -v check-ignore x").unwrap().output.verbosity(), Verbosity::Verbose);
+        assert_eq!(parse("grit -q -v status").unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        assert!(parse("grit diff --no-color").unwrap().output.no_color);
+        assert_eq!(parse("grit diff --color --no-color").unwrap_err().kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_diff_arguments() {
+        let cli = parse("grit diff -U1 --stat HEAD Main").unwrap();
+        assert!(matches!(&cli.command, Command::Diff { unified: Some(1), stat: true, revisions, .. } if revisions == &["HEAD", "Main"]));
+        assert!(matches!(parse("grit diff --cached").unwrap().command, Command::Diff { staged: true, unified: None, .. }));
+        assert!(parse("grit diff HEAD").is_err(), "Commits are compared in pairs");
+        assert!(parse("grit diff --staged HEAD Main").is_err());
+    }
+
+    #[test]
+    fn test_merge_modes_exclude_each_other() {
+        assert!(matches!(parse("grit merge feature").unwrap().command, Command::Merge { rev: Some(_), .. }));
+        assert!(matches!(parse("grit merge --continue").unwrap().command, Command::Merge { continue_merge: true, .. }));
+        assert!(parse("grit merge").is_err());
+        assert!(parse("grit merge feature --abort").is_err());
+        assert!(parse("grit merge --continue --abort").is_err());
+    }
+
+    #[test]
+    fn test_gc_grace_is_validated() {
+        assert!(matches!(parse("grit gc").unwrap().command, Command::Gc { grace: None, .. }));
+        assert!(matches!(parse("grit gc --grace 3d").unwrap().command, Command::Gc { grace: Some(grace), .. } if grace == Duration::from_secs(3 * 24 * 3600)));
+        assert_eq!(parse("grit gc --grace soon").unwrap_err().kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_only_changing_commands_take_the_lock() {
+        for line in ["grit add x", "grit commit -m m", "grit branch new", "grit branch -d old", "grit gc", "grit config set user.name x"] {
+            assert!(parse(line).unwrap().command.changes_repository(), "{}", line);
+        }
+        for line in ["grit status", "grit log", "grit branch", "grit branch --list", "grit fsck", "grit diff", "grit config get user.name", "grit config unset --global user.name"] {
+            assert!(!parse(line).unwrap().command.changes_repository(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_config_scopes() {
+        let cli = parse("grit config set --global user.name Ada").unwrap();
+        assert!(matches!(&cli.command, Command::Config { action: ConfigAction::Set { scope, key, value } }
+            if scope.scope() == Some(Scope::Global) && key == "user.name" && value == "Ada"));
+        let cli = parse("grit config list --show-origin").unwrap();
+        assert!(matches!(&cli.command, Command::Config { action: ConfigAction::List { scope, show_origin: true } } if scope.scope().is_none()));
+        assert_eq!(parse("grit config get --global --local user.name").unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        assert_eq!(parse("grit config set user.name").unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_help_and_usage_errors() {
+        assert_eq!(parse("grit --help").unwrap_err().kind(), ErrorKind::DisplayHelp);
+        assert_eq!(parse("grit commit --help").unwrap_err().exit_code(), 0);
+        let unknown = parse("grit frobnicate").unwrap_err();
+        assert_eq!(unknown.kind(), ErrorKind::InvalidSubcommand);
+        // The same code as GritError::InvalidArgument
+        assert_eq!(unknown.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_completion_scripts() {
+        for (shell, marker) in [(Shell::Bash, "_grit()"), (Shell::Zsh, "#compdef grit"), (Shell::Fish, "complete -c grit")] {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "grit", &mut script);
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains(marker), "{:?}", shell);
+            assert!(script.contains("check-ignore"), "{:?} completes subcommands", shell);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Result of every repository operation.
+pub type Result<T> = std::result::Result<T, GritError>;
+
+/// Underlying error kept as the cause of a more specific one
+pub type Source = Box<dyn Error + Send + Sync>;
+
+/// Everything a grit command can fail with, grouped by what the user can do about it.
+/// Each group has its own exit code, see [`GritError::exit_code`].
+#[derive(Debug)]
+pub enum GritError {
+    /// No `.grit` directory in this directory or any of its parents
+    NotARepository(PathBuf),
+    /// An object or repository file that cannot be decoded or no longer matches its id
+    CorruptObject { what: String, source: Option<Source> },
+    /// A branch, revision, object or path the repository does not have
+    MissingRef(String),
+    /// Unmerged files or a merge in progress stand in the way
+    Conflict(String),
+    /// Local changes would be lost or mixed with the operation
+    DirtyWorkingTree(String),
+    /// A setting or attribute file that cannot be used
+    InvalidConfig { message: String, source: Option<Source> },
+    /// A name, path or option the command cannot accept
+    InvalidArgument(String),
+    /// Another command holds the repository lock
+    Locked(String),
+    /// Reading or writing files failed
+    Io(io::Error),
+}
+
+impl GritError {
+    pub fn corrupt(what: impl Into<String>) -> GritError {
+        GritError::CorruptObject { what: what.into(), source: None }
+    }
+
+    /// Corrupt data, keeping what failed to decode it as the cause
+    pub fn corrupt_from(what: impl Into<String>, source: impl Into<Source>) -> GritError {
+        GritError::CorruptObject { what: what.into(), source: Some(source.into()) }
+    }
+
+    pub fn config(message: impl Into<String>) -> GritError {
+        GritError::InvalidConfig { message: message.into(), source: None }
+    }
+
+    /// Invalid configuration, keeping what rejected it as the cause
+    pub fn config_from(message: impl Into<String>, source: impl Into<Source>) -> GritError {
+        GritError::InvalidConfig { message: message.into(), source: Some(source.into()) }
+    }
+
+    /// The message followed by those of its causes, for reports that fit on one line
+    pub fn with_causes(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = self.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        message
+    }
+
+    /// Exit status of a command failing with this error. These values are stable so
+    /// scripts can tell failures apart; 1 stays for commands that ran but report a
+    /// negative answer, such as conflicts left by a merge.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GritError::InvalidArgument(_) => 2,
+            GritError::NotARepository(_) => 3,
+            GritError::MissingRef(_) => 4,
+            GritError::Conflict(_) => 5,
+            GritError::DirtyWorkingTree(_) => 6,
+            GritError::CorruptObject { .. } => 7,
+            GritError::InvalidConfig { .. } => 8,
+            GritError::Locked(_) => 9,
+            GritError::Io(_) => 10,
+        }
+    }
+}
+
+impl fmt::Display for GritError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GritError::NotARepository(path) => {
+                write!(f, "Not a grit repository (or any of the parent directories): {}", path.display())
+            }
+            GritError::CorruptObject { what, .. } => write!(f, "{}", what),
+            GritError::MissingRef(message)
+            | GritError::Conflict(message)
+            | GritError::DirtyWorkingTree(message)
+            | GritError::InvalidArgument(message)
+            | GritError::Locked(message) => write!(f, "{}", message),
+            GritError::InvalidConfig { message, .. } => write!(f, "{}", message),
+            // Transparent: the I/O error describes itself and has its own causes
+            GritError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for GritError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GritError::CorruptObject { source, .. } | GritError::InvalidConfig { source, .. } => {
+                source.as_deref().map(|source| source as &(dyn Error + 'static))
+            }
+            GritError::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GritError {
+    fn from(e: io::Error) -> GritError {
+        // A grit error that travelled through an I/O boundary comes back as what it was
+        if e.get_ref().is_some_and(|inner| inner.is::<GritError>()) {
+            let inner = e.into_inner().expect("the error has an inner error");
+            return *inner.downcast::<GritError>().expect("the inner error is a GritError");
+        }
+        GritError::Io(e)
+    }
+}
+
+impl From<GritError> for io::Error {
+    fn from(e: GritError) -> io::Error {
+        match e {
+            GritError::Io(e) => e,
+            other => io::Error::other(other),
+        }
+    }
+}
+
+impl From<serde_json::Error> for GritError {
+    fn from(e: serde_json::Error) -> GritError {
+        GritError::corrupt_from("Invalid JSON data", e)
+    }
+}
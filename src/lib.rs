@@ -0,0 +1,25 @@
+//! Grit, a lightweight Git-like version control system.
+//!
+//! Everything the `grit` command does is available through [`Repository`], which finds
+//! every file from the repository it was opened on rather than the process's working
+//! directory:
+//!
+//! ```no_run
+//! use grit::Repository;
+//!
+//! let repo = Repository::discover("path/to/project")?;
+//! repo.add(None)?;
+//! repo.commit("Update the notes", &repo.identity()?)?;
+//! print!("{}", repo.log(false)?);
+//! # Ok::<(), grit::GritError>(())
+//! ```
+
+pub mod error;
+pub mod systems;
+pub mod structure;
+pub mod algorithms;
+#[cfg(test)]
+mod test_support;
+
+pub use error::{GritError, Result};
+pub use systems::repository::Repository;
@@ -1,78 +1,483 @@
 use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-use crate::systems::add::add;
-use crate::systems::commits::commit::Commit;
-use crate::systems::commits::log::log;
-use crate::systems::init::{init_grit, update_branch};
+use clap::{CommandFactory, Parser};
+use grit::{GritError, Repository};
+use grit::systems::checkout::{checkout, restore, switch};
+use grit::systems::branch::{branch_list, create_branch, delete_branch, rename_branch};
+use grit::systems::commits::log::commit_stat;
+use grit::systems::objects::cat::cat_file;
+use grit::systems::objects::pack::repack;
+use grit::systems::objects::tree::Change;
+use grit::systems::identity::Identity;
+use grit::systems::index::{remove, remove_cached, reset};
+use grit::systems::filters::ignore::check_ignore;
+use grit::systems::config::{check_key, edit_config, process_env, Config, ConfigFile, Scope};
+use grit::systems::diff::{diff, format_numstat, format_patch, format_stat, DiffOptions, DiffTarget, DEFAULT_CONTEXT};
+use grit::systems::atomic::RepositoryLock;
+use grit::systems::init::{is_grit_repo, init_grit};
+use grit::systems::fsck::{format_fsck, fsck};
+use grit::systems::gc::{format_gc, gc, DEFAULT_GRACE};
+use grit::systems::refs::migrate_legacy_head;
+use grit::systems::merge::{abort_merge, continue_merge, merge, merge_file, MergeOutcome};
+use grit::algorithms::vcompare::merge::ConflictStyle;
+use grit::systems::status::{format_porcelain, format_status};
+use crate::cli::{BranchArgs, Cli, Command, ConfigAction, OutputArgs, Verbosity};
 
-mod systems;
-mod structure;
-mod algorithms;
+mod cli;
+#[cfg(test)]
+mod cli_test;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // Usage errors exit with 2, like `GritError::InvalidArgument`, and --help with 0
+    let cli = Cli::parse();
 
-    if args.len() < 2 {
-        eprintln!("Usage: grit <command> [args]");
-        process::exit(1);
+    let code = match start_dir(&cli.directory) {
+        Ok(dir) => run(&cli, &dir),
+        Err(e) => fail("Error", &e),
+    };
+    process::exit(code);
+}
+
+/// Directory the command runs in: the current one, moved by every `-C` in turn
+fn start_dir(directories: &[PathBuf]) -> Result<PathBuf, GritError> {
+    let dir = directories.iter().fold(env::current_dir()?, |dir, next| dir.join(next));
+    if !dir.is_dir() {
+        return Err(GritError::InvalidArgument(format!("Cannot change to '{}': not a directory", dir.display())));
     }
+    Ok(dir)
+}
 
-    match args[1].as_str() {
-        "init" => {
-            if let Err(e) = init_grit() {
-                eprintln!("Error initializing repository: {}", e);
-                process::exit(1);
-            }
-            println!("Initialized empty Grit repository");
+/// Reports a failed command with the causes of its error, returning the exit code of the
+/// error's category
+fn fail(context: &str, e: &GritError) -> i32 {
+    eprintln!("{}: {}", context, e);
+    let mut source = e.source();
+    while let Some(cause) = source {
+        eprintln!("  caused by: {}", cause);
+        source = cause.source();
+    }
+    e.exit_code()
+}
+
+/// Where a command prints: what it was asked for always, what it did unless `--quiet`, and
+/// the details only with `--verbose`
+struct Output {
+    verbosity: Verbosity,
+    color: bool,
+}
+
+impl Output {
+    fn new(args: &OutputArgs) -> Self {
+        let color = !args.no_color && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        Output { verbosity: args.verbosity(), color }
+    }
+
+    /// A line telling what the command did
+    fn info(&self, line: impl Display) {
+        if self.verbosity > Verbosity::Quiet {
+            println!("{}", line);
         }
-        "add" => {
-            if args.len() < 3 {
-                eprintln!("Usage: grit add <file>");
-                process::exit(1);
+    }
+
+    /// A line only printed with `--verbose`
+    fn detail(&self, line: impl Display) {
+        if self.verbosity == Verbosity::Verbose {
+            println!("{}", line);
+        }
+    }
+
+    fn verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+}
+
+/// Runs a command from `dir`, returning the exit code
+fn run(cli: &Cli, dir: &Path) -> i32 {
+    let out = Output::new(&cli.output);
+    match &cli.command {
+        Command::Init => return init(dir, &out),
+        Command::MergeFile { stdout, diff3, ours, base, theirs } => {
+            let style = if *diff3 { ConflictStyle::Diff3 } else { ConflictStyle::Merge };
+            let [ours, base, theirs] = [ours, base, theirs].map(|path| dir.join(path).to_string_lossy().into_owned());
+            return merge_files(&ours, &base, &theirs, style, *stdout);
+        }
+        Command::Completions { shell } => {
+            let mut script = Vec::new();
+            clap_complete::generate(*shell, &mut Cli::command(), "grit", &mut script);
+            if let Err(e) = io::stdout().write_all(&script) {
+                return fail("Error writing completions", &e.into());
             }
+            return 0;
+        }
+        _ => {}
+    }
+
+    let repo = match (Repository::discover(dir), &cli.command) {
+        (Ok(repo), _) => repo,
+        // Only the system and global settings are left outside a repository
+        (Err(GritError::NotARepository(_)), Command::Config { action }) => return config(None, dir, action),
+        (Err(e), _) => return fail("Error", &e),
+    };
+    // Commands that change the repository hold its lock until they are done, so two of
+    // them never interleave their writes
+    let lock = match cli.command.changes_repository() {
+        true => match RepositoryLock::acquire(repo.grit_dir()) {
+            Ok(lock) => Some(lock),
+            Err(e) => return fail("Error", &e),
+        },
+        false => None,
+    };
+    // A HEAD left by older versions is only upgraded by commands holding the lock
+    if lock.is_some() {
+        if let Err(e) = migrate_legacy_head(&repo) {
+            return fail("Error", &e);
+        }
+    }
+    run_in(&repo, &cli.command, &out)
+}
+
+/// Creates a repository in `dir`
+fn init(dir: &Path, out: &Output) -> i32 {
+    let existed = is_grit_repo(dir);
+    match init_grit(dir) {
+        Ok(repo) if existed => out.info(format!("Reinitialized existing Grit repository in {}", repo.grit_dir().display())),
+        Ok(repo) => out.info(format!("Initialized empty Grit repository in {}", repo.grit_dir().display())),
+        Err(e) => return fail("Error initializing repository", &e),
+    }
+    0
+}
 
-            let file_arg = if args[2] == "." { None } else { Some(args[2].as_str()) };
+/// `grit merge-file`, which works on any three files and needs no repository
+fn merge_files(ours: &str, base: &str, theirs: &str, style: ConflictStyle, to_stdout: bool) -> i32 {
+    match merge_file(ours, base, theirs, style, to_stdout) {
+        Ok(result) if result.is_clean() => 0,
+        // Like git, the exit code is the number of conflicts
+        Ok(result) => result.conflicts.len().min(127) as i32,
+        // Conflict counts take the low codes, failures are told apart by 255 as in git
+        Err(e) => {
+            fail("Error merging files", &e);
+            255
+        }
+    }
+}
 
-            if let Err(e) = add(file_arg) {
-                eprintln!("Error adding file: {}", e);
-                process::exit(1);
+/// Runs a command inside a repository, returning the exit code
+fn run_in(repo: &Repository, command: &Command, out: &Output) -> i32 {
+    let short = |id: &str| id[..7.min(id.len())].to_string();
+
+    match command {
+        Command::Add { all, paths } => {
+            let targets: Vec<Option<&str>> = match all {
+                true => vec![None],
+                false => paths.iter().map(|path| Some(path.as_str())).collect(),
+            };
+            for target in targets {
+                match repo.add(target) {
+                    Ok(changes) => {
+                        for (path, change) in changes {
+                            let action = match change {
+                                Change::Added => "Added",
+                                Change::Deleted => "Removed",
+                                _ => "Modified",
+                            };
+                            out.info(format!("{}: {}", action, path.display()));
+                        }
+                    }
+                    Err(e) => return fail(&format!("Error adding {}", target.unwrap_or(".")), &e),
+                }
             }
-            println!("Added: {}", args[2]);
         }
-        "commit" => {
-            if args.len() < 4 || args[2] != "-m" {
-                eprintln!("Usage: grit commit -m \"message\"");
-                process::exit(1);
+        Command::Rm { cached, force, paths } => {
+            for path in paths {
+                let result = if *cached { remove_cached(repo, path) } else { remove(repo, path, *force) };
+                match result {
+                    Ok(removed) => removed.iter().for_each(|file| out.info(format!("rm '{}'", file.display()))),
+                    Err(e) => return fail(&format!("Error removing {}", path), &e),
+                }
             }
-
-            let message = &args[3];
-            match Commit::new(message, "Author") {
+        }
+        Command::Reset { paths } => {
+            for path in paths {
+                match reset(repo, path) {
+                    Ok(unstaged) => unstaged.iter().for_each(|file| out.info(format!("Unstaged: {}", file.display()))),
+                    Err(e) => return fail(&format!("Error resetting {}", path), &e),
+                }
+            }
+        }
+        Command::Commit { message, author } => {
+            let identity = match Identity::resolve(repo, author.clone()) {
+                Ok(identity) => identity,
+                Err(e) => return fail("Error committing", &e),
+            };
+            match repo.commit(message, &identity) {
                 Ok(Some(commit)) => {
-                    println!("Committed: {}", commit.id);
+                    out.info(format!("Committed: {}", commit.id));
+                    if out.verbose() {
+                        match commit_stat(repo, &repo.objects(), &commit) {
+                            Ok(stat) => print!("{}", stat),
+                            Err(e) => return fail("Error reading the commit", &e),
+                        }
+                    }
                 }
-                Ok(None) => {
-                    println!("No more changes to commit.");
+                Ok(None) => out.info("No more changes to commit."),
+                Err(e) => return fail("Error committing", &e),
+            }
+        }
+        Command::Log { stat } => {
+            match repo.log(*stat) {
+                Ok(log) => print!("{}", log),
+                Err(e) => return fail("Error reading history", &e),
+            }
+        }
+        Command::Status { porcelain } => {
+            match repo.status() {
+                Ok(status) if *porcelain => print!("{}", format_porcelain(&status)),
+                Ok(status) => print!("{}", format_status(&status)),
+                Err(e) => return fail("Error reading status", &e),
+            }
+        }
+        Command::Diff { staged, stat, numstat, unified, color, revisions } => {
+            let context = match unified {
+                Some(unified) => *unified,
+                None => match Config::for_repository(repo) {
+                    Ok(config) => config.count("diff.context").map_or(DEFAULT_CONTEXT, |count| count as usize),
+                    Err(e) => return fail("Error reading the configuration", &e),
+                },
+            };
+            let options = DiffOptions { color: *color || out.color, context };
+            let target = match (staged, revisions.as_slice()) {
+                (true, _) => DiffTarget::Staged,
+                (false, [old, new]) => DiffTarget::Commits(old.clone(), new.clone()),
+                _ => DiffTarget::WorkingTree,
+            };
+
+            match diff(repo, &target) {
+                Ok(diffs) if *stat => if !diffs.is_empty() { print!("{}", format_stat(&diffs)) },
+                Ok(diffs) if *numstat => print!("{}", format_numstat(&diffs)),
+                Ok(diffs) => print!("{}", format_patch(&diffs, &options)),
+                Err(e) => return fail("Error computing diff", &e),
+            }
+        }
+        Command::Branch(args) => {
+            if let Err(e) = branch(repo, args, out) {
+                return fail("Error", &e);
+            }
+        }
+        Command::Switch { create, force, branch } => {
+            let result = if *create { create_branch(repo, branch, None).map(|_| ()) } else { Ok(()) }
+                .and_then(|_| switch(repo, branch, *force));
+            match result {
+                Ok(_) => out.info(format!("Switched to branch '{}'", branch)),
+                Err(e) => return fail("Error switching branch", &e),
+            }
+        }
+        Command::Checkout { force, rev } => {
+            match checkout(repo, rev, *force) {
+                Ok(commit) => out.info(format!("HEAD is now at {} {}", short(&commit.id), commit.message)),
+                Err(e) => return fail("Error checking out", &e),
+            }
+        }
+        Command::Restore { source, paths } => {
+            for path in paths {
+                match restore(repo, path, source.as_deref()) {
+                    Ok(restored) => restored.iter().for_each(|file| out.info(format!("Restored: {}", file.display()))),
+                    Err(e) => return fail(&format!("Error restoring {}", path), &e),
                 }
-                Err(e) => {
-                    eprintln!("Error committing: {}", e);
-                    process::exit(1);
+            }
+        }
+        Command::Merge { rev, continue_merge: resume, abort } => {
+            let outcome = match (rev, resume, abort) {
+                (_, _, true) => match abort_merge(repo) {
+                    Ok(()) => {
+                        out.info("Merge aborted");
+                        return 0;
+                    }
+                    Err(e) => Err(e),
+                },
+                (_, true, _) => repo.identity().and_then(|identity| continue_merge(repo, &identity)).map(MergeOutcome::Merged),
+                (Some(rev), _, _) => merge(repo, rev, || repo.identity()),
+                (None, _, _) => unreachable!("clap requires a branch without --continue or --abort"),
+            };
+
+            match outcome {
+                Ok(MergeOutcome::UpToDate) => out.info("Already up to date."),
+                Ok(MergeOutcome::FastForward(commit)) => out.info(format!("Fast-forward to {} {}", short(&commit.id), commit.message)),
+                Ok(MergeOutcome::Merged(commit)) => out.info(format!("Merge made: {} {}", short(&commit.id), commit.message)),
+                Ok(MergeOutcome::Conflicts(conflicts)) => {
+                    for (path, stages) in conflicts {
+                        println!("CONFLICT ({}): {}", stages.describe(), path.display());
+                    }
+                    println!("Automatic merge failed; fix conflicts, add the files, then run \"grit merge --continue\".");
+                    return 1;
                 }
+                Err(e) => return fail("Error merging", &e),
             }
         }
-        "branch" => {
-            if args.len() < 4 || args[2] != "-M" {
-                eprintln!("Usage: grit branch -M \"branch name\"");
-                process::exit(1);
+        Command::CheckIgnore { paths } => {
+            let mut any_ignored = false;
+            for path in paths {
+                match check_ignore(repo, path) {
+                    Ok(Some(pattern)) if out.verbose() => {
+                        any_ignored |= !pattern.negated;
+                        println!("{}:{}:{}\t{}", pattern.source.display(), pattern.line, pattern.text, path);
+                    }
+                    Ok(Some(pattern)) if !pattern.negated => {
+                        any_ignored = true;
+                        println!("{}", path);
+                    }
+                    Ok(_) => {}
+                    Err(e) => return fail(&format!("Error checking {}", path), &e),
+                }
+            }
+            // Like grep, success means something matched
+            if !any_ignored {
+                return 1;
+            }
+        }
+        Command::CatFile { kind, object } => {
+            if let Err(e) = cat_file(repo, object, *kind) {
+                return fail("Error reading object", &e);
             }
-            let branch = &args[3];
-            update_branch(branch).expect("Failed to update branch");
         }
-        "log" => {
-            log().expect("An error occurred that's all we know");
+        Command::Repack => {
+            match repack(repo) {
+                Ok(Some(stats)) => {
+                    let name = stats.path.file_name().unwrap_or_default().to_string_lossy();
+                    out.info(format!("Packed {} objects ({} as deltas) into {}, {} bytes", stats.objects, stats.deltas, name, stats.size));
+                }
+                Ok(None) => out.info("Nothing to pack"),
+                Err(e) => return fail("Error repacking", &e),
+            }
         }
-        _ => {
-            eprintln!("Unknown command: {}", args[1]);
-            process::exit(1);
+        Command::Gc { dry_run, grace } => {
+            let grace = match grace {
+                Some(grace) => *grace,
+                None => match Config::for_repository(repo) {
+                    Ok(config) => config.duration("gc.grace").unwrap_or(DEFAULT_GRACE),
+                    Err(e) => return fail("Error reading the configuration", &e),
+                },
+            };
+            match gc(repo, grace, *dry_run) {
+                // A dry run is asked for its list, which is printed whatever the verbosity
+                Ok(summary) if *dry_run => print!("{}", format_gc(&summary, true)),
+                Ok(summary) => {
+                    summary.commits.iter().for_each(|id| out.detail(format!("Removed commit {}", id)));
+                    summary.objects.iter().for_each(|id| out.detail(format!("Removed object {}", id)));
+                    summary.versions.iter().for_each(|file| out.detail(format!("Removed {}", file.display())));
+                    out.info(format_gc(&summary, false).trim_end());
+                }
+                Err(e) => return fail("Error collecting garbage", &e),
+            }
+        }
+        Command::Config { action } => return config(Some(repo), repo.current_dir(), action),
+        Command::Fsck => {
+            match fsck(repo) {
+                Ok(report) => {
+                    print!("{}", format_fsck(&report));
+                    if !report.is_ok() {
+                        return 1;
+                    }
+                }
+                Err(e) => return fail("Error checking the repository", &e),
+            }
+        }
+        Command::Init | Command::MergeFile { .. } | Command::Completions { .. } => {
+            unreachable!("run without a repository")
+        }
+    }
+
+    0
+}
+
+/// Runs `grit config`, in `repo` or outside any repository
+fn config(repo: Option<&Repository>, dir: &Path, action: &ConfigAction) -> i32 {
+    let (ConfigAction::Get { scope, .. } | ConfigAction::Set { scope, .. } | ConfigAction::Unset { scope, .. } | ConfigAction::List { scope, .. }) = action;
+    let scope = scope.scope();
+    // Changes go to the repository's file unless another one is chosen
+    let writes = matches!(action, ConfigAction::Set { .. } | ConfigAction::Unset { .. });
+    let in_repository = scope.map_or(writes, |scope| scope >= Scope::Repository);
+    if repo.is_none() && in_repository {
+        return fail("Error", &GritError::NotARepository(dir.to_path_buf()));
+    }
+    let grit_dir = repo.map(Repository::grit_dir);
+    let load = || match scope {
+        Some(scope) => Config::load_scope(scope, grit_dir, &process_env),
+        None => Config::load(grit_dir, &process_env),
+    };
+    let origin = |file: &ConfigFile| format!("{}:{}\t", file.scope.name(), file.path.display());
+
+    match action {
+        ConfigAction::Get { show_origin, key, .. } => {
+            let config = match check_key(key).and_then(|_| load()) {
+                Ok(config) => config,
+                Err(e) => return fail("Error reading the configuration", &e),
+            };
+            match config.get_with_origin(key) {
+                Some((value, file)) if *show_origin => println!("{}{}", origin(file), value),
+                Some((value, _)) => println!("{}", value),
+                None => return 1,
+            }
+        }
+        ConfigAction::List { show_origin, .. } => {
+            let config = match load() {
+                Ok(config) => config,
+                Err(e) => return fail("Error reading the configuration", &e),
+            };
+            for file in config.files() {
+                let prefix = if *show_origin { origin(file) } else { String::new() };
+                file.values().for_each(|(key, value)| println!("{}{}={}", prefix, key, value));
+            }
+        }
+        ConfigAction::Set { key, value, .. } => {
+            let scope = scope.unwrap_or(Scope::Repository);
+            if let Err(e) = edit_config(repo, scope, &process_env, |file| file.set(key, value)) {
+                return fail("Error changing the configuration", &e);
+            }
+        }
+        ConfigAction::Unset { key, .. } => {
+            let scope = scope.unwrap_or(Scope::Repository);
+            match edit_config(repo, scope, &process_env, |file| file.unset(key)) {
+                Ok(true) => {}
+                Ok(false) => return 1,
+                Err(e) => return fail("Error changing the configuration", &e),
+            }
+        }
+    }
+    0
+}
+
+/// `grit branch`: lists without names, otherwise creates, deletes or renames
+fn branch(repo: &Repository, args: &BranchArgs, out: &Output) -> Result<(), GritError> {
+    let names: Vec<&str> = args.names.iter().map(String::as_str).collect();
+    let usage = || GritError::InvalidArgument(
+        "Usage: grit branch [--list] | <name> [<start>] | -d|-D <name> | -m|-M [<old>] <new>".to_string()
+    );
+
+    if args.delete || args.force_delete {
+        let [name] = names.as_slice() else { return Err(usage()) };
+        delete_branch(repo, name, args.force_delete)?;
+        out.info(format!("Deleted branch {}", name));
+    } else if args.rename || args.force_rename {
+        match names.as_slice() {
+            [new] => rename_branch(repo, None, new, args.force_rename)?,
+            [old, new] => rename_branch(repo, Some(old), new, args.force_rename)?,
+            _ => return Err(usage()),
+        }
+        out.info(format!("Branch renamed to {}", names[names.len() - 1]));
+    } else if args.list || names.is_empty() {
+        for (name, commit, is_current) in branch_list(repo)? {
+            match out.verbose() {
+                true => println!("{} {} {}", if is_current { "*" } else { " " }, name, &commit[..7.min(commit.len())]),
+                false => println!("{} {}", if is_current { "*" } else { " " }, name),
+            }
         }
+    } else {
+        let id = create_branch(repo, names[0], names.get(1).copied())?;
+        out.detail(format!("Created branch {} at {}", names[0], &id[..7.min(id.len())]));
     }
+    Ok(())
 }
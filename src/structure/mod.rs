@@ -1,6 +1,7 @@
+#[cfg(test)]
 mod test;
-pub(super) mod node;
-pub(super) mod ptree;
-pub(super) mod operations;
-pub(super) mod transversal;
-pub(super) mod serialization;
\ No newline at end of file
+pub mod node;
+pub mod ptree;
+pub mod operations;
+pub mod transversal;
+pub mod serialization;
\ No newline at end of file
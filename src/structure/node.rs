@@ -1,8 +1,81 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
 use serde::{Deserialize, Serialize};
+use crate::error::Result;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Node {
-    File { hash: String, modified: bool },
+    File {
+        hash: String,
+        modified: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stat: Option<FileStat>,
+    },
     Directory { children: HashMap<String, Node> },
-}
\ No newline at end of file
+}
+
+/// Size, modification time and permissions of a file when it was staged, so an unchanged
+/// file can be recognised without hashing it again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStat {
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch
+    pub mtime: u64,
+    pub mode: u32,
+}
+
+impl FileStat {
+    pub fn of(path: &Path) -> Result<FileStat> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Ok(FileStat { size: metadata.len(), mtime, mode: file_mode(&metadata) })
+    }
+}
+
+/// Blob ids of the three versions of a file a merge could not combine, `None` for a side
+/// where the file does not exist. Kept in the staging tree until the file is added again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unmerged {
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl Unmerged {
+    /// How the two sides disagree, as `grit status` words it
+    pub fn describe(&self) -> &'static str {
+        match (&self.base, &self.ours, &self.theirs) {
+            (_, None, _) => "deleted by us",
+            (_, _, None) => "deleted by them",
+            (None, _, _) => "both added",
+            _ => "both modified",
+        }
+    }
+
+    /// The two letters `grit status --porcelain` shows
+    pub fn code(&self) -> [char; 2] {
+        match (&self.base, &self.ours, &self.theirs) {
+            (_, None, _) => ['D', 'U'],
+            (_, _, None) => ['U', 'D'],
+            (None, _, _) => ['A', 'A'],
+            _ => ['U', 'U'],
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() { 0o100444 } else { 0o100644 }
+}
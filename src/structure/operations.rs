@@ -1,38 +1,74 @@
-use std::{fs, io};
-use super::node::Node;
-use sha1::{Sha1, Digest};
+use std::fs;
+use super::node::{FileStat, Node};
 use super::ptree::ProjectTree;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use hex::encode;
+use crate::systems::objects::object::{hash_object, ObjectKind};
+use crate::systems::objects::tree::Change;
+use crate::error::{GritError, Result};
 
-pub fn compute_hash(path: &Path) -> io::Result<String> {
+/// Hash a file the same way the object store does, so the result is its blob id
+pub fn compute_hash(path: &Path) -> Result<String> {
     let content = fs::read(path)?;
-    let mut hasher = Sha1::new();
-    hasher.update(&content);
-    let result = hasher.finalize();
-    Ok(encode(&result))
+    Ok(hash_object(ObjectKind::Blob, &content))
 }
 
-pub fn add_file(tree: &mut ProjectTree, rel_path: &Path, current_hash: String) -> io::Result<()> {
+/// Stage a file, returning whether it was added or modified, `None` when its content
+/// is already staged
+pub fn add_file(tree: &mut ProjectTree, rel_path: &Path, current_hash: String, current_stat: FileStat) -> Result<Option<Change>> {
+    let (file_name, dir_components) = split_path(rel_path)?;
+    let parent_node = get_or_create_parent_node(&mut tree.root, dir_components)?;
+
+    match parent_node {
+        Node::Directory { children } => Ok(update_or_insert_file(children, file_name, current_hash, current_stat)),
+        _ => Ok(None),
+    }
+}
+
+/// Record a file as already committed, without reporting it as added or modified
+pub fn set_file(tree: &mut ProjectTree, rel_path: &Path, hash: String) -> Result<()> {
     let (file_name, dir_components) = split_path(rel_path)?;
     let parent_node = get_or_create_parent_node(&mut tree.root, dir_components)?;
 
     if let Node::Directory { children } = parent_node {
-        update_or_insert_file(children, file_name, current_hash, rel_path, &tree.base_path);
+        children.insert(file_name.to_string(), Node::File { hash, modified: false, stat: None });
     }
 
     Ok(())
 }
 
-pub fn add_all(tree: &mut ProjectTree, rel_path: &Path) -> io::Result<()> {
+/// Remove a file, or a directory with everything below it, along with the directories
+/// it leaves empty. Returns whether anything was removed.
+pub fn remove_path(node: &mut Node, components: &[&str]) -> bool {
+    let (Some((first, rest)), Node::Directory { children }) = (components.split_first(), node) else {
+        return false;
+    };
+
+    if rest.is_empty() {
+        children.remove(*first).is_some()
+    } else {
+        match children.get_mut(*first) {
+            Some(child) => {
+                let removed = remove_path(child, rest);
+                if matches!(child, Node::Directory { children } if children.is_empty()) {
+                    children.remove(*first);
+                }
+                removed
+            }
+            None => false,
+        }
+    }
+}
+
+pub fn add_all(tree: &mut ProjectTree, rel_path: &Path) -> Result<Vec<(PathBuf, Change)>> {
     let abs_path = tree.base_path.join(rel_path);
+    let mut changes = Vec::new();
     for entry in fs::read_dir(abs_path)? {
         let entry = entry?;
         let entry_path = entry.path();
-        tree.add(&entry_path)?;
+        changes.extend(tree.add(&entry_path)?);
     }
-    Ok(())
+    Ok(changes)
 }
 
 pub fn get_node<'a>(root: &'a Node, rel_path: &Path) -> Option<&'a Node> {
@@ -54,14 +90,14 @@ pub fn get_node<'a>(root: &'a Node, rel_path: &Path) -> Option<&'a Node> {
 }
 
 
-fn split_path(rel_path: &Path) -> io::Result<(&str, Vec<&str>)> {
+fn split_path(rel_path: &Path) -> Result<(&str, Vec<&str>)> {
     let components: Vec<&str> = rel_path.iter().filter_map(|c| c.to_str()).collect();
     let (file_name, dir_components) = components.split_last()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty path"))?;
+        .ok_or_else(|| GritError::InvalidArgument("Empty path".to_string()))?;
     Ok((file_name, dir_components.to_vec()))
 }
 
-fn get_or_create_parent_node<'a>(root: &'a mut Node, dir_components: Vec<&str>) -> io::Result<&'a mut Node> {
+fn get_or_create_parent_node<'a>(root: &'a mut Node, dir_components: Vec<&str>) -> Result<&'a mut Node> {
     let mut current = root;
 
     for component in dir_components {
@@ -73,28 +109,31 @@ fn get_or_create_parent_node<'a>(root: &'a mut Node, dir_components: Vec<&str>)
                     children: HashMap::new(),
                 });
         } else {
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid path: expected directory"));
+            return Err(GritError::InvalidArgument(format!("Invalid path: {} is not a directory", component)));
         }
     }
 
     Ok(current)
 }
 
-fn update_or_insert_file(children: &mut HashMap<String, Node>, file_name: &str, current_hash: String, rel_path: &Path, base_path: &PathBuf) {
+fn update_or_insert_file(children: &mut HashMap<String, Node>, file_name: &str, current_hash: String, current_stat: FileStat) -> Option<Change> {
     match children.get_mut(file_name) {
-        Some(Node::File { hash, modified }) => {
-            if *hash != current_hash {
-                println!("Modified: {}", base_path.join(rel_path).display());
-                *hash = current_hash;
-                *modified = true;
+        Some(Node::File { hash, modified, stat }) => {
+            *stat = Some(current_stat);
+            if *hash == current_hash {
+                return None;
             }
+            *hash = current_hash;
+            *modified = true;
+            Some(Change::Modified)
         }
         _ => {
-            println!("Added: {}", base_path.join(rel_path).display());
             children.insert(file_name.to_string(), Node::File {
                 hash: current_hash,
                 modified: true, // New files are considered as "modified"
+                stat: Some(current_stat),
             });
+            Some(Change::Added)
         }
     }
 }
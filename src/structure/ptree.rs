@@ -1,50 +1,66 @@
-use std::io;
-use super::node::Node;
-use std::collections::HashMap;
+use super::node::{FileStat, Node, Unmerged};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use super::operations::{
     compute_hash, add_all,
-    add_file, get_node
+    add_file, get_node, remove_path, set_file
 };
-use super::transversal::{traverse, traverse_modified};
+use crate::error::{GritError, Result};
+use crate::systems::objects::tree::Change;
+use super::transversal::{clear_modified, traverse};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectTree {
     pub(crate) root: Node,
     pub(crate) base_path: PathBuf,
+    /// Files left conflicted by a merge, by path from the root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) unmerged: BTreeMap<PathBuf, Unmerged>,
 }
 
 impl ProjectTree {
     /// Create a new project tree rooted at the given path
-    pub fn new(base_path: impl Into<PathBuf>) -> io::Result<Self> {
+    pub fn new(base_path: impl Into<PathBuf>) -> Result<Self> {
         let base_path = base_path.into().canonicalize()?;
         Ok(ProjectTree {
             root: Node::Directory { children: HashMap::new() },
             base_path,
+            unmerged: BTreeMap::new(),
         })
     }
 
-    /// Add a file or directory to the tree
-    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+    /// Create a tree holding exactly the files of a commit snapshot, none of them modified
+    pub fn from_snapshot(base_path: impl Into<PathBuf>, snapshot: &BTreeMap<PathBuf, String>) -> Result<Self> {
+        let mut tree = ProjectTree::new(base_path)?;
+        for (path, hash) in snapshot {
+            set_file(&mut tree, path, hash.clone())?;
+        }
+        Ok(tree)
+    }
+
+    /// Add a file or directory to the tree, returning the files whose staged content changed
+    pub fn add(&mut self, path: &Path) -> Result<Vec<(PathBuf, Change)>> {
         let abs_path = path.canonicalize()?;
         let relative_path = abs_path.strip_prefix(&self.base_path)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path is outside project directory"))?;
+            .map_err(|_| GritError::InvalidArgument(format!("{} is outside project directory", path.display())))?;
 
         if abs_path.is_file() {
             let current_hash = compute_hash(&abs_path)?;
-            add_file(self, relative_path, current_hash)?;
+            let change = add_file(self, relative_path, current_hash, FileStat::of(&abs_path)?)?;
+            // Adding a conflicted file marks it resolved
+            self.unmerged.remove(relative_path);
+            Ok(change.map(|change| (relative_path.to_path_buf(), change)).into_iter().collect())
         } else if abs_path.is_dir() {
-            add_all(self, relative_path)?;
+            add_all(self, relative_path)
+        } else {
+            Ok(Vec::new())
         }
-        Ok(())
     }
 
     /// Get file hash by relative path, ignoring modification status
-    /// Get file hash by relative path, allowing modification of the project tree
-    pub fn get_file_hash(&mut self, rel_path: &Path) -> Option<String> {
-        // Borrow the node mutably, but avoid returning a reference that outlives the borrow
-        get_node(&mut self.root, rel_path).and_then(|node| {
+    pub fn get_file_hash(&self, rel_path: &Path) -> Option<String> {
+        get_node(&self.root, rel_path).and_then(|node| {
             if let Node::File { hash, .. } = node {
                 Some(hash.clone()) // Clone the hash to return it safely
             } else {
@@ -53,6 +69,38 @@ impl ProjectTree {
         })
     }
 
+    /// Stat info recorded when the file was staged, `None` for entries that were not
+    /// read from the working directory, such as those restored from a commit
+    pub fn get_file_stat(&self, rel_path: &Path) -> Option<FileStat> {
+        match get_node(&self.root, rel_path) {
+            Some(Node::File { stat, .. }) => stat.clone(),
+            _ => None,
+        }
+    }
+
+    /// Record a file with a known hash, as committed and without stat info
+    pub fn set(&mut self, rel_path: &Path, hash: String) -> Result<()> {
+        set_file(self, rel_path, hash)
+    }
+
+    /// Stop tracking a file, or every file below a directory. Returns whether the
+    /// path was tracked.
+    pub fn remove(&mut self, rel_path: &Path) -> bool {
+        self.unmerged.retain(|path, _| !path.starts_with(rel_path));
+        let components: Vec<&str> = rel_path.iter().filter_map(|c| c.to_str()).collect();
+        remove_path(&mut self.root, &components)
+    }
+
+    /// Record that a merge left a file conflicted
+    pub fn set_unmerged(&mut self, rel_path: &Path, stages: Unmerged) {
+        self.unmerged.insert(rel_path.to_path_buf(), stages);
+    }
+
+    /// Conflicted files not resolved yet, sorted
+    pub fn unmerged(&self) -> &BTreeMap<PathBuf, Unmerged> {
+        &self.unmerged
+    }
+
     /// Check if a path exists in the tree
     pub fn exists(&self, rel_path: &Path) -> bool {
         get_node(&self.root, rel_path).is_some()
@@ -65,12 +113,16 @@ impl ProjectTree {
         files
     }
 
-    /// Get a list of modified files
+    /// Map every tracked file to its blob hash
+    pub fn snapshot(&self) -> BTreeMap<PathBuf, String> {
+        self.list_files()
+            .into_iter()
+            .filter_map(|path| self.get_file_hash(&path).map(|hash| (path, hash)))
+            .collect()
+    }
 
-    #[allow(dead_code)]
-    pub fn get_modified_files(&mut self) -> Vec<PathBuf> {
-        let mut modified_files = Vec::new();
-        traverse_modified(&mut self.root, PathBuf::new(), &mut modified_files);
-        modified_files
+    /// Mark every file as unmodified, once its content is committed
+    pub fn clear_modified(&mut self) {
+        clear_modified(&mut self.root);
     }
 }
\ No newline at end of file
@@ -1,38 +1,46 @@
-use serde_json;
-use std::path::PathBuf;
+use std::path::Path;
 use super::ptree::ProjectTree;
-use std::{fs::{self, File}, io};
+use crate::systems::atomic::write_atomic;
+use crate::systems::repository::Repository;
+use std::fs::{self, File};
+use crate::error::Result;
 
-const DEFAULT_GRIT_TREE_FILE: &str = ".grit/tree.json";
+/// Name of the staging tree inside `.grit`
+pub const TREE_FILE: &str = "tree.json";
 
-pub fn save(tree: &ProjectTree, file_path: Option<PathBuf>) -> io::Result<()> {
-    let path: PathBuf = file_path.unwrap_or_else(|| PathBuf::from(DEFAULT_GRIT_TREE_FILE));
+/// Write the staging tree of a repository
+pub fn save(repo: &Repository, tree: &ProjectTree) -> Result<()> {
+    save_to(tree, &repo.grit_path(TREE_FILE))
+}
+
+/// Read the staging tree of a repository
+pub fn load(repo: &Repository) -> Result<ProjectTree> {
+    load_from(&repo.grit_path(TREE_FILE), repo.root())
+}
 
+pub fn save_to(tree: &ProjectTree, path: &Path) -> Result<()> {
     // Ensure the .grit directory exists before writing
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
 
     let json = serde_json::to_string_pretty(tree)?;
-    fs::write(path, json)?;
+    write_atomic(path, json)?;
     Ok(())
 }
 
-pub fn load(file_path: Option<PathBuf>) -> io::Result<ProjectTree> {
-    let path: PathBuf = file_path.unwrap_or_else(|| PathBuf::from(DEFAULT_GRIT_TREE_FILE));
-
+/// Read a tree from `path`, a tree of `base_path` with nothing in it when the file is new
+pub fn load_from(path: &Path, base_path: &Path) -> Result<ProjectTree> {
     // Ensure the .grit directory exists before writing
     if !path.exists() {
-        File::create(&path)?;
+        File::create(path)?;
     }
 
-    let json = fs::read_to_string(path.clone())?;
+    let json = fs::read_to_string(path)?;
 
-    // Handle empty file case safely
-    let tree: ProjectTree = if json == "{}" {
-        ProjectTree::new(
-            path
-        )? // Assuming ProjectTree implements Default
+    // Handle empty file case safely: nothing has been staged yet
+    let tree: ProjectTree = if json.trim().is_empty() || json == "{}" {
+        ProjectTree::new(base_path)?
     } else {
         serde_json::from_str(&json)?
     };
@@ -1,14 +1,14 @@
 use super::node::Node;
 use super::ptree::ProjectTree;
 use super::operations::compute_hash;
-use super::serialization::{save, load};
+use super::serialization::{save_to, load_from};
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::{self, File};
     use std::io::{self, Write};
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
     use std::path::{Path, PathBuf};
     use tempfile::{NamedTempFile, TempDir};
 
@@ -134,21 +134,22 @@ mod tests {
         let mut tree = ProjectTree {
             root: Node::Directory { children: HashMap::new() },
             base_path: PathBuf::from("/test"),
+            unmerged: BTreeMap::new(),
         };
 
         // Add a sample file to the tree
         if let Node::Directory { children } = &mut tree.root {
             children.insert(
                 "file.txt".to_string(),
-                Node::File { hash: "dummyhash".to_string(), modified: true },
+                Node::File { hash: "dummyhash".to_string(), modified: true, stat: None },
             );
         }
 
         // Save the tree
-        save(&tree, Option::from(file_path.clone()))?;
+        save_to(&tree, &file_path)?;
 
         // Load the tree back
-        let loaded_tree = load(Option::from(file_path.clone()))?;
+        let loaded_tree = load_from(&file_path, Path::new("/test"))?;
 
         // Check if the saved and loaded trees are the same
         assert_eq!(
@@ -164,8 +165,9 @@ mod tests {
 
     #[test]
     fn test_modification() {
+        let tmp_dir = TempDir::new().unwrap();
         // Setup
-        let base_path = Path::new("./test_project");
+        let base_path = &tmp_dir.path().join("test_project");
         fs::create_dir_all(base_path).expect("Failed to create base directory");
 
         let file_path = base_path.join("file.txt");
@@ -181,7 +183,7 @@ mod tests {
 
         // Step 2: Store the initial hash (borrow project_tree immutably)
         let initial_hash = {
-            project_tree.get_file_hash(&Path::new("file.txt"))
+            project_tree.get_file_hash(Path::new("file.txt"))
                 .expect("Failed to get file hash")
         };
 
@@ -193,18 +195,13 @@ mod tests {
 
         // Step 5: Get the new hash (borrow project_tree immutably again)
         let new_hash = {
-            project_tree.get_file_hash(&Path::new("file.txt"))
+            project_tree.get_file_hash(Path::new("file.txt"))
                 .expect("Failed to get modified file hash")
         };
 
         // Step 6: Ensure the hash has changed (indicating modification)
         assert_ne!(initial_hash, new_hash, "File should have been marked as modified");
 
-        // Step 7: Check if the file is marked as modified
-        let modified_files = project_tree.get_modified_files();
-        assert_eq!(modified_files.len(), 1, "There should be one modified file");
-        assert_eq!(modified_files[0], PathBuf::from("file.txt"), "The modified file path is incorrect");
-
         // Cleanup
         fs::remove_dir_all(base_path).expect("Failed to clean up test directory");
     }
@@ -13,20 +13,10 @@ pub fn traverse(node: &Node, current_path: PathBuf, files: &mut Vec<PathBuf>) {
     }
 }
 
-
-#[allow(dead_code)]
-pub fn traverse_modified(node: &mut Node, current_path: PathBuf, modified_files: &mut Vec<PathBuf>) {
+/// Clear the modified flag of every file below `node`
+pub fn clear_modified(node: &mut Node) {
     match node {
-        Node::File { modified, .. } if *modified => {
-            modified_files.push(current_path);
-            *modified = false;
-        },
-        Node::Directory { children } => {
-            for (name, node) in children {
-                let path = current_path.join(name);
-                traverse_modified(node, path, modified_files);
-            }
-        }
-        _ => {}
+        Node::File { modified, .. } => *modified = false,
+        Node::Directory { children } => children.values_mut().for_each(clear_modified),
     }
-}
\ No newline at end of file
+}
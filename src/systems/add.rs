@@ -1,60 +1,93 @@
-use std::io;
-use std::fs;
-use std::env;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use crate::structure::ptree::ProjectTree;
-use crate::systems::init::find_grit_root;
-use crate::structure::serialization::save;
-
-/// Adds files to the Grit repository. If a path is provided, it adds the file if it exists.
-/// Otherwise, it adds all files in the current directory except `.grit/`.
-pub fn add(path: Option<&str>) -> io::Result<()> {
-    // Get the current directory
-    let current_dir = env::current_dir()?;
-
-    // Locate the Grit repository root
-    let root = match find_grit_root(&current_dir) {
-        Some(root) => root.canonicalize()?, // Normalize path
-        None => return Err(io::Error::new(io::ErrorKind::NotFound, "No .grit repository found in this directory or any parent directory.")),
+use crate::structure::serialization::{load, save};
+use crate::systems::filters::ignore::IgnoreRules;
+use crate::systems::objects::tree::Change;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Adds files to the Grit repository. If a path is provided, relative to the current
+/// directory, it adds that file or directory. Otherwise, it adds all files below the current
+/// directory except `.grit/` and those excluded by `.gritignore`.
+/// Tracked files under the path that were deleted from disk are removed from the staging tree.
+/// Returns the files whose staged content changed, by path from the root.
+pub fn add(repo: &Repository, path: Option<&str>) -> Result<Vec<(PathBuf, Change)>> {
+    let root = repo.root().to_path_buf();
+
+    // Merge into what is already staged, following the repository if it was moved
+    let mut tree = load(repo)?;
+    tree.base_path = root.clone();
+
+    let scope = match path {
+        Some(p) => repo.relative_path(p)?,
+        None => repo.current_dir().strip_prefix(&root).map(Path::to_path_buf).unwrap_or_default(),
     };
+    let abs_path = root.join(&scope);
+    let tracked: Vec<PathBuf> = tree.list_files().into_iter().filter(|file| file.starts_with(&scope)).collect();
 
-    // Ensure we are inside a Grit repository before adding files
-    if !root.join(".grit").exists() {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "No .grit directory found. Please initialize a Grit repository first."));
+    if !abs_path.exists() && tracked.is_empty() {
+        return Err(GritError::MissingRef("Specified file does not exist".to_string()));
     }
 
+    // Untracked paths matching a .gritignore pattern are left out
+    let mut rules = IgnoreRules::load(&root)?;
     let mut files_to_add = Vec::new();
-
-    if let Some(p) = path {
-        // Handle adding a specific file/directory
-        let abs_path = root.join(p); // Ensure path is relative to Grit root
-        if abs_path.exists() {
-            files_to_add.push(abs_path);
-        } else {
-            return Err(io::Error::new(io::ErrorKind::NotFound, "Specified file does not exist"));
+    if abs_path.exists() {
+        let is_dir = abs_path.is_dir();
+        let ignored = !scope.as_os_str().is_empty() && rules.is_ignored(&scope, is_dir)?;
+        if ignored && tracked.is_empty() {
+            return Err(GritError::InvalidArgument(format!("The path '{}' is ignored by one of your .gritignore files", scope.display())));
         }
-    } else {
-        // Handle adding all files except `.grit/`
-        for entry in fs::read_dir(&current_dir)? {
-            let entry = entry?;
-            let entry_path = entry.path();
-
-            if entry_path.is_dir() && entry_path.file_name().map_or(false, |name| name == ".grit") {
-                continue; // Skip .grit directory
-            }
+        if !ignored {
+            files_to_add = if is_dir { rules.walk_files(&scope)? } else { vec![scope.clone()] };
+        }
+    }
 
-            files_to_add.push(entry_path);
+    // Tracked files are updated even when an ignore pattern matches them
+    let walked: HashSet<PathBuf> = files_to_add.iter().cloned().collect();
+    for file in tracked {
+        if !walked.contains(&file) && root.join(&file).is_file() {
+            files_to_add.push(file);
         }
     }
 
-    // Initialize the project tree
-    let mut tree = ProjectTree::new(root)?;
+    // Tracked files that disappeared from disk are staged as removed
+    let mut changes: Vec<(PathBuf, Change)> = stage_removals(&mut tree, &scope).into_iter()
+        .map(|removed| (removed, Change::Deleted))
+        .collect();
 
     // Add each file to the tree
     for file in files_to_add {
-        tree.add(&file)?;
+        changes.extend(tree.add(&root.join(file))?);
     }
 
-    save(&tree, None)?;
+    // Store the content of every staged file so its hash can be resolved later
+    let store = repo.objects();
+    for rel_path in tree.list_files() {
+        let already_stored = tree.get_file_hash(&rel_path).is_some_and(|hash| store.exists(&hash));
+        if !already_stored {
+            store.write_file(&root.join(&rel_path))?;
+        }
+    }
 
-    Ok(())
+    save(repo, &tree)?;
+
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(changes)
+}
+
+/// Drop the entries below `scope` whose file no longer exists, returning their paths
+fn stage_removals(tree: &mut ProjectTree, scope: &Path) -> Vec<PathBuf> {
+    let missing: Vec<PathBuf> = tree.list_files().into_iter()
+        .filter(|file| file.starts_with(scope) && !tree.base_path.join(file).is_file())
+        .collect();
+
+    for file in &missing {
+        tree.remove(file);
+    }
+    // A conflicted file deleted from disk is resolved as removed
+    let base_path = tree.base_path.clone();
+    tree.unmerged.retain(|file, _| !file.starts_with(scope) || base_path.join(file).is_file());
+    missing
 }
@@ -1,96 +1,132 @@
-use std::env;
-use std::fs::{self};
-use std::io::{self, Write};
-use crate::systems::init::{init_grit};
+use std::io;
+use crate::test_support::TempRepo;
 
 /// Helper function to set up a test `.grit` repository
-fn setup_grit_repo() -> io::Result<()> {
-    // Initialize a .grit repository
-    init_grit()?;
+fn setup_grit_repo() -> io::Result<TempRepo> {
+    let repo = TempRepo::new();
     // Ensure the .grit directory exists
-    let grit_path = env::current_dir()?.join(".grit");
-    if !grit_path.exists() {
+    if !repo.grit_dir().exists() {
         return Err(io::Error::new(io::ErrorKind::NotFound, "Failed to create .grit directory"));
     }
-    Ok(())
-}
-
-/// Helper function to clean up after tests
-fn cleanup_grit_repo() -> io::Result<()> {
-    let grit_path = env::current_dir()?.join(".grit");
-    if grit_path.exists() {
-        fs::remove_dir_all(grit_path)?;  // Clean up .grit directory
-    }
-    Ok(())
+    Ok(repo)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use std::fs;
     use crate::systems::add::add;
     use std::path::{Path, PathBuf};
     use crate::structure::serialization::load;
+    use crate::systems::objects::object::ObjectKind;
 
     #[test]
     fn test_add_file_to_grit_repo() -> io::Result<()> {
         // Setup .grit directory
-        setup_grit_repo()?;
+        let repo = setup_grit_repo()?;
 
         // Create a dummy file to add
         let file_path = Path::new("test_file.txt");
-        let mut file = File::create(file_path)?;
-        writeln!(file, "This is a test file.")?;
+        repo.write(file_path, "This is a test file.\n")?;
 
         // Add the file to the repository using the `add` function
-        add(Some(file_path.to_str().unwrap()))?;
+        add(&repo, Some(file_path.to_str().unwrap()))?;
 
         // Verify if the file was added correctly to the project tree
-        let tree = load(None)?;
+        let tree = load(&repo)?;
         let files = tree.list_files();
 
         // Check if the file is in the tree (this assumes ProjectTree has a method to check for files)
-        assert!(files.len() > 0);
+        assert!(!files.is_empty());
         assert!(files.contains(&PathBuf::from(file_path.to_str().unwrap())));
 
-        // Cleanup after the test
-        cleanup_grit_repo()?;
-
-        // Clean up the test file
-        fs::remove_file(file_path)?;
-
         Ok(())
     }
 
     #[test]
     fn test_add_multiple_files_to_grit_repo() -> io::Result<()> {
         // Setup .grit directory
-        setup_grit_repo()?;
+        let repo = setup_grit_repo()?;
 
         // Create dummy files to add
         let file1_path = Path::new("test_file1.txt");
         let file2_path = Path::new("test_file2.txt");
-        let mut file1 = File::create(file1_path)?;
-        let mut file2 = File::create(file2_path)?;
-        writeln!(file1, "Test content 1")?;
-        writeln!(file2, "Test content 2")?;
+        repo.write(file1_path, "Test content 1\n")?;
+        repo.write(file2_path, "Test content 2\n")?;
 
         // Add both files to the repository
-        add(None)?; // No specific path, so all files should be added
+        add(&repo, None)?; // No specific path, so all files should be added
 
         // Verify both files were added correctly to the project tree
-        let tree = load(None)?;
+        let tree = load(&repo)?;
 
         // Check if both files are in the tree
         assert!(tree.exists(file1_path));
         assert!(tree.exists(file2_path));
 
-        // Cleanup after the test
-        cleanup_grit_repo()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_stores_file_content_as_blob() -> io::Result<()> {
+        let repo = setup_grit_repo()?;
+
+        repo.write("first.txt", "same content")?;
+        repo.write("second.txt", "same content")?;
+        add(&repo, None)?;
+
+        let tree = load(&repo)?;
+        let first = tree.get_file_hash(Path::new("first.txt")).unwrap();
+        let second = tree.get_file_hash(Path::new("second.txt")).unwrap();
+        assert_eq!(first, second, "Identical files should share a hash");
+
+        let (kind, data) = repo.objects().read(&first)?;
+        assert_eq!(kind, ObjectKind::Blob);
+        assert_eq!(data, b"same content");
 
-        // Clean up the test files
-        fs::remove_file(file1_path)?;
-        fs::remove_file(file2_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_merges_into_existing_staging_tree() -> io::Result<()> {
+        let repo = setup_grit_repo()?;
+
+        repo.write("a.txt", "a")?;
+        repo.write("b.txt", "bb")?;
+        add(&repo, Some("a.txt"))?;
+        add(&repo, Some("b.txt"))?;
+
+        let tree = load(&repo)?;
+        assert!(tree.exists(Path::new("a.txt")), "Adding b.txt must not forget a.txt");
+        assert!(tree.exists(Path::new("b.txt")));
+
+        let stat = tree.get_file_stat(Path::new("b.txt")).expect("Staged files record their stat info");
+        assert_eq!(stat.size, 2);
+        assert!(stat.mtime > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_stages_removed_files() -> io::Result<()> {
+        let repo = setup_grit_repo()?;
+
+        repo.write("dir/gone.txt", "gone")?;
+        repo.write("dir/kept.txt", "kept")?;
+        repo.write("other.txt", "other")?;
+        add(&repo, None)?;
+
+        fs::remove_file(repo.working_path("dir/gone.txt"))?;
+        fs::remove_file(repo.working_path("other.txt"))?;
+        add(&repo, Some("dir"))?;
+        let tree = load(&repo)?;
+        assert!(!tree.exists(Path::new("dir/gone.txt")), "Removal under the given path is staged");
+        assert!(tree.exists(Path::new("other.txt")), "Files outside the given path are left alone");
+
+        // A deleted path can be named directly
+        add(&repo, Some("other.txt"))?;
+        assert!(!load(&repo)?.exists(Path::new("other.txt")));
+        assert!(add(&repo, Some("never_tracked.txt")).is_err());
 
         Ok(())
     }
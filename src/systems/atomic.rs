@@ -0,0 +1,94 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::error::{GritError, Result};
+
+/// Held inside `.grit` by the command currently changing the repository
+pub const LOCK_FILE: &str = "index.lock";
+
+/// Distinguishes temporary files of concurrent writes from the same process
+static TEMPORARY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replaces a file so that readers, and whatever is left after a crash, only ever see the
+/// old content or the new one: the content goes to a temporary file in the same directory,
+/// is flushed to disk, and is then renamed over the target.
+pub fn write_atomic(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> Result<()> {
+    write_atomic_with(path, |file| Ok(file.write_all(content.as_ref())?))
+}
+
+/// Like `write_atomic`, the content being written by `write`. When it fails the target is
+/// left untouched and the temporary file removed.
+pub fn write_atomic_with(path: impl AsRef<Path>, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path.file_name()
+        .ok_or_else(|| GritError::InvalidArgument(format!("Cannot write to {}", path.display())))?;
+    let temporary = dir.join(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        process::id(),
+        TEMPORARY_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let written = File::create(&temporary).map_err(GritError::from).and_then(|mut file| {
+        write(&mut file)?;
+        Ok(file.sync_all()?)
+    });
+    if let Err(e) = written.and_then(|_| Ok(fs::rename(&temporary, path)?)) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+
+    // The rename itself is only durable once the directory is flushed; not every platform
+    // can open a directory for that, which costs durability but not atomicity
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Exclusive right to change a repository, held by creating `.grit/index.lock` and given
+/// up when dropped. A second command finds the file and stops instead of interleaving its
+/// writes with the first.
+#[derive(Debug)]
+pub struct RepositoryLock {
+    path: PathBuf,
+}
+
+impl RepositoryLock {
+    pub fn acquire(grit_dir: &Path) -> Result<RepositoryLock> {
+        let path = grit_dir.join(LOCK_FILE);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                // The owner is only recorded to help whoever finds a stale lock
+                writeln!(file, "{}", process::id())?;
+                Ok(RepositoryLock { path })
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&path).unwrap_or_default();
+                let owner = match owner.trim() {
+                    "" => String::new(),
+                    pid => format!(" (process {})", pid),
+                };
+                Err(GritError::Locked(format!(
+                    "Unable to lock the repository: {} exists.\nAnother grit command{} seems to be running in this repository. \
+                    If it crashed, remove the file and try again.",
+                    path.display(),
+                    owner
+                )))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for RepositoryLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::error::GritError;
+
+/// Helper listing the names of the files in a directory, sorted
+fn file_names(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+        .collect::<io::Result<_>>()?;
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::test_support::{tester, TempRepo};
+    use crate::systems::add::add;
+    use crate::systems::atomic::{write_atomic, write_atomic_with, RepositoryLock, LOCK_FILE};
+    use crate::systems::commits::commit::Commit;
+    use crate::systems::commits::functions::load_all_commits;
+    
+    use crate::structure::serialization::load;
+
+    #[test]
+    fn test_write_atomic_replaces_content() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("HEAD");
+
+        write_atomic(&path, "first\n")?;
+        write_atomic(&path, "second\n")?;
+        assert_eq!(fs::read_to_string(&path)?, "second\n");
+        assert_eq!(file_names(tmp_dir.path())?, vec!["HEAD"], "No temporary file is left behind");
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_the_old_content() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("commits.json");
+        write_atomic(&path, "{\"complete\": true}")?;
+
+        // The writer fails half way, as a crash or a full disk would make it
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"{\"compl")?;
+            Err(io::Error::other("disk full").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path)?, "{\"complete\": true}");
+        assert_eq!(file_names(tmp_dir.path())?, vec!["commits.json"]);
+
+        let missing = tmp_dir.path().join("new.json");
+        assert!(write_atomic_with(&missing, |_| Err(io::Error::other("interrupted").into())).is_err());
+        assert!(!missing.exists(), "A file that did not exist is not created half written");
+        Ok(())
+    }
+
+    #[test]
+    fn test_leftover_temporary_files_are_ignored() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n")?;
+        add(&repo, None)?;
+        Commit::new(&repo, "First", &tester())?;
+
+        // What a process killed between writing and renaming leaves behind
+        repo.write(".grit/.tree.json.4242-0.tmp", "{\"root\": ")?;
+        repo.write(".grit/.commits.json.4242-1.tmp", "")?;
+
+        assert!(load(&repo)?.exists(Path::new("a.txt")));
+        assert_eq!(load_all_commits(&repo)?.len(), 1);
+        repo.write("a.txt", "b\n")?;
+        add(&repo, None)?;
+        assert!(Commit::new(&repo, "Second", &tester())?.is_some());
+        assert_eq!(load_all_commits(&repo)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let lock = RepositoryLock::acquire(tmp_dir.path())?;
+        assert!(tmp_dir.path().join(LOCK_FILE).exists());
+
+        let error = RepositoryLock::acquire(tmp_dir.path()).unwrap_err();
+        assert!(matches!(error, GritError::Locked(_)));
+        assert!(error.to_string().contains("index.lock exists"));
+        assert!(error.to_string().contains(&format!("process {}", std::process::id())));
+
+        drop(lock);
+        assert!(!tmp_dir.path().join(LOCK_FILE).exists());
+        drop(RepositoryLock::acquire(tmp_dir.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_lock_is_reported() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        fs::write(tmp_dir.path().join(LOCK_FILE), "")?;
+
+        let error = RepositoryLock::acquire(tmp_dir.path()).unwrap_err();
+        assert!(error.to_string().contains("If it crashed, remove the file"));
+        assert!(tmp_dir.path().join(LOCK_FILE).exists(), "Someone else's lock is never removed");
+        Ok(())
+    }
+}
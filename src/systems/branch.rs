@@ -0,0 +1,108 @@
+use std::fs;
+use crate::systems::commits::versioning::VERSIONS_DIR;
+use crate::systems::commits::functions::{commit_history, get_head_commit, resolve_commit};
+use crate::systems::repository::Repository;
+use crate::systems::refs::{
+    current_branch, delete_ref, list_branches,
+    read_ref, set_head_branch, validate_branch_name, write_ref
+};
+use crate::error::{GritError, Result};
+
+/// All branches with the commit they point at, the checked out one flagged
+pub fn branch_list(repo: &Repository) -> Result<Vec<(String, String, bool)>> {
+    let current = current_branch(repo)?;
+    let mut branches = Vec::new();
+
+    for name in list_branches(repo)? {
+        let commit = read_ref(repo, &name)?.unwrap_or_default();
+        let is_current = current.as_deref() == Some(name.as_str());
+        branches.push((name, commit, is_current));
+    }
+    Ok(branches)
+}
+
+/// Create a branch at `start` (any revision), or at HEAD, and return the commit it points at
+pub fn create_branch(repo: &Repository, name: &str, start: Option<&str>) -> Result<String> {
+    validate_branch_name(name)?;
+    if read_ref(repo, name)?.is_some() {
+        return Err(GritError::InvalidArgument(format!("A branch named '{}' already exists", name)));
+    }
+
+    let start = start.unwrap_or("HEAD");
+    let commit = resolve_commit(repo, start)
+        .map_err(|_| GritError::MissingRef(format!("Not a valid starting point: '{}'", start)))?;
+
+    write_ref(repo, name, &commit.id)?;
+    Ok(commit.id)
+}
+
+/// Delete a branch. Unless `force` is set, its commits must be reachable from HEAD,
+/// so deleting it loses nothing.
+pub fn delete_branch(repo: &Repository, name: &str, force: bool) -> Result<()> {
+    let tip = read_ref(repo, name)?
+        .ok_or_else(|| GritError::MissingRef(format!("Branch '{}' not found", name)))?;
+
+    if current_branch(repo)?.as_deref() == Some(name) {
+        return Err(GritError::InvalidArgument(format!("Cannot delete the branch '{}' which you are currently on", name)));
+    }
+
+    if !force {
+        let head = get_head_commit(repo)?.unwrap_or_default();
+        let merged = !head.is_empty() && commit_history(repo, &head)?.iter().any(|commit| commit.id == tip);
+        if !merged {
+            return Err(GritError::Conflict(format!(
+                "The branch '{}' is not fully merged. If you are sure you want to delete it, use -D.", name
+            )));
+        }
+    }
+
+    delete_ref(repo, name)
+}
+
+/// Rename a branch, the current one when `old` is omitted. An existing branch named `new`
+/// is only replaced when `force` is set.
+pub fn rename_branch(repo: &Repository, old: Option<&str>, new: &str, force: bool) -> Result<()> {
+    validate_branch_name(new)?;
+    let current = current_branch(repo)?;
+    let old = match old {
+        Some(old) => old.to_string(),
+        None => current.clone().ok_or_else(|| GritError::InvalidArgument("Not on any branch".to_string()))?,
+    };
+    if old == new {
+        return Ok(());
+    }
+
+    let tip = read_ref(repo, &old)?;
+    let following = current.as_deref() == Some(old.as_str());
+    if tip.is_none() && !following {
+        return Err(GritError::MissingRef(format!("Branch '{}' not found", old)));
+    }
+    if read_ref(repo, new)?.is_some() && !force {
+        return Err(GritError::InvalidArgument(format!("A branch named '{}' already exists", new)));
+    }
+
+    // A branch without commits only exists through HEAD
+    if let Some(tip) = tip {
+        write_ref(repo, new, &tip)?;
+        delete_ref(repo, &old)?;
+    }
+    if following {
+        set_head_branch(repo, new)?;
+    }
+
+    // Delta chains are stored per branch and follow the rename, replacing the chains of
+    // a branch the rename replaced
+    let old_versions = repo.grit_path(VERSIONS_DIR).join(&old);
+    let new_versions = repo.grit_path(VERSIONS_DIR).join(new);
+    if old_versions.is_dir() {
+        if new_versions.exists() {
+            fs::remove_dir_all(&new_versions)?;
+        }
+        if let Some(parent) = new_versions.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(old_versions, new_versions)?;
+    }
+
+    Ok(())
+}
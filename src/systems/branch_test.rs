@@ -0,0 +1,175 @@
+use std::fs;
+use std::io;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::test_support::TempRepo;
+    use crate::systems::checkout::{checkout, switch};
+    use crate::systems::commits::functions::get_head_commit;
+    use crate::systems::refs::HEAD_FILE;
+    use crate::systems::repository::Repository;
+    use tempfile::TempDir;
+    use crate::systems::branch::{branch_list, create_branch, delete_branch, rename_branch};
+    use crate::systems::refs::{current_branch, list_branches, migrate_legacy_head, read_head, read_ref, validate_branch_name, Head};
+
+    #[test]
+    fn test_init_points_head_at_default_branch() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        assert_eq!(fs::read_to_string(repo.grit_path(HEAD_FILE))?, "ref: refs/heads/Main\n");
+        assert_eq!(read_head(&repo)?, Head::Branch("Main".to_string()));
+        assert_eq!(get_head_commit(&repo)?, None, "A new branch has no commit yet");
+
+        let first = repo.commit_file("a.txt", "a", "First")?;
+        assert_eq!(read_ref(&repo, "Main")?, Some(first.id.clone()));
+        assert_eq!(repo.read(".grit/refs/heads/Main")?.trim(), first.id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_list_and_switch() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let first = repo.commit_file("a.txt", "one\n", "First")?;
+        let second = repo.commit_file("a.txt", "two\n", "Second")?;
+
+        // Branches can start from any commit
+        assert_eq!(create_branch(&repo, "feature/old", Some(&first.id[..10]))?, first.id);
+        assert_eq!(create_branch(&repo, "topic", None)?, second.id);
+        assert!(create_branch(&repo, "topic", None).is_err(), "Existing branches must not be overwritten");
+
+        let listed: Vec<(String, bool)> = branch_list(&repo)?.into_iter().map(|(name, _, current)| (name, current)).collect();
+        assert_eq!(listed, vec![
+            ("Main".to_string(), true),
+            ("feature/old".to_string(), false),
+            ("topic".to_string(), false),
+        ]);
+
+        switch(&repo, "feature/old", false)?;
+        assert_eq!(current_branch(&repo)?, Some("feature/old".to_string()));
+        assert_eq!(repo.read("a.txt")?, "one\n");
+
+        // New commits advance only the checked out branch
+        let third = repo.commit_file("b.txt", "b\n", "Third")?;
+        assert_eq!(third.parents, vec![first.id.clone()]);
+        assert_eq!(read_ref(&repo, "feature/old")?, Some(third.id));
+        assert_eq!(read_ref(&repo, "Main")?, Some(second.id));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_commit_detaches_head() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let first = repo.commit_file("a.txt", "one\n", "First")?;
+        let second = repo.commit_file("a.txt", "two\n", "Second")?;
+
+        checkout(&repo, &first.id, false)?;
+        assert_eq!(read_head(&repo)?, Head::Detached(first.id.clone()));
+        assert_eq!(current_branch(&repo)?, None);
+
+        let detached = repo.commit_file("a.txt", "three\n", "Detached")?;
+        assert_eq!(get_head_commit(&repo)?, Some(detached.id));
+        assert_eq!(read_ref(&repo, "Main")?, Some(second.id.clone()), "Branches are untouched by detached commits");
+
+        checkout(&repo, "Main", true)?;
+        assert_eq!(read_head(&repo)?, Head::Branch("Main".to_string()));
+        assert_eq!(repo.read("a.txt")?, "two\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_requires_merged_branch() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "one\n", "First")?;
+
+        create_branch(&repo, "merged", None)?;
+        create_branch(&repo, "unmerged", None)?;
+        switch(&repo, "unmerged", false)?;
+        repo.commit_file("b.txt", "work\n", "Unmerged work")?;
+        switch(&repo, "Main", false)?;
+
+        assert!(delete_branch(&repo, "Main", false).is_err(), "The current branch cannot be deleted");
+        delete_branch(&repo, "merged", false)?;
+        assert_eq!(read_ref(&repo, "merged")?, None);
+
+        let err = delete_branch(&repo, "unmerged", false).unwrap_err();
+        assert!(err.to_string().contains("not fully merged"));
+        delete_branch(&repo, "unmerged", true)?;
+        assert_eq!(read_ref(&repo, "unmerged")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_branch() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        // Renaming before the first commit only changes where HEAD points
+        rename_branch(&repo, None, "trunk", false)?;
+        assert_eq!(current_branch(&repo)?, Some("trunk".to_string()));
+
+        let first = repo.commit_file("a.txt", "one\n", "First")?;
+        assert!(repo.exists(".grit/versions/trunk"));
+        create_branch(&repo, "other", None)?;
+
+        assert!(rename_branch(&repo, None, "other", false).is_err());
+        rename_branch(&repo, Some("trunk"), "main", false)?;
+        assert_eq!(current_branch(&repo)?, Some("main".to_string()));
+        assert_eq!(read_ref(&repo, "main")?, Some(first.id));
+        assert_eq!(read_ref(&repo, "trunk")?, None);
+        assert!(repo.exists(".grit/versions/main"), "Delta chains follow the branch");
+        Ok(())
+    }
+
+    #[test]
+    fn test_forced_rename_replaces_the_chains() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "one\n", "First")?;
+        create_branch(&repo, "other", None)?;
+        switch(&repo, "other", false)?;
+        repo.commit_file("b.txt", "only on other\n", "Other")?;
+        switch(&repo, "Main", false)?;
+        let tip = repo.commit_file("a.txt", "two\n", "Second")?;
+
+        rename_branch(&repo, None, "other", true)?;
+        assert_eq!(read_ref(&repo, "other")?, Some(tip.id));
+        assert!(!repo.exists(".grit/versions/Main"), "No chains are left behind");
+        assert!(repo.exists(".grit/versions/other/a.txt.json"));
+        assert!(!repo.exists(".grit/versions/other/b.txt.json"), "The replaced branch's chains go with it");
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_head_is_migrated() -> io::Result<()> {
+        // A repository from before branches were refs: no refs/heads, HEAD holding a commit
+        let tmp_dir = TempDir::new()?;
+        fs::create_dir(tmp_dir.path().join(".grit"))?;
+        fs::write(tmp_dir.path().join(".grit/config"), "path=/somewhere\nbranch=dev\n")?;
+        fs::write(tmp_dir.path().join(".grit").join(HEAD_FILE), "0123456789abcdef0123456789abcdef01234567")?;
+        let repo = Repository::open(tmp_dir.path())?;
+
+        // Reading leaves the repository as it is
+        assert_eq!(read_head(&repo)?, Head::Branch("dev".to_string()));
+        assert_eq!(read_ref(&repo, "dev")?, Some("0123456789abcdef0123456789abcdef01234567".to_string()));
+        assert_eq!(list_branches(&repo)?, vec!["dev".to_string()]);
+        assert_eq!(fs::read_to_string(repo.grit_path("config"))?, "path=/somewhere\nbranch=dev\n");
+        assert!(!repo.grit_path("refs/heads/dev").exists());
+
+        migrate_legacy_head(&repo)?;
+        assert_eq!(read_head(&repo)?, Head::Branch("dev".to_string()));
+        assert_eq!(read_ref(&repo, "dev")?, Some("0123456789abcdef0123456789abcdef01234567".to_string()));
+        assert_eq!(fs::read_to_string(repo.grit_path(HEAD_FILE))?, "ref: refs/heads/dev\n");
+        assert_eq!(fs::read_to_string(repo.grit_path("config"))?, "[core]\npath = \"/somewhere\"\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_name_validation() {
+        for name in ["feature", "feature/x", "v1.2", "fix-123"] {
+            assert!(validate_branch_name(name).is_ok(), "{} should be valid", name);
+        }
+        for name in ["", "HEAD", "-d", "a..b", "a b", "x/", "/x", ".hidden", "a/.b", "x.lock", "a~1", "a:b"] {
+            assert!(validate_branch_name(name).is_err(), "{} should be rejected", name);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::structure::ptree::ProjectTree;
+use crate::structure::operations::compute_hash;
+use crate::structure::serialization::{load, save};
+use crate::systems::commits::commit::Commit;
+use crate::systems::commits::functions::{head_snapshot, load_commit, resolve_commit};
+use crate::systems::refs::{current_branch, read_ref, set_head_branch, set_head_detached};
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Rewrites the working directory to match a commit, given as a commit id or a branch name.
+///
+/// Checking out a branch makes HEAD follow it, while any other revision detaches HEAD.
+/// `HEAD` itself leaves HEAD as it is. Unless `force` is set, nothing is touched when
+/// changes are staged or a file the checkout would change has uncommitted modifications.
+pub fn checkout(repo: &Repository, rev: &str, force: bool) -> Result<Commit> {
+    if read_ref(repo, rev)?.is_some() {
+        return switch(repo, rev, force);
+    }
+    if rev == "HEAD" {
+        if let Some(branch) = current_branch(repo)? {
+            return switch(repo, &branch, force);
+        }
+    }
+
+    let target = resolve_commit(repo, rev)?;
+    update_working_tree(repo, &target, force)?;
+    set_head_detached(repo, &target.id)?;
+    Ok(target)
+}
+
+/// Checks out a branch, making HEAD follow it
+pub fn switch(repo: &Repository, branch: &str, force: bool) -> Result<Commit> {
+    let id = read_ref(repo, branch)?
+        .ok_or_else(|| GritError::MissingRef(format!("Branch '{}' not found", branch)))?;
+    let target = load_commit(repo, &id)?
+        .ok_or_else(|| GritError::corrupt(format!("Branch '{}' points at unknown commit {}", branch, id)))?;
+
+    update_working_tree(repo, &target, force)?;
+    set_head_branch(repo, branch)?;
+    Ok(target)
+}
+
+/// Creates, updates and deletes files so that the tracked content equals the commit's
+/// snapshot, then resets the staging tree to it. HEAD is left to the caller.
+///
+/// With `force`, staged changes are dropped and every tracked file whose content differs
+/// from the commit is rewritten.
+pub(crate) fn update_working_tree(repo: &Repository, target: &Commit, force: bool) -> Result<()> {
+    let root = repo.root();
+    let store = repo.objects();
+
+    let target_files = target.snapshot(&store)?;
+    let current_files = head_snapshot(repo, &store)?;
+
+    // Replacing the staging tree would lose whatever was added since HEAD
+    if !force && load(repo)?.snapshot() != current_files {
+        return Err(GritError::DirtyWorkingTree(
+            "Your staged changes would be lost by checkout. Commit them or use --force to discard them.".to_string()
+        ));
+    }
+
+    let mut to_update: Vec<(&PathBuf, &String)> = Vec::new();
+    let mut to_delete: Vec<&PathBuf> = Vec::new();
+    let mut conflicts: Vec<&PathBuf> = Vec::new();
+
+    for (path, hash) in &target_files {
+        if current_files.get(path) != Some(hash) || (force && working_hash(root, path).as_ref() != Some(hash)) {
+            to_update.push((path, hash));
+        }
+    }
+    for path in current_files.keys() {
+        if !target_files.contains_key(path) {
+            to_delete.push(path);
+        }
+    }
+
+    // A path is safe to overwrite when the working copy still matches HEAD,
+    // or already has the content being checked out
+    for path in to_update.iter().map(|(path, _)| *path).chain(to_delete.iter().copied()) {
+        let on_disk = working_hash(root, path);
+        let expected = current_files.get(path);
+        let wanted = target_files.get(path);
+        if on_disk.as_ref() != expected && on_disk.as_ref() != wanted {
+            conflicts.push(path);
+        }
+    }
+
+    if !conflicts.is_empty() && !force {
+        let paths: Vec<String> = conflicts.iter().map(|p| format!("\t{}", p.display())).collect();
+        return Err(GritError::DirtyWorkingTree(format!(
+            "Your local changes to the following files would be overwritten by checkout:\n{}\nCommit them or use --force to discard them.",
+            paths.join("\n")
+        )));
+    }
+
+    for (path, hash) in to_update {
+        write_blob(&store, root, path, hash)?;
+    }
+    for path in to_delete {
+        remove_file(root, path)?;
+    }
+
+    // The staging tree now matches the checked out commit
+    save(repo, &ProjectTree::from_snapshot(root, &target_files)?)
+}
+
+/// Restores files from the staging tree, or from `source` when given, discarding changes
+/// made in the working directory. `path` may name a single file or a directory.
+pub fn restore(repo: &Repository, path: &str, source: Option<&str>) -> Result<Vec<PathBuf>> {
+    let root = repo.root();
+    let store = repo.objects();
+
+    let files = match source {
+        Some(rev) => resolve_commit(repo, rev)?.snapshot(&store)?,
+        None => load(repo)?.snapshot(),
+    };
+
+    let prefix = repo.relative_path(path)?;
+    let matching: Vec<(&PathBuf, &String)> = files.iter()
+        .filter(|(file, _)| prefix.as_os_str().is_empty() || file.starts_with(&prefix))
+        .collect();
+
+    if matching.is_empty() {
+        return Err(GritError::MissingRef(format!("Pathspec '{}' did not match any tracked file", path)));
+    }
+
+    let mut restored = Vec::new();
+    for (file, hash) in matching {
+        if working_hash(root, file).as_ref() != Some(hash) {
+            write_blob(&store, root, file, hash)?;
+            restored.push(file.clone());
+        }
+    }
+    Ok(restored)
+}
+
+/// Hash of a file in the working directory, `None` when it does not exist
+pub(crate) fn working_hash(root: &Path, rel_path: &Path) -> Option<String> {
+    let abs_path = root.join(rel_path);
+    if abs_path.is_file() { compute_hash(&abs_path).ok() } else { None }
+}
+
+/// Write a blob from the object store to its place in the working directory
+pub(crate) fn write_blob(store: &ObjectStore, root: &Path, rel_path: &Path, hash: &str) -> Result<()> {
+    let (_, content) = store.read(hash)?;
+    let abs_path = root.join(rel_path);
+
+    if let Some(parent) = abs_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(abs_path, content)?;
+    Ok(())
+}
+
+/// Delete a tracked file along with the directories it leaves empty
+pub(crate) fn remove_file(root: &Path, rel_path: &Path) -> Result<()> {
+    let abs_path = root.join(rel_path);
+    if abs_path.exists() {
+        fs::remove_file(&abs_path)?;
+    }
+
+    let mut dir = abs_path.parent();
+    while let Some(parent) = dir {
+        if parent == root || fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
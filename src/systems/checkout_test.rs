@@ -0,0 +1,135 @@
+use std::fs;
+use std::io;
+use crate::systems::index::remove_cached;
+use crate::error::GritError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::test_support::TempRepo;
+    use crate::structure::serialization::load;
+    use crate::systems::checkout::{checkout, restore};
+    use crate::systems::commits::functions::get_head_commit;
+    use crate::systems::refs::current_branch;
+    use std::path::Path;
+
+    #[test]
+    fn test_checkout_restores_previous_commit() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        repo.write("kept.txt", "version 1\n")?;
+        repo.write("removed_later.txt", "only in first commit\n")?;
+        let first = repo.commit_all("First")?;
+
+        repo.write("kept.txt", "version 2\n")?;
+        fs::remove_file(repo.working_path("removed_later.txt"))?;
+        remove_cached(&repo, "removed_later.txt")?;
+        fs::create_dir(repo.working_path("dir"))?;
+        repo.write("dir/added.txt", "only in second commit\n")?;
+        let second = repo.commit_all("Second")?;
+
+        checkout(&repo, &first.id, false)?;
+        assert_eq!(repo.read("kept.txt")?, "version 1\n");
+        assert_eq!(repo.read("removed_later.txt")?, "only in first commit\n");
+        assert!(!repo.exists("dir"), "Files and directories absent from the commit should be removed");
+        assert_eq!(get_head_commit(&repo)?, Some(first.id.clone()));
+
+        // The staging tree follows the checkout, so nothing looks modified
+        let tree = load(&repo)?;
+        assert_eq!(tree.snapshot(), first.snapshot(&repo.objects())?);
+
+        checkout(&repo, &second.id[..8], false)?;
+        assert_eq!(repo.read("kept.txt")?, "version 2\n");
+        assert_eq!(repo.read("dir/added.txt")?, "only in second commit\n");
+        assert!(!repo.exists("removed_later.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_clobber_local_changes() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        repo.write("file.txt", "first\n")?;
+        let first = repo.commit_all("First")?;
+        repo.write("file.txt", "second\n")?;
+        repo.commit_all("Second")?;
+
+        repo.write("file.txt", "uncommitted work\n")?;
+        let err = checkout(&repo, &first.id, false).unwrap_err();
+        assert!(err.to_string().contains("file.txt"));
+        assert_eq!(repo.read("file.txt")?, "uncommitted work\n", "Nothing should be touched on refusal");
+
+        checkout(&repo, &first.id, true)?;
+        assert_eq!(repo.read("file.txt")?, "first\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_drop_staged_changes() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        let first = repo.commit_file("file.txt", "first\n", "First")?;
+        repo.commit_file("other.txt", "other\n", "Second")?;
+
+        // A staged file the checkout does not touch would still vanish from the staging tree
+        repo.write("staged.txt", "staged\n")?;
+        repo.add(None)?;
+        let err = checkout(&repo, &first.id, false).unwrap_err();
+        assert!(matches!(err, GritError::DirtyWorkingTree(_)), "{:?}", err);
+        assert!(load(&repo)?.snapshot().contains_key(Path::new("staged.txt")));
+
+        checkout(&repo, &first.id, true)?;
+        assert_eq!(load(&repo)?.snapshot(), first.snapshot(&repo.objects())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forced_checkout_of_head_discards_local_changes() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        let first = repo.commit_file("file.txt", "first\n", "First")?;
+        repo.write("file.txt", "uncommitted work\n")?;
+
+        // The file is the same in HEAD and the target, yet its working copy is rewritten
+        checkout(&repo, "HEAD", true)?;
+        assert_eq!(repo.read("file.txt")?, "first\n");
+        assert_eq!(current_branch(&repo)?, Some("Main".to_string()), "Checking out HEAD must not detach it");
+        assert_eq!(get_head_commit(&repo)?, Some(first.id));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_unknown_revision() {
+        let repo = TempRepo::new();
+
+        let err = checkout(&repo, "does-not-exist", false).unwrap_err();
+        assert!(matches!(err, GritError::MissingRef(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_restore_from_index_and_source() -> io::Result<()> {
+        let repo = TempRepo::new();
+
+        fs::create_dir(repo.working_path("docs"))?;
+        repo.write("docs/guide.txt", "original\n")?;
+        let first = repo.commit_all("First")?;
+        repo.write("docs/guide.txt", "improved\n")?;
+        repo.commit_all("Second")?;
+
+        // Without a source the staged content comes back
+        repo.write("docs/guide.txt", "scribbles\n")?;
+        let restored = restore(&repo, "docs/guide.txt", None)?;
+        assert_eq!(restored.len(), 1);
+        assert_eq!(repo.read("docs/guide.txt")?, "improved\n");
+
+        // A directory restores every file below it
+        restore(&repo, "docs", Some(&first.id))?;
+        assert_eq!(repo.read("docs/guide.txt")?, "original\n");
+
+        assert!(restore(&repo, "missing.txt", None).is_err());
+        Ok(())
+    }
+}
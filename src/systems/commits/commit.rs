@@ -1,80 +1,176 @@
-use uuid::Uuid;
-use std::path::Path;
-use std::collections::HashMap;
-use super::versioning::Version;
+use std::io;
+use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use super::versioning::{version_file, Version};
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 use crate::structure::serialization::{load, save};
-use crate::systems::commits::functions::{create_commit_files, save_commit};
-use crate::systems::filters::filter::{filter_paths, load_file_filter};
+use crate::systems::commits::functions::{create_commit_files, get_head_commit, load_commit, save_commit};
+use crate::systems::objects::object::{hash_object, ObjectKind};
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::objects::tree::{changed_paths, read_tree, write_tree};
+use crate::systems::identity::Identity;
 use crate::systems::init::get_current_branch;
+use crate::systems::merge::{clear_merge_state, merge_head};
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Commit {
     pub id: String,
+    #[serde(default)]
+    pub tree: String, // Root tree object holding every tracked file
+    #[serde(default)]
+    pub parents: Vec<String>, // Empty for a root commit, several for a merge
     pub timestamp: u64,
-    pub author: String,
+    pub author: String, // `Name <email>` of who wrote the changes
+    #[serde(default)]
+    pub committer: String, // Who recorded the commit, empty for commits made before it was kept
     pub branch: String,
     pub message: String,
-    pub files: Vec<String>,
+    pub files: Vec<String>, // Paths changed since the first parent
+    #[serde(default)]
+    pub deleted: Vec<String>, // Paths of `files` the commit no longer has
     pub versions: HashMap<String, String>, // Maps file paths to version IDs
 }
 
 impl Commit {
-    pub fn new(message: &str, author: &str) -> std::io::Result<Option<Commit>> {
-        let mut tree = load(None)?;
-        let modified_files = filter_paths(tree.get_modified_files(), &load_file_filter(".filter")?);
-        save(&tree, None)?;
+    pub fn new(repo: &Repository, message: &str, identity: &Identity) -> Result<Option<Commit>> {
+        let mut tree = load(repo)?;
+        let store = repo.objects();
+
+        // Snapshot every tracked file, not only the ones modified since the last commit
+        let snapshot: BTreeMap<PathBuf, String> = tree.snapshot();
+        // The staged blobs are what gets committed, the working files may have changed since
+        if let Some((path, hash)) = snapshot.iter().find(|(_, hash)| !store.exists(hash)) {
+            return Err(GritError::corrupt(format!(
+                "Staged blob {} of {} is missing, add the file again", hash, path.display()
+            )));
+        }
+        let tree_id = write_tree(&store, &snapshot)?;
+
+        if !tree.unmerged().is_empty() {
+            let paths: Vec<String> = tree.unmerged().keys().map(|path| format!("\t{}", path.display())).collect();
+            return Err(GritError::Conflict(format!(
+                "Committing is not possible because you have unmerged files:\n{}\nFix them up, then add them before committing.",
+                paths.join("\n")
+            )));
+        }
+
+        let mut parents: Vec<String> = get_head_commit(repo)?.into_iter().filter(|id| !id.is_empty()).collect();
+        // While merging, the merged commit is the second parent
+        let merging = merge_head(repo)?;
+        parents.extend(merging.iter().cloned());
+        let parent_snapshot = match parents.first() {
+            Some(parent) => match load_commit(repo, parent)? {
+                Some(parent) => parent.snapshot(&store)?,
+                None => BTreeMap::new(),
+            },
+            None => BTreeMap::new(),
+        };
+
+        // Avoid creating a commit if the snapshot is the same as the parent's, unless it
+        // records a merge
+        let changed_files = changed_paths(&parent_snapshot, &snapshot);
+        if changed_files.is_empty() && merging.is_none() {
+            return Ok(None);
+        }
+
         let mut versions_map: HashMap<String, _> = HashMap::new();
-        let mut has_actual_changes = false;
-        let branch = get_current_branch()?;  // Extract branch name first
-
-        for file_path in &modified_files {
-            let file_stem = Path::new(file_path)
-                .display()
-                .to_string()
-                .replace("/", "_") // Works for Unix but not Windows
-                .replace("\\", "_"); // Ensures Windows compatibility
-
-            let version_path = format!(".grit/versions/{}/{}.json", branch, file_stem);
-            let mut version = match Version::load(&version_path) {
-                Ok(v) => v,
-                Err(_) => {
-                    Version::create(&file_path.to_string_lossy(), &version_path)?;
-                    Version::load(&version_path)?
+        let branch = get_current_branch(repo)?;  // Extract branch name first
+
+        for (file_path, hash) in changed_files.iter().filter_map(|path| snapshot.get(path).map(|hash| (path, hash))) {
+            let content = store.read(hash)?.1;
+            let version_path = version_file(repo, &branch, file_path);
+            // Only a missing chain is started afresh, a damaged one must not be overwritten
+            let (mut version, is_new) = match Version::load(repo, &version_path) {
+                Ok(v) => (v, false),
+                Err(GritError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                    Version::create(repo, file_path, &content, &version_path)?;
+                    (Version::load(repo, &version_path)?, true)
                 }
+                Err(e) => return Err(e),
             };
 
-            let version_id = version.add_version(&file_path.to_string_lossy(), &version_path)?;
+            let mut version_id = version.add_version(repo, file_path, &content, &version_path)?;
+
+            // A newly tracked file has no delta yet, its stored blob stands for the first version
+            if version_id.is_empty() && is_new {
+                version_id = hash.clone();
+            }
 
             if !version_id.is_empty() {
                 versions_map.insert(file_path.to_string_lossy().to_string(), version_id);
-                has_actual_changes = true;
-            } else {
-                eprintln!("Warning: version ID is empty for {:?}", file_path);
             }
         }
 
-        let commit = Commit {
-            id: Uuid::new_v4().to_string(),
+        let mut commit = Commit {
+            id: String::new(),
+            tree: tree_id,
+            parents,
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_else(|_| std::time::Duration::from_secs(0))
                 .as_secs(),
-            author: author.to_string(),
+            author: identity.author.to_string(),
+            committer: identity.committer.to_string(),
             branch,
             message: message.to_string(),
-            files: modified_files.iter().map(|f| f.to_string_lossy().to_string()).collect(),
+            files: changed_files.iter().map(|f| f.to_string_lossy().to_string()).collect(),
+            deleted: changed_files.iter()
+                .filter(|path| !snapshot.contains_key(*path))
+                .map(|f| f.to_string_lossy().to_string())
+                .collect(),
             versions: versions_map,
         };
-        create_commit_files()?;
-        save_commit(&commit)?;
+        commit.id = commit.compute_id();
 
-        // Avoid creating a commit if no actual changes were recorded
-        if !has_actual_changes {
-            return Ok(None);
-        }
+        create_commit_files(repo)?;
+        save_commit(repo, &commit)?;
+        clear_merge_state(repo)?;
+
+        // Only once the commit is recorded are the staged changes no longer pending
+        tree.clear_modified();
+        save(repo, &tree)?;
 
         Ok(Some(commit))
     }
+
+    /// Every file recorded by this commit, mapped to its blob id
+    pub fn snapshot(&self, store: &ObjectStore) -> Result<BTreeMap<PathBuf, String>> {
+        if self.tree.is_empty() {
+            // Commits made before snapshots were recorded have no tree
+            return Ok(BTreeMap::new());
+        }
+        read_tree(store, &self.tree)
+    }
+
+    /// Serialize the commit in the form stored in the object database.
+    /// Everything except the id is covered, so the output is reproducible.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut content = format!("tree {}\n", self.tree);
+        for parent in &self.parents {
+            content.push_str(&format!("parent {}\n", parent));
+        }
+        content.push_str(&format!("author {}\n", self.author));
+        // Left out when empty so the ids of older commits still verify
+        if !self.committer.is_empty() {
+            content.push_str(&format!("committer {}\n", self.committer));
+        }
+        content.push_str(&format!("timestamp {}\n", self.timestamp));
+
+        content.push('\n');
+        content.push_str(&self.message);
+        content.into_bytes()
+    }
+
+    /// The id a commit with this content must have
+    pub fn compute_id(&self) -> String {
+        hash_object(ObjectKind::Commit, &self.encode())
+    }
+
+    /// Check that the id still matches the content, detecting tampered history
+    pub fn verify(&self) -> bool {
+        self.id == self.compute_id()
+    }
 }
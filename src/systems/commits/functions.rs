@@ -1,76 +1,160 @@
-use std::{io, fs};
-use std::io::Read;
+use std::fs;
 use std::fs::File;
-use std::path::Path;
+use std::path::PathBuf;
 use super::commit::Commit;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::systems::atomic::write_atomic;
+use crate::systems::refs::{head_commit, read_ref, update_head};
+use crate::systems::objects::object::ObjectKind;
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
 
-const COMMITS_FILE: &str = ".grit/commits.json";
-pub const HEAD_FILE: &str = ".grit/HEAD";
+/// The commit database, inside `.grit`
+pub const COMMITS_FILE: &str = "commits.json";
 
-pub fn create_commit_files() -> io::Result<()> {
-    // Ensure HEAD file exists before writing
-    if !Path::new(HEAD_FILE).exists() || !Path::new(COMMITS_FILE).exists() {
-        if let Some(parent) = Path::new(COMMITS_FILE).parent() {
-            fs::create_dir_all(parent)?;
-        }
-        if let Some(parent) = Path::new(HEAD_FILE).parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        File::create(COMMITS_FILE)?;
-        File::create(HEAD_FILE)?; // Create HEAD file if it doesn’t exist
+pub fn create_commit_files(repo: &Repository) -> Result<()> {
+    // HEAD is managed by `refs`, only the commit database has to exist
+    let commits_file = repo.grit_path(COMMITS_FILE);
+    if !commits_file.exists() {
+        fs::create_dir_all(repo.grit_dir())?;
+        File::create(commits_file)?;
     }
 
     Ok(())
 }
 
-/// Save a new commit and update HEAD
-pub fn save_commit(commit: &Commit) -> io::Result<()> {
-    let mut commits = load_all_commits()?; // Avoid unwrap()
+/// Save a new commit and move the current branch (or detached HEAD) to it
+pub fn save_commit(repo: &Repository, commit: &Commit) -> Result<()> {
+    let mut commits = load_all_commits(repo)?; // Avoid unwrap()
+
+    // Keep the commit in the object database, where its id is the hash of its content
+    repo.objects().write(ObjectKind::Commit, &commit.encode())?;
 
     // Save the new commit
     commits.insert(commit.id.clone(), commit.clone());
-    let json = serde_json::to_string_pretty(&commits)?;
-    fs::write(COMMITS_FILE, json)?;
-    fs::write(HEAD_FILE, &commit.id.trim())?;
+    save_all_commits(repo, &commits)?;
+    update_head(repo, commit.id.trim())?;
 
     Ok(())
 }
 
-/// Get the last commit ID from HEAD
-pub fn get_head_commit() -> io::Result<Option<String>> {
-    if Path::new(HEAD_FILE).exists() {
-        let mut head = String::new();
-        File::open(HEAD_FILE)?.read_to_string(&mut head)?;
-        return Ok(Some(head.trim().to_string())); // Trim any newlines
+/// Get the commit ID HEAD resolves to, `None` before the first commit on a branch
+pub fn get_head_commit(repo: &Repository) -> Result<Option<String>> {
+    head_commit(repo)
+}
+
+/// Snapshot of the commit HEAD points at, empty before the first commit
+pub fn head_snapshot(repo: &Repository, store: &ObjectStore) -> Result<BTreeMap<PathBuf, String>> {
+    let head = get_head_commit(repo)?.unwrap_or_default();
+    match load_commit(repo, &head) {
+        Ok(Some(commit)) => commit.snapshot(store),
+        _ => Ok(BTreeMap::new()),
     }
-    Ok(None)
 }
 
 /// Load a commit by ID
-pub fn load_commit(commit_id: &str) -> io::Result<Option<Commit>> {
-    let commits = load_all_commits()?;
+pub fn load_commit(repo: &Repository, commit_id: &str) -> Result<Option<Commit>> {
+    let commits = load_all_commits(repo)?;
     Ok(commits.get(commit_id).cloned())
 }
 
-/// Load all commits
-pub fn load_all_commits() -> io::Result<HashMap<String, Commit>> {
-    let json = fs::read_to_string(COMMITS_FILE)?;
-    let commits: HashMap<String, Commit> = serde_json::from_str(&json).unwrap_or_else(|_| HashMap::new()); // Handle invalid JSON
-    Ok(commits)
+/// Resolve a revision to a commit: `HEAD`, a full or abbreviated commit id, or a branch name
+pub fn resolve_commit(repo: &Repository, rev: &str) -> Result<Commit> {
+    let unknown = || GritError::MissingRef(format!("Unknown revision: {}", rev));
+    if !repo.grit_path(COMMITS_FILE).exists() {
+        return Err(unknown());
+    }
+    let commits = load_all_commits(repo)?;
+
+    let rev = if rev == "HEAD" { get_head_commit(repo)?.unwrap_or_default() } else { rev.to_string() };
+    if let Some(commit) = commits.get(&rev) {
+        return Ok(commit.clone());
+    }
+
+    if let Some(commit) = read_ref(repo, &rev)?.and_then(|id| commits.get(&id)) {
+        return Ok(commit.clone());
+    }
+
+    // Abbreviated ids must be long enough and match a single commit
+    if rev.len() >= 4 && rev.chars().all(|c| c.is_ascii_hexdigit()) {
+        let mut matches = commits.values().filter(|c| c.id.starts_with(&rev));
+        if let Some(commit) = matches.next() {
+            if matches.next().is_some() {
+                return Err(GritError::InvalidArgument(format!("Ambiguous revision: {}", rev)));
+            }
+            return Ok(commit.clone());
+        }
+    }
+
+    Err(unknown())
 }
 
-/// Reads a file as UTF-8 or falls back to best-effort conversion
-pub fn read_file(file_path: &str) -> io::Result<String> {
-    let mut raw_content = Vec::new();
-    File::open(file_path)?.read_to_end(&mut raw_content)?;
+/// Load all commits. The database starts out as an empty file; anything else that does not
+/// parse is an error, so a damaged history is never mistaken for an empty one.
+pub fn load_all_commits(repo: &Repository) -> Result<HashMap<String, Commit>> {
+    let json = fs::read_to_string(repo.grit_path(COMMITS_FILE))?;
+    if json.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    serde_json::from_str(&json).map_err(|e| GritError::corrupt_from(format!("{} is corrupt", COMMITS_FILE), e))
+}
+
+/// Replace the commit database
+pub fn save_all_commits(repo: &Repository, commits: &HashMap<String, Commit>) -> Result<()> {
+    let json = serde_json::to_string_pretty(commits)?;
+    write_atomic(repo.grit_path(COMMITS_FILE), json)
+}
+
+/// Walk the commit graph from `start_id` through every parent, newest commits first
+pub fn commit_history(repo: &Repository, start_id: &str) -> Result<Vec<Commit>> {
+    let commits = load_all_commits(repo)?;
+    let mut history = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending: Vec<&Commit> = commits.get(start_id).into_iter().collect();
+
+    while !pending.is_empty() {
+        // Always continue with the most recent commit so merged histories interleave by date
+        let newest = (0..pending.len()).max_by_key(|&i| pending[i].timestamp).unwrap_or(0);
+        let commit = pending.swap_remove(newest);
+
+        if !visited.insert(commit.id.as_str()) {
+            continue;
+        }
+        pending.extend(commit.parents.iter().filter_map(|parent| commits.get(parent)));
+        history.push(commit.clone());
+    }
+
+    Ok(history)
+}
+
+/// Best common ancestor of two commits: one reachable from both that is not an ancestor
+/// of another such commit, the most recent if there are several. `None` when the
+/// histories are unrelated.
+pub fn merge_base(repo: &Repository, first: &str, second: &str) -> Result<Option<String>> {
+    let commits = load_all_commits(repo)?;
+    let from_first = reachable(&commits, [first]);
+    let from_second = reachable(&commits, [second]);
+    let common: HashSet<&str> = from_first.intersection(&from_second).copied().collect();
+
+    // Whatever the parents of common ancestors lead to is older than them
+    let older = reachable(&commits, common.iter().flat_map(|id| commits[*id].parents.iter().map(String::as_str)));
+    let mut best: Vec<&str> = common.into_iter().filter(|id| !older.contains(id)).collect();
+    best.sort_by_key(|id| (std::cmp::Reverse(commits[*id].timestamp), *id));
+    Ok(best.first().map(|id| id.to_string()))
+}
 
-    match std::str::from_utf8(&raw_content) {
-        Ok(text) => Ok(text.to_string()), // Return valid UTF-8 string
-        Err(_) => {
-            println!("Warning: File is not UTF-8 encoded, converting with loss."); // More descriptive message
-            Ok(String::from_utf8_lossy(&raw_content).to_string()) // Convert with lossy UTF-8
+/// Ids of the given commits and all their ancestors
+pub(crate) fn reachable<'a>(commits: &'a HashMap<String, Commit>, start: impl IntoIterator<Item = &'a str>) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut pending: Vec<&str> = start.into_iter().collect();
+    while let Some(id) = pending.pop() {
+        let Some((id, commit)) = commits.get_key_value(id) else {
+            continue;
+        };
+        if seen.insert(id.as_str()) {
+            pending.extend(commit.parents.iter().map(String::as_str));
         }
     }
+    seen
 }
@@ -1,28 +1,58 @@
-use chrono::NaiveDateTime;
-use crate::systems::commits::commit::Commit;
+use std::collections::BTreeMap;
+use chrono::DateTime;
 use crate::systems::init::get_current_branch;
-use crate::systems::commits::functions::load_all_commits;
-
-/// Log all commits
-pub fn log() -> std::io::Result<()> {
-    let branch = get_current_branch()?;  // Get the current branch
-    let commits = load_all_commits()?;   // Load all commits
+use crate::systems::commits::commit::Commit;
+use crate::systems::commits::functions::{commit_history, get_head_commit, load_commit};
+use crate::systems::diff::{file_diffs, format_stat};
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::repository::Repository;
+use crate::error::Result;
 
-    // Filter commits for the current branch and collect into a vector
-    let commits: Vec<&Commit> = commits.values()
-        .filter(|commit| commit.branch == branch)
-        .collect();
+/// Every commit reachable from HEAD, with the files each one changed when `stat` is set
+pub fn format_log(repo: &Repository, stat: bool) -> Result<String> {
+    let branch = get_current_branch(repo)?;  // Get the current branch
+    let head = get_head_commit(repo)?.unwrap_or_default();
+    let commits = if head.is_empty() { Vec::new() } else { commit_history(repo, &head)? };
+    let store = repo.objects();
 
-    println!("\tOn branch: {}\n", branch);
+    let mut out = if branch == "HEAD" {
+        format!("\tHEAD detached at {}\n\n", head)
+    } else {
+        format!("\tOn branch: {}\n\n", branch)
+    };
 
     for commit in &commits {
-        let datetime = NaiveDateTime::from_timestamp(commit.timestamp as i64, 0);
+        let datetime = DateTime::from_timestamp(commit.timestamp as i64, 0).unwrap_or_default();
 
-        println!("commit {}", commit.id);
-        println!("Author: {}", commit.author);
-        println!("Date: {}", datetime.format("%Y-%m-%d %H:%M:%S"));
-        println!("\n{}\n", commit.message);
+        out.push_str(&format!("commit {}\n", commit.id));
+        if commit.parents.len() > 1 {
+            out.push_str(&format!("Merge: {}\n", commit.parents.join(" ")));
+        }
+        out.push_str(&format!("Author: {}\n", commit.author));
+        if !commit.committer.is_empty() && commit.committer != commit.author {
+            out.push_str(&format!("Commit: {}\n", commit.committer));
+        }
+        out.push_str(&format!("Date: {}\n", datetime.format("%Y-%m-%d %H:%M:%S")));
+        out.push_str(&format!("\n{}\n\n", commit.message));
+
+        if stat {
+            out.push_str(&commit_stat(repo, &store, commit)?);
+            out.push('\n');
+        }
     }
 
-    Ok(())
+    Ok(out)
+}
+
+/// `--stat` summary of a commit against its first parent
+pub fn commit_stat(repo: &Repository, store: &ObjectStore, commit: &Commit) -> Result<String> {
+    let parent = match commit.parents.first() {
+        Some(parent) => match load_commit(repo, parent)? {
+            Some(parent) => parent.snapshot(store)?,
+            None => BTreeMap::new(),
+        },
+        None => BTreeMap::new(),
+    };
+    let diffs = file_diffs(repo, store, &parent, &commit.snapshot(store)?, |_, hash| Ok(store.read(hash)?.1))?;
+    Ok(format_stat(&diffs))
 }
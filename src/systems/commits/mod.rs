@@ -1,8 +1,8 @@
+#[cfg(test)]
 mod test;
 pub mod log;
 pub mod commit;
+#[cfg(test)]
 mod version_test;
-pub(super) mod model;
-pub(super) mod functions;
-pub(super) mod versioning;
-mod function_test;
+pub mod functions;
+pub mod versioning;
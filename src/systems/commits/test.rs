@@ -1,67 +1,69 @@
 use super::commit::Commit;
 use super::functions::{
-    HEAD_FILE,
     save_commit, load_commit,
-    load_all_commits, get_head_commit
+    load_all_commits, get_head_commit,
+    commit_history
 };
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{tester, TempRepo};
     use std::fs::File;
     use std::io::Write;
-    use std::{env, fs, io};
+    use std::{fs, io};
     use crate::systems::add::add;
     use std::path::{Path, PathBuf};
-    use crate::systems::init::init_grit;
+    use crate::systems::objects::object::{hash_object, ObjectKind};
+    use crate::systems::commits::versioning::{version_file, Version};
+    use crate::systems::commits::log::commit_stat;
+    use crate::systems::refs::HEAD_FILE;
+    use crate::error::GritError;
 
-    fn setup() -> io::Result<()> {
-        // Initialize a .grit repository
-        init_grit()?;
+    fn setup() -> io::Result<TempRepo> {
+        let repo = TempRepo::new();
         // Ensure the .grit directory exists
-        let grit_path = env::current_dir()?.join(".grit");
-        if !grit_path.exists() {
+        if !repo.grit_dir().exists() {
             return Err(io::Error::new(io::ErrorKind::NotFound, "Failed to create .grit directory"));
         }
-        Ok(())
+        Ok(repo)
     }
 
-    fn create_and_modify_file() -> io::Result<PathBuf> {
-        setup()?;
-
+    fn create_and_modify_file(repo: &TempRepo) -> io::Result<PathBuf> {
         // Create a dummy file
         let file_path = Path::new("test_file.txt").to_path_buf();
-        let mut file = File::create(&file_path)?;
+        let mut file = File::create(repo.working_path(&file_path))?;
         writeln!(file, "This is the initial content.")?;
         file.sync_all()?;
 
         // Add the file (first commit)
-        add(Some(file_path.to_str().unwrap()))?;
-        let commit1 = Commit::new("Initial Commit", "Tester")?;
-        save_commit(&commit1.unwrap())?;
+        add(repo, Some(file_path.to_str().unwrap()))?;
+        let commit1 = Commit::new(repo, "Initial Commit", &tester())?;
+        save_commit(repo, &commit1.unwrap())?;
 
         // Modify the file
-        let mut file = File::options().append(true).open(&file_path)?;
+        let mut file = File::options().append(true).open(repo.working_path(&file_path))?;
         writeln!(file, "This is a modified line.")?;
         file.sync_all()?;
 
         // Add the modified file (second commit)
-        add(Some(file_path.to_str().unwrap()))?;
+        add(repo, Some(file_path.to_str().unwrap()))?;
         Ok(file_path)
     }
 
     #[test]
     fn test_commit_creation_no_changes() {
-        setup().unwrap();
-        let commit = Commit::new("Test Commit", "Author").unwrap();
+        let repo = setup().unwrap();
+        let commit = Commit::new(&repo, "Test Commit", &tester()).unwrap();
         assert!(commit.is_none(), "Commit should not be created if there are no changes.");
     }
 
     #[test]
     fn test_commit_creation_with_changes() {
-        let file_path = create_and_modify_file().unwrap();
+        let repo = setup().unwrap();
+        let file_path = create_and_modify_file(&repo).unwrap();
 
-        let commit = Commit::new("Modified Commit", "Author").unwrap();
+        let commit = Commit::new(&repo, "Modified Commit", &tester()).unwrap();
         assert!(commit.is_some(), "Commit should be created when there are modifications.");
         let commit = commit.unwrap();
 
@@ -72,38 +74,216 @@ mod tests {
 
     #[test]
     fn test_save_commit_and_retrieve() {
-        let commit = Commit::new("Initial Commit", "Tester").unwrap().unwrap();
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        let commit = Commit::new(&repo, "Initial Commit", &tester()).unwrap().unwrap();
 
         // Save commit
-        save_commit(&commit).unwrap();
+        save_commit(&repo, &commit).unwrap();
 
         // Check if the commit is saved correctly
-        let saved_commits = load_all_commits().unwrap();
+        let saved_commits = load_all_commits(&repo).unwrap();
         assert!(saved_commits.contains_key(&commit.id), "Commit should be saved in commits.json");
 
         // Check HEAD update
-        let head_commit = get_head_commit().unwrap();
+        let head_commit = get_head_commit(&repo).unwrap();
         assert_eq!(head_commit, Some(commit.id.clone()), "HEAD should point to latest commit");
     }
 
     #[test]
     fn test_load_commit_by_id() {
-        let commit = Commit::new("Feature Commit", "Developer").unwrap().unwrap();
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        let commit = Commit::new(&repo, "Feature Commit", &tester()).unwrap().unwrap();
 
         // Save the commit
-        save_commit(&commit).unwrap();
+        save_commit(&repo, &commit).unwrap();
 
         // Load the commit back
-        let loaded_commit = load_commit(&commit.id).unwrap();
+        let loaded_commit = load_commit(&repo, &commit.id).unwrap();
         assert!(loaded_commit.is_some(), "Commit should be found");
         assert_eq!(loaded_commit.unwrap().id, commit.id, "Loaded commit should match the original");
     }
 
     #[test]
     fn test_get_head_commit_no_commits() {
+        let repo = TempRepo::new();
         // Ensure HEAD file does not exist
-        fs::remove_file(HEAD_FILE).ok();
-        let head_commit = get_head_commit().unwrap();
+        fs::remove_file(repo.grit_path(HEAD_FILE)).ok();
+        let head_commit = get_head_commit(&repo).unwrap();
         assert!(head_commit.is_none(), "HEAD should be None if no commits exist");
     }
+
+    #[test]
+    fn test_commits_link_to_their_parent() {
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        let first_id = get_head_commit(&repo).unwrap().unwrap();
+
+        let second = Commit::new(&repo, "Second Commit", &tester()).unwrap().unwrap();
+        assert_eq!(second.parents, vec![first_id.clone()]);
+
+        let first = load_commit(&repo, &first_id).unwrap().unwrap();
+        assert!(first.parents.is_empty(), "The first commit should be a root commit");
+
+        let history: Vec<String> = commit_history(&repo, &second.id).unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(history, vec![second.id.clone(), first_id]);
+    }
+
+    #[test]
+    fn test_commit_id_is_derived_from_content() {
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        let mut commit = Commit::new(&repo, "Content Commit", &tester()).unwrap().unwrap();
+
+        assert_eq!(commit.id, commit.compute_id());
+        assert_eq!(commit.id.len(), 40);
+        assert!(commit.verify());
+
+        // The commit object is stored under its id
+        let store = repo.objects();
+        let (kind, data) = store.read(&commit.id).unwrap();
+        assert_eq!(kind, ObjectKind::Commit);
+        assert_eq!(data, commit.encode());
+
+        // Any change to the content invalidates the id
+        commit.message = "Rewritten history".to_string();
+        assert!(!commit.verify());
+    }
+
+    #[test]
+    fn test_history_follows_every_parent() {
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        let root = load_commit(&repo, &get_head_commit(&repo).unwrap().unwrap()).unwrap().unwrap();
+
+        // Two commits sharing the same parent, joined by a merge commit
+        let mut left = root.clone();
+        left.parents = vec![root.id.clone()];
+        left.message = "left".to_string();
+        left.timestamp += 1;
+        left.id = left.compute_id();
+
+        let mut right = left.clone();
+        right.message = "right".to_string();
+        right.timestamp += 1;
+        right.id = right.compute_id();
+
+        let mut merge = root.clone();
+        merge.parents = vec![left.id.clone(), right.id.clone()];
+        merge.message = "merge".to_string();
+        merge.timestamp += 3;
+        merge.id = merge.compute_id();
+
+        for commit in [&left, &right, &merge] {
+            save_commit(&repo, commit).unwrap();
+        }
+
+        let history: Vec<String> = commit_history(&repo, &merge.id).unwrap().into_iter().map(|c| c.message).collect();
+        assert_eq!(history, vec!["merge", "right", "left", "Initial Commit"]);
+    }
+
+    #[test]
+    fn test_commit_snapshot_includes_unchanged_files() {
+        let repo = setup().unwrap();
+        repo.write("stable.txt", "never changes\n").unwrap();
+        repo.write("edited.txt", "first\n").unwrap();
+        add(&repo, None).unwrap();
+        let first = Commit::new(&repo, "First", &tester()).unwrap().unwrap();
+
+        repo.write("edited.txt", "second\n").unwrap();
+        add(&repo, None).unwrap();
+        let second = Commit::new(&repo, "Second", &tester()).unwrap().unwrap();
+
+        // Only the edited file changed, but the snapshot still has both
+        assert_eq!(second.files, vec!["edited.txt".to_string()]);
+        let store = repo.objects();
+        let files = second.snapshot(&store).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[Path::new("stable.txt")], first.snapshot(&store).unwrap()[Path::new("stable.txt")]);
+
+        let (_, content) = store.read(&files[Path::new("edited.txt")]).unwrap();
+        assert_eq!(content, b"second\n");
+    }
+
+    #[test]
+    fn test_no_commit_when_snapshot_is_unchanged() {
+        let repo = setup().unwrap();
+        create_and_modify_file(&repo).unwrap();
+        Commit::new(&repo, "Second", &tester()).unwrap().unwrap();
+
+        // Re-adding identical content must not produce an empty commit
+        add(&repo, None).unwrap();
+        assert!(Commit::new(&repo, "Nothing", &tester()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_damaged_version_chain_is_not_replaced() {
+        let repo = setup().unwrap();
+        repo.commit_file("a.txt", "one\n", "First").unwrap();
+        let chain = ".grit/versions/Main/a.txt.json";
+        repo.write(chain, "{\"original\": ").unwrap();
+
+        repo.write("a.txt", "two\n").unwrap();
+        add(&repo, None).unwrap();
+        let error = Commit::new(&repo, "Second", &tester()).unwrap_err();
+        assert!(matches!(error, GritError::CorruptObject { .. }), "{:?}", error);
+        assert_eq!(repo.read(chain).unwrap(), "{\"original\": ", "The damaged chain is left for fsck to report");
+    }
+
+    #[test]
+    fn test_versions_record_the_staged_content() {
+        let repo = setup().unwrap();
+        repo.write("a.txt", "one\n").unwrap();
+        add(&repo, None).unwrap();
+        repo.write("a.txt", "edited after adding\n").unwrap();
+        let first = Commit::new(&repo, "First", &tester()).unwrap().unwrap();
+        assert_eq!(first.versions["a.txt"], hash_object(ObjectKind::Blob, b"one\n"));
+
+        repo.write("a.txt", "two\n").unwrap();
+        add(&repo, None).unwrap();
+        repo.write("a.txt", "three\n").unwrap();
+        let second = Commit::new(&repo, "Second", &tester()).unwrap().unwrap();
+
+        // The chain follows what was added, not the working file
+        let chain = Version::load(&repo, &version_file(&repo, "Main", Path::new("a.txt"))).unwrap();
+        assert_eq!(chain.reconstruct_latest().unwrap(), b"two\n");
+        assert_eq!(chain.reconstruct_version(&repo, &second.versions["a.txt"]).unwrap().unwrap(), b"two\n");
+    }
+
+    #[test]
+    fn test_missing_staged_blob_fails_the_commit() {
+        let repo = setup().unwrap();
+        repo.write("a.txt", "one\n").unwrap();
+        add(&repo, None).unwrap();
+        let blob = hash_object(ObjectKind::Blob, b"one\n");
+        repo.objects().remove_loose(&blob).unwrap();
+
+        let error = Commit::new(&repo, "First", &tester()).unwrap_err();
+        assert!(matches!(error, GritError::CorruptObject { .. }), "{:?}", error);
+        assert!(!repo.objects().exists(&blob), "The working file must not stand in for the staged blob");
+    }
+
+    #[test]
+    fn test_log_stat_reports_renames_and_line_counts() {
+        let repo = setup().unwrap();
+        repo.write("old_name.txt", "a\nb\nc\nd\n").unwrap();
+        repo.write("edited.txt", "1\n").unwrap();
+        add(&repo, None).unwrap();
+        Commit::new(&repo, "First", &tester()).unwrap().unwrap();
+
+        fs::rename(repo.working_path("old_name.txt"), repo.working_path("new_name.txt")).unwrap();
+        repo.write("new_name.txt", "a\nb\nc\nD\n").unwrap();
+        repo.write("edited.txt", "1\n2\n3\n").unwrap();
+        add(&repo, None).unwrap();
+        let second = Commit::new(&repo, "Second", &tester()).unwrap().unwrap();
+        assert_eq!(second.deleted, vec!["old_name.txt".to_string()]);
+
+        let store = repo.objects();
+        assert_eq!(commit_stat(&repo, &store, &second).unwrap(), concat!(
+            " edited.txt                   | 2 ++\n",
+            " old_name.txt => new_name.txt | 2 +-\n",
+            " 2 files changed, 3 insertions(+), 1 deletion(-)\n",
+        ));
+    }
 }
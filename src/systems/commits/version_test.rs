@@ -3,93 +3,346 @@ use super::versioning::Version;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::systems::commits::versioning::version_file;
+    use crate::systems::fsck::fsck;
+    use crate::systems::objects::pack::repack;
+    use crate::test_support::{Lcg, TempRepo};
     use std::fs;
+    use std::path::Path;
 
     const TEST_FILE: &str = "test_file.txt";
     const TEST_JSON: &str = "test_versions.json";
 
-    /// Helper function to clean up test files
-    fn cleanup() {
-        let _ = fs::remove_file(TEST_FILE);
-        let _ = fs::remove_file(TEST_JSON);
+    /// Current content of the test file, standing in for its staged blob
+    fn staged(repo: &TempRepo) -> Vec<u8> {
+        fs::read(repo.working_path(TEST_FILE)).unwrap()
     }
 
     #[test]
     fn test_create_version_file() {
-        cleanup();
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
 
-        let result = Version::create(TEST_FILE, TEST_JSON);
+        let result = Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json);
         assert!(result.is_ok(), "Failed to create version file");
 
-        let version_data = Version::load(TEST_JSON).unwrap();
+        let version_data = Version::load(&repo, &json).unwrap();
         assert_eq!(version_data.original, "Hello, world!\nThis is version 0.\n");
         assert!(version_data.versions.is_empty(), "New file should have no versions");
     }
 
     #[test]
     fn test_add_version() {
-        cleanup();
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
-        let mut version_data = Version::load(TEST_JSON).unwrap_or_else(|_| {
-            Version::create(TEST_FILE, TEST_JSON).unwrap();
-            Version::load(TEST_JSON).unwrap()
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap_or_else(|_| {
+            Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            Version::load(&repo, &json).unwrap()
         });
 
         // Modify file content
-        fs::write(TEST_FILE, "Hello, universe!\nThis is version 1.\n").unwrap();
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
+        repo.write(TEST_FILE, "Hello, universe!\nThis is version 1.\n").unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
         assert_eq!(version_data.versions.len(), 1, "Should have one version entry");
 
         // Check that the latest reconstruction matches the new content
-        let latest_version = version_data.reconstruct_latest();
-        assert_eq!(latest_version, "Hello, universe!\nThis is version 1.");
+        let latest_version = version_data.reconstruct_latest().unwrap();
+        assert_eq!(latest_version, b"Hello, universe!\nThis is version 1.\n");
     }
 
     #[test]
     fn test_reconstruct_version() {
-        cleanup();
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
-        let mut version_data = Version::load(TEST_JSON).unwrap_or_else(|_| {
-            Version::create(TEST_FILE, TEST_JSON).unwrap();
-            Version::load(TEST_JSON).unwrap()
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap_or_else(|_| {
+            Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            Version::load(&repo, &json).unwrap()
         });
 
         // Modify file and add a new version
-        fs::write(TEST_FILE, "Hello, universe!\nThis is version 1.\n").unwrap();
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
+        repo.write(TEST_FILE, "Hello, universe!\nThis is version 1.\n").unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
         let version_id = version_data.versions[0].version_id.clone();
 
         // Further modify file and add another version
-        fs::write(TEST_FILE, "Hello, universe!\nThis is version 2.\n").unwrap();
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
+        repo.write(TEST_FILE, "Hello, universe!\nThis is version 2.\n").unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
 
         assert_eq!(version_data.versions.len(), 2, "Should have two versions");
 
         // Retrieve first version
-        let reconstructed_v1 = version_data.reconstruct_version(&version_id);
+        let reconstructed_v1 = version_data.reconstruct_version(&repo, &version_id).unwrap();
         assert!(reconstructed_v1.is_some(), "Version 1 should exist");
-        assert_eq!(reconstructed_v1.unwrap(), "Hello, universe!\nThis is version 1.");
+        assert_eq!(reconstructed_v1.unwrap(), b"Hello, universe!\nThis is version 1.\n");
     }
 
     #[test]
     fn test_no_changes_detected() {
-        cleanup();
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
-        let mut version_data = Version::load(TEST_JSON).unwrap_or_else(|_| {
-            Version::create(TEST_FILE, TEST_JSON).unwrap();
-            Version::load(TEST_JSON).unwrap()
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap_or_else(|_| {
+            Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            Version::load(&repo, &json).unwrap()
         });
 
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
         assert_eq!(version_data.versions.len(), 0, "No new version should be created if no changes");
 
         // Modify file and then revert it back
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 1.\n").unwrap();
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
-        fs::write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
-        version_data.add_version(TEST_FILE, TEST_JSON).unwrap();
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 1.\n").unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        repo.write(TEST_FILE, "Hello, world!\nThis is version 0.\n").unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
 
-        assert_eq!(version_data.versions.len(), 2, "Only one version should be stored after a revert");
+        assert_eq!(version_data.history(&repo).unwrap().len(), 2, "Only one version should be stored after a revert");
+    }
+
+    /// Builds a text file with the whitespace details that must survive versioning
+    fn random_content(rng: &mut Lcg) -> String {
+        const LINES: [&str; 6] = ["fn main() {", "    let x = 1;", "\tindented with tab", "trailing spaces   ", "", "}"];
+        const ENDINGS: [&str; 2] = ["\n", "\r\n"];
+
+        let mut content = String::new();
+        for _ in 0..rng.next(12) {
+            content.push_str(LINES[rng.next(LINES.len() as u64) as usize]);
+            content.push_str(ENDINGS[rng.next(ENDINGS.len() as u64) as usize]);
+        }
+        // Sometimes drop the final newline
+        if rng.next(2) == 0 {
+            content.pop();
+        }
+        content
+    }
+
+    #[test]
+    fn test_every_version_reconstructs_exact_bytes() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        let mut rng = Lcg(7);
+
+        let original = random_content(&mut rng);
+        repo.write(TEST_FILE, &original).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+        assert_eq!(version_data.original, original);
+
+        let mut expected = Vec::new();
+        for _ in 0..100 {
+            let content = random_content(&mut rng);
+            repo.write(TEST_FILE, &content).unwrap();
+
+            let version_id = version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            if !version_id.is_empty() {
+                expected.push((version_id, content));
+            }
+        }
+
+        // Reload to make sure the stored deltas are enough on their own
+        let version_data = Version::load(&repo, &json).unwrap();
+        for (version_id, content) in &expected {
+            assert_eq!(version_data.reconstruct_version(&repo, version_id).unwrap().as_deref(), Some(content.as_bytes()));
+        }
+        assert_eq!(version_data.reconstruct_latest().unwrap(), expected.last().unwrap().1.as_bytes());
+    }
+
+    #[test]
+    fn test_binary_versions_are_byte_exact_deltas() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        let mut rng = Lcg(5);
+
+        // NUL bytes and invalid UTF-8 must survive untouched
+        let mut content: Vec<u8> = (0..20_000).map(|_| rng.next(256) as u8).collect();
+        content[0] = 0;
+        repo.write(TEST_FILE, &content).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+        assert!(version_data.binary);
+
+        let mut expected = Vec::new();
+        for round in 0..5 {
+            let at = rng.next(content.len() as u64) as usize;
+            content.splice(at..at, vec![0xff, round, 0x00]);
+            repo.write(TEST_FILE, &content).unwrap();
+            let version_id = version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            expected.push((version_id, content.clone()));
+        }
+
+        let version_data = Version::load(&repo, &json).unwrap();
+        for (version_id, content) in &expected {
+            assert_eq!(version_data.reconstruct_version(&repo, version_id).unwrap().as_ref(), Some(content));
+        }
+        let delta = version_data.versions[0].delta.as_ref().expect("Binary revisions are stored as deltas");
+        assert!(delta.len() < 1000, "A small edit must not store a full copy");
+    }
+
+    #[test]
+    fn test_file_switching_between_text_and_binary() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        // Large enough for the changes to stay smaller than a full copy
+        let text = "plain text\n".repeat(100);
+        repo.write(TEST_FILE, &text).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+        assert!(!version_data.binary);
+
+        repo.write(TEST_FILE, format!("{}now\0binary\n", text)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        repo.write(TEST_FILE, format!("{}text again\n", text)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        repo.write(TEST_FILE, format!("{}text again\nand more\n", text)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+
+        let kinds: Vec<bool> = version_data.versions.iter().map(|version| version.delta.is_some()).collect();
+        assert_eq!(kinds, vec![true, true, false], "Line edits are used between text versions only");
+        let expected = format!("{}text again\nand more\n", text);
+        assert_eq!(Version::load(&repo, &json).unwrap().reconstruct_latest().unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_attributes_mark_files_binary() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(".gritattributes", "*.txt binary\n").unwrap();
+        repo.write(TEST_FILE, "looks like text\n").unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        assert!(Version::load(&repo, &json).unwrap().binary);
+    }
+
+    /// Helper building a numbered text file where line `changed` carries `revision`
+    fn revision_content(lines: usize, changed: usize, revision: usize) -> String {
+        (0..lines)
+            .map(|line| if line == changed { format!("line {} revision {}\n", line, revision) } else { format!("line {}\n", line) })
+            .collect()
+    }
+
+    #[test]
+    fn test_keyframes_bound_the_replayed_chain() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        // Long enough for 64 small changes to stay below the size of a full copy
+        repo.write(TEST_FILE, revision_content(600, 0, 0)).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+
+        let mut expected = Vec::new();
+        for revision in 1..=130 {
+            let content = revision_content(600, revision, revision);
+            repo.write(TEST_FILE, &content).unwrap();
+            expected.push((version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap(), content));
+        }
+
+        let history = version_data.history(&repo).unwrap();
+        let keyframes: Vec<usize> = history.iter()
+            .enumerate()
+            .filter(|(_, version)| version.keyframe.is_some())
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(keyframes, vec![63, 127], "A full copy every 64 versions");
+        assert!(history[63].changes.is_empty(), "Keyframes do not store changes as well");
+        // Only the versions since the last keyframe are left in the chain file
+        assert_eq!(version_data.segments.len(), 2);
+        assert_eq!(version_data.versions.len(), 3);
+        assert_eq!(version_data.verify(&repo).unwrap(), 130);
+
+        let version_data = Version::load(&repo, &json).unwrap();
+        for (version_id, content) in &expected {
+            assert_eq!(version_data.reconstruct_version(&repo, version_id).unwrap().as_deref(), Some(content.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_large_change_becomes_a_keyframe() {
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, "short\n").unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+
+        // Changes bigger than the original are cheaper to store whole
+        repo.write(TEST_FILE, "a completely different and much longer content\n".repeat(10)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        assert!(version_data.versions[0].keyframe.is_some());
+
+        repo.write(TEST_FILE, "a completely different and much longer content\n".repeat(11)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        assert!(version_data.versions[1].keyframe.is_none(), "A small change after it is stored as edits");
+        assert_eq!(
+            version_data.reconstruct_latest().unwrap(),
+            "a completely different and much longer content\n".repeat(11).as_bytes(),
+        );
+    }
+
+    #[test]
+    fn test_repack_moves_chains_into_the_pack() {
+        let repo = TempRepo::new();
+        let json = version_file(&repo, "Main", Path::new(TEST_FILE));
+        repo.write(TEST_FILE, revision_content(50, 0, 0)).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+        for revision in 1..=3 {
+            repo.write(TEST_FILE, revision_content(50, revision, revision)).unwrap();
+            version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        }
+
+        repack(&repo).unwrap().expect("The chain is packed");
+        assert!(repo.objects().loose_ids().unwrap().is_empty());
+        let packed = Version::load(&repo, &json).unwrap();
+        assert!(repo.read(json.strip_prefix(repo.root()).unwrap()).unwrap().contains(packed.packed.as_deref().unwrap()));
+        assert_eq!(packed.reconstruct_latest().unwrap(), revision_content(50, 3, 3).as_bytes());
+        assert!(fsck(&repo).unwrap().is_ok());
+
+        // Adding to a packed chain writes it out in full again
+        let mut version_data = packed;
+        repo.write(TEST_FILE, revision_content(50, 4, 4)).unwrap();
+        version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let version_data = Version::load(&repo, &json).unwrap();
+        assert!(version_data.packed.is_none());
+        assert_eq!(version_data.verify(&repo).unwrap(), 4);
+    }
+
+    /// Benchmark: run with `cargo test --release -- --ignored --nocapture bench_`.
+    /// Times adding and reconstructing versions early and late in a long history.
+    #[test]
+    #[ignore]
+    fn bench_add_version_with_long_history() {
+        use std::time::{Duration, Instant};
+        const REVISIONS: usize = 3000;
+        const SAMPLE: usize = 100;
+
+        let repo = TempRepo::new();
+        let json = repo.working_path(TEST_JSON);
+        repo.write(TEST_FILE, revision_content(300, 0, 0)).unwrap();
+        Version::create(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+        let mut version_data = Version::load(&repo, &json).unwrap();
+
+        let mut add_times = Vec::new();
+        let mut reconstruct_times = Vec::new();
+        for revision in 1..=REVISIONS {
+            repo.write(TEST_FILE, revision_content(300, revision % 300, revision)).unwrap();
+            let start = Instant::now();
+            version_data.add_version(&repo, Path::new(TEST_FILE), &staged(&repo), &json).unwrap();
+            add_times.push(start.elapsed());
+
+            let start = Instant::now();
+            version_data.reconstruct_latest().unwrap();
+            reconstruct_times.push(start.elapsed());
+        }
+
+        let average = |times: &[Duration]| times.iter().sum::<Duration>() / times.len() as u32;
+        let (first_add, last_add) = (average(&add_times[..SAMPLE]), average(&add_times[REVISIONS - SAMPLE..]));
+        let (first_rebuild, last_rebuild) = (average(&reconstruct_times[..SAMPLE]), average(&reconstruct_times[REVISIONS - SAMPLE..]));
+        println!("add_version:        first {} {:?}, last {} {:?}", SAMPLE, first_add, SAMPLE, last_add);
+        println!("reconstruct_latest: first {} {:?}, last {} {:?}", SAMPLE, first_rebuild, SAMPLE, last_rebuild);
+
+        // Replaying is bounded by the keyframe interval, whatever the history length
+        assert!(last_rebuild < first_rebuild * 3, "Reconstruction must not grow with the history");
+        // Versions before the last keyframe are sealed away, so each commit writes a bounded amount
+        assert!(last_add < first_add * 2, "Adding a version must not grow with the history");
     }
 }
@@ -1,135 +1,371 @@
 use uuid::Uuid;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::Read;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 use crate::algorithms::vcompare::edit::Edit;
 use crate::algorithms::vcompare::compv::compare;
-use crate::algorithms::vcompare::utils::split_lines;
+use crate::algorithms::vcompare::utils::{join_lines, split_lines};
+use crate::algorithms::bdelta::{apply_delta, make_delta};
+use crate::systems::atomic::write_atomic;
+use crate::systems::filters::attributes::{looks_binary, Attributes};
+use crate::systems::gc::version_files;
+use crate::systems::objects::object::ObjectKind;
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Directory inside `.grit` holding a chain file per branch and tracked file
+pub const VERSIONS_DIR: &str = "versions";
+
+/// Longest run of versions stored as changes before one is stored whole, bounding how
+/// many changes reconstructing any version replays
+const KEYFRAME_INTERVAL: usize = 64;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Version {
+    /// First content of the file, hex encoded when `binary` is set
     pub original: String,
-    pub versions: Vec<VersionData>
+    /// Whether the file started out as binary content
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+    /// Blobs holding the versions before the latest keyframe, oldest first, each one
+    /// closed when a keyframe starts the next. Only later versions stay in the chain file,
+    /// so adding a version rewrites a bounded amount whatever the length of the history.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<String>,
+    /// The open segment: versions since the latest keyframe, starting with it
+    pub versions: Vec<VersionData>,
+    /// Blob the chain was read from when its file only refers to it, see `pack_chains`
+    #[serde(skip)]
+    pub packed: Option<String>,
+}
+
+/// What a chain file holds once its content has moved into the object store
+#[derive(Debug, Serialize, Deserialize)]
+struct PackedChain {
+    packed: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VersionData {
     pub version_id: String,
     pub timestamp: u64,
-    pub changes: Vec<Edit>
+    /// Line edits from the previous version, when both are text
+    #[serde(default)]
+    pub changes: Vec<Edit>,
+    /// Hex encoded binary delta from the previous version, used instead of `changes`
+    /// when either of them is binary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<String>,
+    /// Full content of the version, stored instead of changes from time to time so
+    /// reconstruction can start here rather than at `original`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyframe: Option<Keyframe>,
+}
+
+/// Content stored whole, hex encoded when binary.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Keyframe {
+    pub content: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+}
+
+impl Keyframe {
+    fn new(content: &[u8], binary_attribute: Option<bool>) -> Keyframe {
+        match as_text(content, binary_attribute) {
+            Some(text) => Keyframe { content: text.to_string(), binary: false },
+            None => Keyframe { content: hex::encode(content), binary: true },
+        }
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>> {
+        decode_content(&self.content, self.binary)
+    }
+
+    /// Number of bytes of the content, rather than of its hex encoding
+    fn size(&self) -> usize {
+        decoded_len(&self.content, self.binary)
+    }
+}
+
+impl VersionData {
+    /// Content of this version, given the content of the previous one
+    fn apply(&self, previous: &[u8]) -> Result<Vec<u8>> {
+        if let Some(keyframe) = &self.keyframe {
+            return keyframe.bytes();
+        }
+        match &self.delta {
+            Some(delta) => {
+                let delta = hex::decode(delta).map_err(|e| GritError::corrupt_from("Version delta is not valid hex", e))?;
+                apply_delta(previous, &delta).map_err(|e| GritError::corrupt_from("Version delta cannot be applied", e))
+            }
+            // Line edits are only recorded between text versions
+            None => Ok(apply_changes(&String::from_utf8_lossy(previous), &self.changes)?.into_bytes()),
+        }
+    }
+
+    /// Rough number of bytes the changes take, compared with the size of a full copy
+    fn stored_size(&self) -> usize {
+        if let Some(keyframe) = &self.keyframe {
+            return keyframe.size();
+        }
+        let edits: usize = self.changes.iter()
+            .map(|change| match change {
+                Edit::Delete(_) => 1,
+                Edit::Insert(_, text) | Edit::Replace(_, text) => text.len() + 1,
+            })
+            .sum();
+        edits + self.delta.as_ref().map_or(0, |delta| delta.len() / 2)
+    }
 }
 
 impl Version {
-    /// Creates a new version-tracked file
-    pub fn create(file_path: &str, json_path: &str) -> std::io::Result<()> {
-        let content = super::functions::read_file(file_path)?;
+    /// Creates a new version-tracked file starting at `content`, `file_path` being relative
+    /// to the root
+    pub fn create(repo: &Repository, file_path: &Path, content: &[u8], json_path: &Path) -> Result<()> {
+        let attributes = Attributes::load(repo.root())?;
 
-        let version_data = Version {
-            original: content.clone(),
-            versions: Vec::new(),
-        };
+        // Binary content is kept byte for byte rather than as text
+        let original = Keyframe::new(content, attributes.binary(file_path));
+        let version_data = Version { original: original.content, binary: original.binary, segments: Vec::new(), versions: Vec::new(), packed: None };
 
         let json = serde_json::to_string_pretty(&version_data)?;
 
         // Ensure the directory exists before writing
-        if let Some(parent) = Path::new(json_path).parent() {
+        if let Some(parent) = json_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        let mut file = File::create(json_path)?;
-        file.write_all(json.as_bytes())?;
+        write_atomic(json_path, json)?;
 
         Ok(())
     }
 
-    /// Load an existing tracked file
-    pub fn load(json_path: &str) -> std::io::Result<Version> {
+    /// Load an existing tracked file, from the object store when the file refers to it
+    pub fn load(repo: &Repository, json_path: &Path) -> Result<Version> {
         let mut file = File::open(json_path)?;
         let mut json_content = String::new();
         file.read_to_string(&mut json_content)?;
 
+        if let Ok(PackedChain { packed }) = serde_json::from_str(&json_content) {
+            let mut version_data: Version = read_blob(&repo.objects(), &packed, "Packed version chain")?;
+            version_data.packed = Some(packed);
+            return Ok(version_data);
+        }
         let version_data: Version = serde_json::from_str(&json_content)?;
         Ok(version_data)
     }
 
-    /// Adds a new version by computing differences
-    pub fn add_version(&mut self, file_path: &str, json_path: &str) -> std::io::Result<String> {
-        let new_content = super::functions::read_file(file_path)?;
-
-        // Normalize content (trim trailing whitespace & newlines)
-        let new_content = new_content.trim_end().to_string();
-        let last_content = self.reconstruct_latest().trim_end().to_string();
+    /// Objects the chain file needs: the blob it was packed into and its closed segments
+    pub fn objects(&self) -> impl Iterator<Item = &String> {
+        self.packed.iter().chain(&self.segments)
+    }
 
-        let changes = compare(&last_content, &new_content);
+    /// Adds `new_content` as a new version by computing differences
+    pub fn add_version(&mut self, repo: &Repository, file_path: &Path, new_content: &[u8], json_path: &Path) -> Result<String> {
+        let last_content = self.reconstruct_latest()?;
 
         // Avoid adding an unnecessary version if no real changes exist
-        if changes.is_empty() {
+        if new_content == last_content {
             return Ok("".to_string());
         }
 
+        // Text revisions are stored as line edits, anything involving binary content as a delta
+        let binary = Attributes::load(repo.root())?.binary(file_path);
+        let (changes, delta) = match (as_text(&last_content, binary), as_text(new_content, binary)) {
+            (Some(last), Some(new)) => (compare(last, new), None),
+            _ => (Vec::new(), Some(hex::encode(make_delta(&last_content, new_content)))),
+        };
+
         let id = Uuid::new_v4().to_string();
 
-        let new_version = VersionData {
+        let mut new_version = VersionData {
             version_id: id.clone(),
             timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
             changes,
+            delta,
+            keyframe: None,
         };
 
+        // Store the version whole once the chain since the last full copy is long enough,
+        // or once its changes add up to more than that copy
+        let (base_size, chain) = self.chain_since_keyframe();
+        let chain_size: usize = chain.iter().map(|version| version.stored_size()).sum::<usize>() + new_version.stored_size();
+        if chain.len() + 1 >= KEYFRAME_INTERVAL || chain_size > base_size {
+            new_version.changes = Vec::new();
+            new_version.delta = None;
+            new_version.keyframe = Some(Keyframe::new(new_content, binary));
+
+            // The versions before it never change again, so they move out of the chain file
+            if !self.versions.is_empty() {
+                let segment = serde_json::to_vec(&self.versions)?;
+                self.segments.push(repo.objects().write(ObjectKind::Blob, &segment)?);
+                self.versions.clear();
+            }
+        }
+
         self.versions.push(new_version);
 
+        // A packed chain goes back to being stored in its file, until the next repack
         let json = serde_json::to_string_pretty(&self)?;
-        fs::write(json_path, json)?;
+        write_atomic(json_path, json)?;
+        self.packed = None;
 
         Ok(id)
     }
 
-    /// Reconstructs the latest version from stored data
-    pub fn reconstruct_latest(&self) -> String {
-        let mut content = self.original.clone();
-        for version in &self.versions {
-            content = apply_changes(&content, &version.changes);
+    /// Reconstructs the latest version from stored data, reading the open segment only
+    pub fn reconstruct_latest(&self) -> Result<Vec<u8>> {
+        if !self.segments.is_empty() && self.versions.first().is_none_or(|version| version.keyframe.is_none()) {
+            return Err(GritError::corrupt("Version chain does not start its open segment with a keyframe"));
         }
-        content
+        self.reconstruct_first(&self.versions, self.versions.len())
     }
 
     /// Retrieves a specific version by reconstructing it
-    pub fn reconstruct_version(&self, version_id: &str) -> Option<String> {
-        let mut content = self.original.clone();
-        for version in &self.versions {
-            content = apply_changes(&content, &version.changes);
-            if version.version_id == version_id {
-                return Some(content);
-            }
+    pub fn reconstruct_version(&self, repo: &Repository, version_id: &str) -> Result<Option<Vec<u8>>> {
+        let versions = self.history(repo)?;
+        match versions.iter().position(|version| version.version_id == version_id) {
+            Some(index) => self.reconstruct_first(&versions, index + 1).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Every version, the closed segments read back from the object store
+    pub fn history(&self, repo: &Repository) -> Result<Vec<VersionData>> {
+        let store = repo.objects();
+        let mut versions = Vec::new();
+        for id in &self.segments {
+            versions.extend(read_blob::<Vec<VersionData>>(&store, id, "Version segment")?);
+        }
+        versions.extend(self.versions.iter().cloned());
+        Ok(versions)
+    }
+
+    /// Content once the first `count` of `versions` are applied, starting from the closest
+    /// keyframe before them
+    fn reconstruct_first(&self, versions: &[VersionData], count: usize) -> Result<Vec<u8>> {
+        let versions = &versions[..count];
+        let (mut content, rest) = match versions.iter().rposition(|version| version.keyframe.is_some()) {
+            Some(keyframe) => (Vec::new(), &versions[keyframe..]),
+            None => (decode_content(&self.original, self.binary)?, versions),
+        };
+        for version in rest {
+            content = version.apply(&content)?;
+        }
+        Ok(content)
+    }
+
+    /// Replays every version from the original, failing at the first one that cannot be
+    /// rebuilt. Returns how many versions were checked.
+    pub fn verify(&self, repo: &Repository) -> Result<usize> {
+        let versions = self.history(repo)?;
+        let mut content = decode_content(&self.original, self.binary)?;
+        for version in &versions {
+            content = version.apply(&content).map_err(|e| GritError::corrupt_from(
+                format!("version {} cannot be rebuilt", version.version_id),
+                e,
+            ))?;
+        }
+        Ok(versions.len())
+    }
+
+    /// Size of the last full copy, the keyframe or the original, and the versions after it
+    fn chain_since_keyframe(&self) -> (usize, &[VersionData]) {
+        match self.versions.iter().rposition(|version| version.keyframe.is_some()) {
+            Some(keyframe) => (self.versions[keyframe].stored_size(), &self.versions[keyframe + 1..]),
+            None => (decoded_len(&self.original, self.binary), &self.versions),
         }
-        None
     }
 }
 
-/// Applies a list of changes to a string and returns the modified result
-fn apply_changes(content: &str, changes: &[Edit]) -> String {
+/// Chain file recording the versions of `file_path` on `branch`
+pub fn version_file(repo: &Repository, branch: &str, file_path: &Path) -> PathBuf {
+    let file_stem = file_path
+        .display()
+        .to_string()
+        .replace("/", "_") // Works for Unix but not Windows
+        .replace("\\", "_"); // Ensures Windows compatibility
+
+    repo.grit_path(VERSIONS_DIR).join(branch).join(format!("{}.json", file_stem))
+}
+
+/// Moves the content of every chain file into the object store, leaving only a reference
+/// to its blob behind, so packing bundles the chains with the other objects instead of
+/// keeping a file of changes per branch and path. Returns the blobs written.
+pub fn pack_chains(repo: &Repository) -> Result<Vec<String>> {
+    let store = repo.objects();
+    let mut written = Vec::new();
+    for (_, json_path) in version_files(repo)? {
+        let version_data = Version::load(repo, &json_path)?;
+        if version_data.packed.is_some() {
+            continue;
+        }
+        // The blob is complete before the file refers to it
+        let id = store.write(ObjectKind::Blob, &serde_json::to_vec(&version_data)?)?;
+        write_atomic(&json_path, serde_json::to_string_pretty(&PackedChain { packed: id.clone() })?)?;
+        written.push(id);
+    }
+    Ok(written)
+}
+
+/// A blob of the chain, parsed from JSON
+fn read_blob<T: serde::de::DeserializeOwned>(store: &ObjectStore, id: &str, what: &str) -> Result<T> {
+    let (kind, data) = store.read(id)?;
+    if kind != ObjectKind::Blob {
+        return Err(GritError::corrupt(format!("{} {} is a {}", what, id, kind.as_str())));
+    }
+    serde_json::from_slice(&data).map_err(|e| GritError::corrupt_from(format!("{} {} cannot be parsed", what, id), e))
+}
+
+fn decode_content(content: &str, binary: bool) -> Result<Vec<u8>> {
+    if binary {
+        hex::decode(content).map_err(|e| GritError::corrupt_from("Binary content is not valid hex", e))
+    } else {
+        Ok(content.as_bytes().to_vec())
+    }
+}
+
+/// Number of bytes `content` stands for, hex encoded when `binary`
+fn decoded_len(content: &str, binary: bool) -> usize {
+    if binary { content.len() / 2 } else { content.len() }
+}
+
+/// The content as text if it can be diffed line by line: valid UTF-8, and neither marked
+/// binary by the attributes nor looking binary when they do not say
+fn as_text(content: &[u8], binary_attribute: Option<bool>) -> Option<&str> {
+    if binary_attribute.unwrap_or_else(|| looks_binary(content)) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// Applies a list of changes to a string and returns the modified result.
+/// Lines carry their own terminators, so the content is rebuilt byte for byte.
+/// An edit pointing past the end of the content means the chain is damaged.
+fn apply_changes(content: &str, changes: &[Edit]) -> Result<String> {
     let mut lines: Vec<String> = split_lines(content);
 
     for change in changes {
+        let (index, limit) = match change {
+            // Inserting at the end appends a line
+            Edit::Insert(index, _) => (*index, lines.len() + 1),
+            Edit::Delete(index) | Edit::Replace(index, _) => (*index, lines.len()),
+        };
+        if index >= limit {
+            return Err(GritError::corrupt(format!(
+                "Edit at line {} is out of range for {} lines", index, lines.len()
+            )));
+        }
         match change {
-            Edit::Insert(index, text) => {
-                if *index < lines.len() {
-                    lines.insert(*index, text.clone());
-                }
-            }
-            Edit::Delete(index) => {
-                if *index < lines.len() {
-                    lines.remove(*index);
-                }
-            }
-            Edit::Replace(index, text) => {
-                if *index < lines.len() {
-                    lines[*index] = text.clone();
-                }
-            }
+            Edit::Insert(index, text) => lines.insert(*index, text.clone()),
+            Edit::Delete(index) => { lines.remove(*index); }
+            Edit::Replace(index, text) => lines[*index] = text.clone(),
         }
     }
 
-    lines.join("\n")
+    Ok(join_lines(&lines))
 }
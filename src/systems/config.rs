@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::systems::atomic::write_atomic;
+use crate::systems::gc::parse_grace;
+use crate::systems::init::CONFIG_FILE;
+use crate::systems::refs::migrate_legacy_head;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Settings of this working directory only, inside `.grit`, above the repository's
+pub const WORKTREE_CONFIG_FILE: &str = "config.worktree";
+/// Settings of every user of the machine, unless `GRIT_CONFIG_SYSTEM` names another file
+pub const SYSTEM_CONFIG_FILE: &str = "/etc/grit/config";
+
+/// Looks up an environment variable. Commands read the process environment through
+/// `process_env`, tests pass their own so they do not depend on the machine.
+pub type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// An environment variable of the process, `None` when unset or empty
+pub fn process_env(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The user's grit configuration directory: `$XDG_CONFIG_HOME/grit`, otherwise
+/// `~/.config/grit`
+pub fn user_config_dir(env: Env) -> Option<PathBuf> {
+    env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("grit"))
+}
+
+/// Settings shared by every repository of the user, unless `GRIT_CONFIG_GLOBAL` names
+/// another file
+pub fn global_config_file(env: Env) -> Option<PathBuf> {
+    env("GRIT_CONFIG_GLOBAL")
+        .map(PathBuf::from)
+        .or_else(|| user_config_dir(env).map(|dir| dir.join(CONFIG_FILE)))
+}
+
+/// Where a layer of the configuration comes from, in increasing order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    System,
+    Global,
+    Repository,
+    Worktree,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::System, Scope::Global, Scope::Repository, Scope::Worktree];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::System => "system",
+            Scope::Global => "global",
+            Scope::Repository => "repository",
+            Scope::Worktree => "worktree",
+        }
+    }
+
+    /// The file of this scope, `None` for the repository scopes outside a repository or
+    /// the global one without a home directory
+    pub fn path(self, grit_dir: Option<&Path>, env: Env) -> Option<PathBuf> {
+        match self {
+            Scope::System => Some(env("GRIT_CONFIG_SYSTEM").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(SYSTEM_CONFIG_FILE))),
+            Scope::Global => global_config_file(env),
+            Scope::Repository => grit_dir.map(|dir| dir.join(CONFIG_FILE)),
+            Scope::Worktree => grit_dir.map(|dir| dir.join(WORKTREE_CONFIG_FILE)),
+        }
+    }
+}
+
+/// What a setting holds, checked when a file is read and when a value is set
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    /// A file, `~/` standing for the home directory
+    Path,
+    /// A non-negative number
+    Count,
+    /// A duration such as `12h` or `2w`, see `gc::parse_grace`
+    Duration,
+}
+
+/// Every setting grit reads, by `section.name`. Files holding anything else are rejected,
+/// so a misspelt key is reported rather than silently ignored.
+const SETTINGS: &[(&str, Kind)] = &[
+    ("core.excludesfile", Kind::Path),
+    ("core.path", Kind::Path),
+    ("diff.context", Kind::Count),
+    ("gc.grace", Kind::Duration),
+    ("user.email", Kind::Text),
+    ("user.name", Kind::Text),
+];
+
+/// Keys of the legacy `key=value` format that were renamed
+const LEGACY_KEYS: &[(&str, &str)] = &[("path", "core.path"), ("excludesfile", "core.excludesfile")];
+
+/// The value of a setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Text(String),
+    Count(u64),
+}
+
+impl Value {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            Value::Count(_) => None,
+        }
+    }
+
+    pub fn as_count(&self) -> Option<u64> {
+        match self {
+            Value::Count(count) => Some(*count),
+            Value::Text(_) => None,
+        }
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        match self {
+            Value::Text(text) => toml::Value::String(text.clone()),
+            // Kind::parse rejects larger counts, so this never saturates for values it made
+            Value::Count(count) => toml::Value::Integer(i64::try_from(*count).unwrap_or(i64::MAX)),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Count(count) => write!(f, "{}", count),
+        }
+    }
+}
+
+impl Kind {
+    /// A value given as text, on the command line or in a legacy file
+    fn parse(self, text: &str) -> std::result::Result<Value, String> {
+        let text = text.trim();
+        match self {
+            // Counts are stored as TOML integers, which stop at i64::MAX
+            Kind::Count => text.parse::<u64>().ok()
+                .filter(|count| i64::try_from(*count).is_ok())
+                .map(Value::Count)
+                .ok_or_else(|| format!("'{}' is not a number from 0 to {}", text, i64::MAX)),
+            Kind::Duration => parse_grace(text).map(|_| Value::Text(text.to_string())).map_err(|e| e.to_string()),
+            Kind::Text | Kind::Path if text.is_empty() => Err("the value is empty".to_string()),
+            Kind::Text | Kind::Path if text.contains(['\n', '\r']) => Err("the value spans several lines".to_string()),
+            Kind::Text | Kind::Path => Ok(Value::Text(text.to_string())),
+        }
+    }
+
+    /// A value read from a TOML file
+    fn read_toml(self, value: &toml::Value) -> std::result::Result<Value, String> {
+        match (self, value) {
+            (Kind::Count, toml::Value::Integer(count)) => u64::try_from(*count)
+                .map(Value::Count)
+                .map_err(|_| format!("{} is negative", count)),
+            (Kind::Count, _) => Err("expected a number".to_string()),
+            (_, toml::Value::String(text)) => self.parse(text),
+            _ => Err("expected a string".to_string()),
+        }
+    }
+}
+
+/// How a key given by the user or found in a file is described, listing the known ones
+/// when it is not one of them
+fn kind_of(key: &str) -> std::result::Result<Kind, String> {
+    SETTINGS.iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, kind)| *kind)
+        .ok_or_else(|| {
+            let known: Vec<&str> = SETTINGS.iter().map(|(name, _)| *name).collect();
+            format!("unknown setting '{}', the known settings are {}", key, known.join(", "))
+        })
+}
+
+/// Checks that `key` is a setting grit knows
+pub fn check_key(key: &str) -> Result<()> {
+    kind_of(key).map(|_| ()).map_err(|e| GritError::InvalidArgument(capitalize(&e)))
+}
+
+/// One layer of the configuration, a TOML file with a table per section:
+///
+/// ```toml
+/// [user]
+/// name = "Ada Lovelace"
+/// email = "ada@example.com"
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    pub scope: Scope,
+    pub path: PathBuf,
+    values: BTreeMap<String, Value>,
+    /// Read from the `key=value` lines older versions wrote, rewritten in TOML on save
+    legacy: bool,
+    /// `branch=` of a legacy `.grit/config`, which now belongs in HEAD
+    legacy_branch: Option<String>,
+}
+
+impl ConfigFile {
+    /// Reads the file of a scope, empty when it does not exist
+    pub fn load(scope: Scope, path: impl Into<PathBuf>) -> Result<ConfigFile> {
+        let path = path.into();
+        let mut file = ConfigFile { scope, path, values: BTreeMap::new(), legacy: false, legacy_branch: None };
+        let content = match fs::read_to_string(&file.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(file),
+            Err(e) => return Err(e.into()),
+        };
+
+        match content.parse::<toml::Table>() {
+            Ok(table) => file.values = parse_table(&table).map_err(|e| file.invalid(e))?,
+            Err(e) => match parse_legacy(&content) {
+                Some((values, branch)) => {
+                    file.values = values;
+                    file.legacy = true;
+                    file.legacy_branch = branch;
+                }
+                None => return Err(GritError::config_from(format!("{} is not a valid config file", file.path.display()), e)),
+            },
+        }
+        Ok(file)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    /// Every setting of the file, sorted by key
+    pub fn values(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Whether the file still uses the legacy `key=value` format
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    /// Sets a setting from its text, checking the key and the value
+    pub fn set(&mut self, key: &str, text: &str) -> Result<()> {
+        let kind = kind_of(key).map_err(|e| GritError::InvalidArgument(capitalize(&e)))?;
+        let value = kind.parse(text)
+            .map_err(|e| GritError::InvalidArgument(format!("Invalid value for {}: {}", key, e)))?;
+        self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Removes a setting, returning whether the file had it
+    pub fn unset(&mut self, key: &str) -> Result<bool> {
+        check_key(key)?;
+        Ok(self.values.remove(key).is_some())
+    }
+
+    /// The branch a legacy file kept, which is dropped from the file
+    pub fn take_legacy_branch(&mut self) -> Option<String> {
+        self.legacy_branch.take()
+    }
+
+    /// Writes the file back in TOML, which also migrates a legacy one
+    pub fn save(&self) -> Result<()> {
+        let mut table = toml::Table::new();
+        for (key, value) in &self.values {
+            let (section, name) = key.split_once('.').expect("Known settings have a section");
+            let section = table.entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(section) = section {
+                section.insert(name.to_string(), value.to_toml());
+            }
+        }
+
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(&table)
+            .map_err(|e| GritError::config_from(format!("Cannot write {}", self.path.display()), e))?;
+        write_atomic(&self.path, content)
+    }
+
+    fn invalid(&self, message: String) -> GritError {
+        GritError::config(format!("{}: {}", self.path.display(), message))
+    }
+}
+
+/// The settings of a TOML file, which may only hold known settings in their sections
+fn parse_table(table: &toml::Table) -> std::result::Result<BTreeMap<String, Value>, String> {
+    let mut values = BTreeMap::new();
+    for (section, entries) in table {
+        let toml::Value::Table(entries) = entries else {
+            return Err(format!("'{}' is not in a section, such as [user] or [core]", section));
+        };
+        for (name, value) in entries {
+            let key = format!("{}.{}", section, name);
+            let value = kind_of(&key)?.read_toml(value)
+                .map_err(|e| format!("invalid value for {}: {}", key, e))?;
+            values.insert(key, value);
+        }
+    }
+    Ok(values)
+}
+
+/// The settings and the branch of a file in the legacy `key=value` format, `None` when
+/// the content is not in that format
+fn parse_legacy(content: &str) -> Option<(BTreeMap<String, Value>, Option<String>)> {
+    let mut values = BTreeMap::new();
+    let mut branch = None;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line.split_once('=')?;
+        let key = key.trim();
+        if key == "branch" {
+            branch = Some(value.trim().to_string());
+            continue;
+        }
+        let key = LEGACY_KEYS.iter().find(|(old, _)| *old == key).map_or(key, |(_, new)| *new);
+        values.insert(key.to_string(), kind_of(key).ok()?.parse(value).ok()?);
+    }
+    Some((values, branch))
+}
+
+/// Every layer of the configuration, the later ones taking precedence
+#[derive(Debug, Clone)]
+pub struct Config {
+    files: Vec<ConfigFile>,
+}
+
+impl Config {
+    /// The system, global, repository and worktree files, the last two only inside a
+    /// repository. Missing files are empty.
+    pub fn load(grit_dir: Option<&Path>, env: Env) -> Result<Config> {
+        let files = Scope::ALL.into_iter()
+            .filter_map(|scope| scope.path(grit_dir, env).map(|path| (scope, path)))
+            .map(|(scope, path)| ConfigFile::load(scope, path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Config { files })
+    }
+
+    /// Only the file of `scope`, empty when that scope has no file
+    pub fn load_scope(scope: Scope, grit_dir: Option<&Path>, env: Env) -> Result<Config> {
+        let files = match scope.path(grit_dir, env) {
+            Some(path) => vec![ConfigFile::load(scope, path)?],
+            None => Vec::new(),
+        };
+        Ok(Config { files })
+    }
+
+    /// The configuration of a repository, from the process environment
+    pub fn for_repository(repo: &Repository) -> Result<Config> {
+        Config::load(Some(repo.grit_dir()), &process_env)
+    }
+
+    /// The files, from the lowest precedence to the highest
+    pub fn files(&self) -> &[ConfigFile] {
+        &self.files
+    }
+
+    /// The value of a setting and the file deciding it
+    pub fn get_with_origin(&self, key: &str) -> Option<(&Value, &ConfigFile)> {
+        self.files.iter().rev().find_map(|file| file.get(key).map(|value| (value, file)))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.get_with_origin(key).map(|(value, _)| value)
+    }
+
+    pub fn text(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_text)
+    }
+
+    pub fn count(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(Value::as_count)
+    }
+
+    /// A duration setting, already checked when the file was read
+    pub fn duration(&self, key: &str) -> Option<Duration> {
+        self.text(key).and_then(|text| parse_grace(text).ok())
+    }
+
+    /// A path setting with `~/` expanded to the home directory
+    pub fn path(&self, key: &str, env: Env) -> Option<PathBuf> {
+        let path = self.text(key)?;
+        match (path.strip_prefix("~/"), env("HOME")) {
+            (Some(rest), Some(home)) => Some(PathBuf::from(home).join(rest)),
+            _ => Some(PathBuf::from(path)),
+        }
+    }
+}
+
+/// Changes the file of a scope and writes it back. `repo` is needed for the repository
+/// and worktree scopes.
+pub fn edit_config<T>(repo: Option<&Repository>, scope: Scope, env: Env, change: impl FnOnce(&mut ConfigFile) -> Result<T>) -> Result<T> {
+    let path = scope.path(repo.map(Repository::grit_dir), env).ok_or_else(|| match scope {
+        Scope::Global => GritError::config("No home directory to keep the global configuration in"),
+        _ => GritError::InvalidArgument(format!("The {} configuration only exists inside a repository", scope.name())),
+    })?;
+    if let (Some(repo), Scope::Repository) = (repo, scope) {
+        // Move a legacy `branch=` line to HEAD before the file is rewritten without it
+        migrate_legacy_head(repo)?;
+    }
+
+    let mut file = ConfigFile::load(scope, path)?;
+    let result = change(&mut file)?;
+    file.save()?;
+    Ok(result)
+}
+
+/// The message with its first letter in upper case
+pub fn capitalize(message: &str) -> String {
+    let mut chars = message.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use crate::error::Result;
+use crate::test_support::TempRepo;
+
+/// Helper writing a config file, creating its directory
+fn write(path: &Path, content: &str) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Helper standing in for the process environment, with the system and global files in
+/// a temporary directory
+fn environment(dir: &TempDir) -> HashMap<String, String> {
+    HashMap::from([
+        ("GRIT_CONFIG_SYSTEM".to_string(), dir.path().join("system").display().to_string()),
+        ("GRIT_CONFIG_GLOBAL".to_string(), dir.path().join("global").display().to_string()),
+        ("HOME".to_string(), dir.path().join("home").display().to_string()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::error::GritError;
+    use crate::systems::config::{edit_config, Config, ConfigFile, Scope, Value, WORKTREE_CONFIG_FILE};
+    use crate::systems::init::CONFIG_FILE;
+    use crate::systems::refs::{read_head, read_ref, Head, HEAD_FILE};
+    use crate::systems::repository::Repository;
+
+    #[test]
+    fn test_later_layers_take_precedence() -> Result<()> {
+        let repo = TempRepo::new();
+        let dir = TempDir::new()?;
+        let env = environment(&dir);
+        let env = |name: &str| env.get(name).cloned();
+
+        write(&dir.path().join("system"), "[user]\nname = \"System\"\nemail = \"all@example.com\"\n[diff]\ncontext = 5\n")?;
+        write(&dir.path().join("global"), "[user]\nname = \"Global\"\n[core]\nexcludesfile = \"~/ignore\"\n")?;
+        write(&repo.grit_path(WORKTREE_CONFIG_FILE), "[gc]\ngrace = \"3d\"\n")?;
+        edit_config(Some(&repo), Scope::Repository, &env, |config| config.set("user.name", "Repository"))?;
+
+        let config = Config::load(Some(repo.grit_dir()), &env)?;
+        let scopes: Vec<Scope> = config.files().iter().map(|file| file.scope).collect();
+        assert_eq!(scopes, Scope::ALL);
+        let (name, origin) = config.get_with_origin("user.name").expect("A name is configured");
+        assert_eq!((name, origin.scope), (&Value::Text("Repository".to_string()), Scope::Repository));
+        assert_eq!(config.get_with_origin("user.email").map(|(_, file)| file.path.clone()), Some(dir.path().join("system")));
+        assert_eq!(config.count("diff.context"), Some(5));
+        assert_eq!(config.duration("gc.grace"), Some(Duration::from_secs(3 * 24 * 3600)));
+        assert_eq!(config.path("core.excludesfile", &env), Some(dir.path().join("home/ignore")));
+
+        // Outside a repository only the system and global files are read
+        let config = Config::load(None, &env)?;
+        assert_eq!(config.text("user.name"), Some("Global"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_are_validated() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("config");
+        for (content, expected) in [
+            ("[user]\nnmae = \"Ada\"\n", "unknown setting 'user.nmae'"),
+            ("[diff]\ncontext = \"many\"\n", "invalid value for diff.context"),
+            ("[diff]\ncontext = -1\n", "invalid value for diff.context"),
+            ("[gc]\ngrace = \"soon\"\n", "invalid value for gc.grace"),
+            ("name = \"Ada\"\n", "not in a section"),
+        ] {
+            write(&path, content)?;
+            let error = ConfigFile::load(Scope::Global, &path).unwrap_err();
+            assert!(matches!(error, GritError::InvalidConfig { .. }), "{}: {:?}", content, error);
+            assert!(error.to_string().contains(expected), "{}: {}", content, error);
+            assert!(error.to_string().contains(&path.display().to_string()), "{}", error);
+        }
+
+        write(&path, "[user\nname = \n")?;
+        assert!(matches!(ConfigFile::load(Scope::Global, &path), Err(GritError::InvalidConfig { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_checks_keys_and_values() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut config = ConfigFile::load(Scope::Global, dir.path().join("config"))?;
+
+        let error = config.set("user.nmae", "Ada").unwrap_err();
+        assert!(matches!(&error, GritError::InvalidArgument(message) if message.contains("user.name")), "{:?}", error);
+        assert!(matches!(config.set("diff.context", "many"), Err(GritError::InvalidArgument(_))));
+        assert!(matches!(config.set("gc.grace", "soon"), Err(GritError::InvalidArgument(_))));
+        // TOML integers cannot hold more, so the file would not round-trip
+        assert!(matches!(config.set("diff.context", &u64::MAX.to_string()), Err(GritError::InvalidArgument(_))));
+        config.set("diff.context", &i64::MAX.to_string())?;
+        assert!(matches!(config.set("user.name", " "), Err(GritError::InvalidArgument(_))));
+
+        config.set("diff.context", "7")?;
+        config.set("user.name", "Ada Lovelace")?;
+        config.save()?;
+        assert_eq!(fs::read_to_string(&config.path)?, "[diff]\ncontext = 7\n\n[user]\nname = \"Ada Lovelace\"\n");
+
+        let mut config = ConfigFile::load(Scope::Global, &config.path)?;
+        assert_eq!(config.get("diff.context"), Some(&Value::Count(7)));
+        assert!(config.unset("diff.context")?);
+        assert!(!config.unset("diff.context")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_format_is_read_and_migrated() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("config");
+        write(&path, "path=/somewhere\nexcludesfile=~/ignore\nuser.name=Ada Lovelace\n")?;
+
+        let config = ConfigFile::load(Scope::Repository, &path)?;
+        assert!(config.is_legacy());
+        let values: Vec<(&str, String)> = config.values().map(|(key, value)| (key, value.to_string())).collect();
+        assert_eq!(values, [
+            ("core.excludesfile", "~/ignore".to_string()),
+            ("core.path", "/somewhere".to_string()),
+            ("user.name", "Ada Lovelace".to_string()),
+        ]);
+
+        config.save()?;
+        let migrated = ConfigFile::load(Scope::Repository, &path)?;
+        assert!(!migrated.is_legacy());
+        assert!(migrated.values().eq(config.values()));
+
+        // Lines that are neither TOML nor known legacy settings are not guessed at
+        write(&path, "colour=always\n")?;
+        assert!(matches!(ConfigFile::load(Scope::Repository, &path), Err(GritError::InvalidConfig { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_editing_a_legacy_repository_keeps_its_branch() -> Result<()> {
+        let dir = TempDir::new()?;
+        fs::create_dir(dir.path().join(".grit"))?;
+        write(&dir.path().join(".grit").join(CONFIG_FILE), "path=/somewhere\nbranch=dev\n")?;
+        write(&dir.path().join(".grit").join(HEAD_FILE), "0123456789abcdef0123456789abcdef01234567")?;
+        let repo = Repository::open(dir.path())?;
+        let nothing = |_: &str| None;
+
+        edit_config(Some(&repo), Scope::Repository, &nothing, |config| config.set("user.name", "Ada"))?;
+        assert_eq!(read_head(&repo)?, Head::Branch("dev".to_string()));
+        assert_eq!(read_ref(&repo, "dev")?, Some("0123456789abcdef0123456789abcdef01234567".to_string()));
+        assert_eq!(fs::read_to_string(repo.grit_path(CONFIG_FILE))?, "[core]\npath = \"/somewhere\"\n\n[user]\nname = \"Ada\"\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_migrates_a_legacy_config() -> Result<()> {
+        let repo = TempRepo::new();
+        fs::write(repo.grit_path(CONFIG_FILE), "path=/elsewhere\nuser.email=ada@example.com\n")?;
+
+        let repo = Repository::init(repo.root())?;
+        let config = ConfigFile::load(Scope::Repository, repo.grit_path(CONFIG_FILE))?;
+        assert!(!config.is_legacy());
+        assert_eq!(config.get("core.path").and_then(Value::as_text).map(PathBuf::from).as_deref(), Some(repo.root()));
+        assert_eq!(config.get("user.email"), Some(&Value::Text("ada@example.com".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repository_scopes_need_a_repository() {
+        let nothing = |_: &str| None;
+        for scope in [Scope::Repository, Scope::Worktree] {
+            assert!(matches!(edit_config(None, scope, &nothing, |config| config.set("user.name", "Ada")), Err(GritError::InvalidArgument(_))));
+        }
+        assert!(matches!(edit_config(None, Scope::Global, &nothing, |config| config.set("user.name", "Ada")), Err(GritError::InvalidConfig { .. })));
+    }
+}
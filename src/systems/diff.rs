@@ -0,0 +1,337 @@
+use std::fs;
+use std::ops::Range;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::algorithms::vcompare::compv::count_changes;
+use crate::algorithms::vcompare::difference::{diff_ops, DiffOp};
+use crate::algorithms::vcompare::utils::split_lines;
+use crate::structure::serialization::load;
+use crate::systems::checkout::working_hash;
+use crate::systems::commits::functions::{head_snapshot, resolve_commit};
+use crate::systems::filters::attributes::Attributes;
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::objects::tree::{compare_snapshots, Change};
+use crate::systems::repository::Repository;
+use crate::error::Result;
+
+/// Lines of context around each change, unless asked otherwise
+pub const DEFAULT_CONTEXT: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The two states `grit diff` compares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffTarget {
+    /// Staging tree against the working directory
+    WorkingTree,
+    /// HEAD against the staging tree
+    Staged,
+    /// One commit against another
+    Commits(String, String),
+}
+
+/// How a patch is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    pub context: usize,
+    pub color: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions { context: DEFAULT_CONTEXT, color: false }
+    }
+}
+
+/// A changed file with both versions of its content, empty on the side where it does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    pub change: Change,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    /// Either version is binary, so the change is not shown line by line
+    pub binary: bool,
+}
+
+impl FileDiff {
+    /// Path of the file before the change
+    pub fn old_path(&self) -> &Path {
+        match &self.change {
+            Change::Renamed { from, .. } => from,
+            _ => &self.path,
+        }
+    }
+
+    /// Lines added and removed, none for binary files
+    pub fn line_counts(&self) -> (usize, usize) {
+        if self.binary {
+            return (0, 0);
+        }
+        count_changes(&String::from_utf8_lossy(&self.old), &String::from_utf8_lossy(&self.new))
+    }
+}
+
+/// Collect the files that differ between the two sides of `target`
+pub fn diff(repo: &Repository, target: &DiffTarget) -> Result<Vec<FileDiff>> {
+    let root = repo.root();
+    let store = repo.objects();
+
+    match target {
+        DiffTarget::WorkingTree => {
+            // Only tracked files are compared, untracked ones have nothing to diff against
+            let index = load(repo)?.snapshot();
+            let working: BTreeMap<PathBuf, String> = index.keys()
+                .filter_map(|path| working_hash(root, path).map(|hash| (path.clone(), hash)))
+                .collect();
+            file_diffs(repo, &store, &index, &working, |path, _| Ok(fs::read(root.join(path))?))
+        }
+        DiffTarget::Staged => {
+            let index = load(repo)?.snapshot();
+            file_diffs(repo, &store, &head_snapshot(repo, &store)?, &index, |_, hash| read_blob(&store, hash))
+        }
+        DiffTarget::Commits(old, new) => {
+            let old = resolve_commit(repo, old)?.snapshot(&store)?;
+            let new = resolve_commit(repo, new)?.snapshot(&store)?;
+            file_diffs(repo, &store, &old, &new, |_, hash| read_blob(&store, hash))
+        }
+    }
+}
+
+/// Changes between two snapshots along with their content. The old side is read from the
+/// store, the new one through `read_new`, given the path and blob id of each file.
+pub fn file_diffs(
+    repo: &Repository,
+    store: &ObjectStore,
+    old: &BTreeMap<PathBuf, String>,
+    new: &BTreeMap<PathBuf, String>,
+    read_new: impl Fn(&Path, &str) -> Result<Vec<u8>>,
+) -> Result<Vec<FileDiff>> {
+    let attributes = Attributes::load(repo.root())?;
+    let mut diffs = Vec::new();
+    for (path, change) in compare_snapshots(store, old, new)? {
+        let old_path = match &change {
+            Change::Renamed { from, .. } => from.clone(),
+            _ => path.clone(),
+        };
+        let old_content = match old.get(&old_path) {
+            Some(hash) if change != Change::Added => read_blob(store, hash)?,
+            _ => Vec::new(),
+        };
+        let new_content = match new.get(&path) {
+            Some(hash) if change != Change::Deleted => read_new(&path, hash)?,
+            _ => Vec::new(),
+        };
+        let binary = attributes.is_binary(&path, &old_content) || attributes.is_binary(&path, &new_content);
+        diffs.push(FileDiff { path, change, old: old_content, new: new_content, binary });
+    }
+    Ok(diffs)
+}
+
+fn read_blob(store: &ObjectStore, hash: &str) -> Result<Vec<u8>> {
+    Ok(store.read(hash)?.1)
+}
+
+/// Render the changes as a unified diff that `patch -p1` can apply
+pub fn format_patch(diffs: &[FileDiff], options: &DiffOptions) -> String {
+    let paint = |color: &str, text: &str| -> String {
+        if options.color { format!("{}{}{}", color, text, RESET) } else { text.to_string() }
+    };
+
+    let mut out = String::new();
+    for diff in diffs {
+        let old_name = format!("a/{}", to_slash(diff.old_path()));
+        let new_name = format!("b/{}", to_slash(&diff.path));
+
+        out.push_str(&paint(BOLD, &format!("diff --grit {} {}", old_name, new_name)));
+        out.push('\n');
+        match &diff.change {
+            Change::Added => out.push_str(&paint(BOLD, "new file")),
+            Change::Deleted => out.push_str(&paint(BOLD, "deleted file")),
+            Change::Renamed { from, similarity } => out.push_str(&paint(BOLD, &format!(
+                "similarity index {}%\nrename from {}\nrename to {}",
+                similarity, to_slash(from), to_slash(&diff.path),
+            ))),
+            Change::Modified => {}
+        }
+        if diff.change != Change::Modified {
+            out.push('\n');
+        }
+        if diff.old == diff.new {
+            continue;
+        }
+
+        let old_label = if diff.change == Change::Added { "/dev/null".to_string() } else { old_name };
+        let new_label = if diff.change == Change::Deleted { "/dev/null".to_string() } else { new_name };
+        if diff.binary {
+            out.push_str(&format!("Binary files {} and {} differ\n", old_label, new_label));
+            continue;
+        }
+        out.push_str(&paint(BOLD, &format!("--- {}\n+++ {}", old_label, new_label)));
+        out.push('\n');
+
+        let (old_lines, new_lines, ops) = line_ops(&diff.old, &diff.new);
+        for hunk in hunks(&ops, options.context) {
+            out.push_str(&paint(CYAN, &hunk_header(&ops, hunk.clone())));
+            out.push('\n');
+            for op in &ops[hunk] {
+                let (prefix, line, color) = match *op {
+                    DiffOp::Equal(o, _) => (' ', &old_lines[o], None),
+                    DiffOp::Delete(o) => ('-', &old_lines[o], Some(RED)),
+                    DiffOp::Insert(n) => ('+', &new_lines[n], Some(GREEN)),
+                };
+                let text = format!("{}{}", prefix, line.strip_suffix('\n').unwrap_or(line));
+                match color {
+                    Some(color) => out.push_str(&paint(color, &text)),
+                    None => out.push_str(&text),
+                }
+                out.push('\n');
+                if !line.ends_with('\n') {
+                    out.push_str("\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// One line per changed file with the number of lines added and removed, then a total
+pub fn format_stat(diffs: &[FileDiff]) -> String {
+    /// Widest `+`/`-` bar
+    const BAR_WIDTH: usize = 50;
+
+    let rows: Vec<(String, usize, usize)> = diffs.iter()
+        .map(|diff| {
+            let (added, removed) = diff.line_counts();
+            (display_name(diff), added, removed)
+        })
+        .collect();
+
+    let name_width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    let largest = rows.iter().map(|(_, added, removed)| added + removed).max().unwrap_or(0);
+    let count_width = largest.to_string().len();
+
+    let mut out = String::new();
+    for ((name, added, removed), diff) in rows.iter().zip(diffs) {
+        // Binary files have no lines to count, only their sizes
+        if diff.binary {
+            out.push_str(&format!(" {:<name_width$} | Bin {} -> {} bytes\n", name, diff.old.len(), diff.new.len()));
+            continue;
+        }
+
+        // Scale the bar down for large changes
+        let (mut plus, mut minus) = (*added, *removed);
+        if largest > BAR_WIDTH {
+            plus = (added * BAR_WIDTH).div_ceil(largest);
+            minus = (removed * BAR_WIDTH).div_ceil(largest);
+        }
+        let line = format!(" {:<name_width$} | {:>count_width$} {}{}", name, added + removed, "+".repeat(plus), "-".repeat(minus));
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    let insertions: usize = rows.iter().map(|(_, added, _)| added).sum();
+    let deletions: usize = rows.iter().map(|(_, _, removed)| removed).sum();
+    out.push_str(&format!(
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)\n",
+        rows.len(), plural(rows.len()), insertions, plural(insertions), deletions, plural(deletions),
+    ));
+    out
+}
+
+/// Tab separated `added removed path` lines for scripts, `-` counts for binary files
+pub fn format_numstat(diffs: &[FileDiff]) -> String {
+    diffs.iter()
+        .map(|diff| {
+            let (added, removed) = diff.line_counts();
+            if diff.binary {
+                format!("-\t-\t{}\n", display_name(diff))
+            } else {
+                format!("{}\t{}\t{}\n", added, removed, display_name(diff))
+            }
+        })
+        .collect()
+}
+
+fn display_name(diff: &FileDiff) -> String {
+    match &diff.change {
+        Change::Renamed { from, .. } => format!("{} => {}", to_slash(from), to_slash(&diff.path)),
+        _ => to_slash(&diff.path),
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+fn to_slash(path: &Path) -> String {
+    path.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn line_ops(old: &[u8], new: &[u8]) -> (Vec<String>, Vec<String>, Vec<DiffOp>) {
+    let old_lines = split_lines(&String::from_utf8_lossy(old));
+    let new_lines = split_lines(&String::from_utf8_lossy(new));
+    let mut ops = diff_ops(&old_lines, &new_lines);
+
+    // Within a block of changes, removed lines are listed before added ones
+    for run in ops.split_mut(|op| matches!(op, DiffOp::Equal(..))) {
+        run.sort_by_key(|op| matches!(op, DiffOp::Insert(_)));
+    }
+    (old_lines, new_lines, ops)
+}
+
+/// Ranges of `ops` to print: every change with up to `context` unchanged lines on each
+/// side, changes closer than twice the context sharing one hunk
+fn hunks(ops: &[DiffOp], context: usize) -> Vec<Range<usize>> {
+    let is_change = |op: &DiffOp| !matches!(op, DiffOp::Equal(..));
+    let mut hunks = Vec::new();
+    let mut i = 0;
+
+    while i < ops.len() {
+        if !is_change(&ops[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i.saturating_sub(context);
+        let mut end = i;
+        loop {
+            while end < ops.len() && is_change(&ops[end]) {
+                end += 1;
+            }
+            let next_change = ops[end..].iter().position(is_change).map(|gap| end + gap);
+            match next_change {
+                Some(next) if next - end <= 2 * context => end = next,
+                _ => break,
+            }
+        }
+
+        let stop = (end + context).min(ops.len());
+        hunks.push(start..stop);
+        i = stop;
+    }
+    hunks
+}
+
+/// `@@ -start,count +start,count @@`, lines numbered from 1 and a count of 1 left out
+fn hunk_header(ops: &[DiffOp], hunk: Range<usize>) -> String {
+    let old_before = ops[..hunk.start].iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+    let new_before = ops[..hunk.start].iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+    let old_count = ops[hunk.clone()].iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+    let new_count = ops[hunk].iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+
+    format!("@@ -{} +{} @@", range_label(old_before, old_count), range_label(new_before, new_count))
+}
+
+fn range_label(before: usize, count: usize) -> String {
+    match count {
+        // An empty range names the line after which the change happens
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
@@ -0,0 +1,148 @@
+use std::io;
+use std::path::PathBuf;
+use crate::systems::add::add;
+use crate::systems::diff::{format_patch, DiffOptions, FileDiff};
+use crate::systems::objects::tree::Change;
+
+/// Helper building a modified file from two texts
+fn modified(path: &str, old: &str, new: &str) -> FileDiff {
+    FileDiff { path: PathBuf::from(path), change: Change::Modified, old: old.into(), new: new.into(), binary: false }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+    use crate::systems::diff::{diff, format_numstat, format_stat, DiffTarget};
+
+    fn numbered(lines: std::ops::RangeInclusive<usize>) -> String {
+        lines.map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_patch_has_context_and_headers() {
+        let old = numbered(1..=10);
+        let new = old.replace("line 5\n", "line five\n");
+        let patch = format_patch(&[modified("a.txt", &old, &new)], &DiffOptions::default());
+
+        assert_eq!(patch, concat!(
+            "diff --grit a/a.txt b/a.txt\n",
+            "--- a/a.txt\n",
+            "+++ b/a.txt\n",
+            "@@ -2,7 +2,7 @@\n",
+            " line 2\n",
+            " line 3\n",
+            " line 4\n",
+            "-line 5\n",
+            "+line five\n",
+            " line 6\n",
+            " line 7\n",
+            " line 8\n",
+        ));
+    }
+
+    #[test]
+    fn test_close_changes_share_a_hunk() {
+        let old = numbered(1..=30);
+        let new = old.replace("line 3\n", "").replace("line 8\n", "line eight\n").replace("line 25\n", "line 25\nextra\n");
+
+        let options = DiffOptions { context: 2, ..DiffOptions::default() };
+        let patch = format_patch(&[modified("a.txt", &old, &new)], &options);
+        let headers: Vec<&str> = patch.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, vec!["@@ -1,10 +1,9 @@", "@@ -24,4 +23,5 @@"]);
+
+        let none = format_patch(&[modified("a.txt", &old, &new)], &DiffOptions { context: 0, ..options });
+        let headers: Vec<&str> = none.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, vec!["@@ -3 +2,0 @@", "@@ -8 +7 @@", "@@ -25,0 +25 @@"]);
+    }
+
+    #[test]
+    fn test_added_deleted_and_missing_newline() {
+        let added = FileDiff { path: PathBuf::from("new.txt"), change: Change::Added, old: Vec::new(), new: b"one\ntwo".to_vec(), binary: false };
+        let deleted = FileDiff { path: PathBuf::from("old.txt"), change: Change::Deleted, old: b"gone\n".to_vec(), new: Vec::new(), binary: false };
+
+        let patch = format_patch(&[added, deleted], &DiffOptions::default());
+        assert_eq!(patch, concat!(
+            "diff --grit a/new.txt b/new.txt\n",
+            "new file\n",
+            "--- /dev/null\n",
+            "+++ b/new.txt\n",
+            "@@ -0,0 +1,2 @@\n",
+            "+one\n",
+            "+two\n",
+            "\\ No newline at end of file\n",
+            "diff --grit a/old.txt b/old.txt\n",
+            "deleted file\n",
+            "--- a/old.txt\n",
+            "+++ /dev/null\n",
+            "@@ -1 +0,0 @@\n",
+            "-gone\n",
+        ));
+    }
+
+    #[test]
+    fn test_color_wraps_changed_lines() {
+        let options = DiffOptions { color: true, ..DiffOptions::default() };
+        let patch = format_patch(&[modified("a.txt", "a\n", "b\n")], &options);
+        assert!(patch.contains("\x1b[31m-a\x1b[0m\n"));
+        assert!(patch.contains("\x1b[32m+b\x1b[0m\n"));
+        assert!(!format_patch(&[modified("a.txt", "a\n", "b\n")], &DiffOptions::default()).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_diff_targets() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "one\n")?;
+        repo.write("b.txt", "bee\n")?;
+        let first = repo.commit_all("First")?.id;
+
+        repo.write("a.txt", "one\ntwo\n")?;
+        add(&repo, None)?;
+        repo.write("b.txt", "BEE\n")?;
+
+        let working = diff(&repo, &DiffTarget::WorkingTree)?;
+        assert_eq!(working, vec![modified("b.txt", "bee\n", "BEE\n")]);
+
+        let staged = diff(&repo, &DiffTarget::Staged)?;
+        assert_eq!(staged, vec![modified("a.txt", "one\n", "one\ntwo\n")]);
+
+        let second = repo.commit_all("Second")?.id;
+        let between = diff(&repo, &DiffTarget::Commits(first, second))?;
+        assert_eq!(format_numstat(&between), "1\t0\ta.txt\n1\t1\tb.txt\n");
+        assert_eq!(format_stat(&between), concat!(
+            " a.txt | 1 +\n",
+            " b.txt | 2 +-\n",
+            " 2 files changed, 2 insertions(+), 1 deletion(-)\n",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_files_differ() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("image.bin", b"\x89PNG\0\x01\x02")?;
+        repo.write("notes.txt", "text\n")?;
+        repo.commit_all("First")?;
+        repo.write("image.bin", b"\x89PNG\0\x03\x04\x05")?;
+
+        let diffs = diff(&repo, &DiffTarget::WorkingTree)?;
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].binary);
+        assert_eq!(format_patch(&diffs, &DiffOptions::default()), concat!(
+            "diff --grit a/image.bin b/image.bin\n",
+            "Binary files a/image.bin and b/image.bin differ\n",
+        ));
+        assert_eq!(format_numstat(&diffs), "-\t-\timage.bin\n");
+        assert_eq!(format_stat(&diffs), concat!(
+            " image.bin | Bin 7 -> 8 bytes\n",
+            " 1 file changed, 0 insertions(+), 0 deletions(-)\n",
+        ));
+
+        // Attributes can turn a text file into a binary one
+        repo.write(".gritattributes", "*.txt binary\n")?;
+        repo.write("notes.txt", "more text\n")?;
+        let diffs = diff(&repo, &DiffTarget::WorkingTree)?;
+        assert!(diffs.iter().all(|diff| diff.binary));
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::io;
+use crate::test_support::{tester, TempRepo};
+use crate::error::{GritError, Result};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use tempfile::TempDir;
+    use crate::systems::atomic::RepositoryLock;
+    use crate::systems::branch::create_branch;
+    use crate::systems::checkout::switch;
+    use crate::systems::commits::functions::{load_all_commits, resolve_commit, COMMITS_FILE};
+    use crate::systems::config::{ConfigFile, Scope};
+    use crate::systems::gc::parse_grace;
+    use crate::systems::index::remove;
+    use crate::systems::init::{CONFIG_FILE, DEFAULT_BRANCH};
+    use crate::systems::merge::merge;
+    use crate::systems::repository::Repository;
+
+    #[test]
+    fn test_outside_a_repository() {
+        let tmp_dir = TempDir::new().unwrap();
+
+        let error = Repository::discover(tmp_dir.path()).unwrap_err();
+        assert!(matches!(&error, GritError::NotARepository(path) if path == tmp_dir.path()), "{:?}", error);
+        assert!(error.to_string().starts_with("Not a grit repository"));
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_unknown_revisions_and_branches() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "a\n", "Write a.txt")?;
+
+        assert!(matches!(resolve_commit(&repo, "nowhere"), Err(GritError::MissingRef(_))));
+        let error = switch(&repo, "nowhere", false).unwrap_err();
+        assert!(matches!(error, GritError::MissingRef(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_commit_database_keeps_its_cause() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "a\n", "Write a.txt")?;
+        fs::write(repo.grit_path(COMMITS_FILE), "{\"truncated\": ")?;
+
+        let error = load_all_commits(&repo).unwrap_err();
+        assert!(matches!(error, GritError::CorruptObject { .. }), "{:?}", error);
+        assert_eq!(error.to_string(), format!("{} is corrupt", COMMITS_FILE));
+        assert!(error.source().is_some_and(|source| source.is::<serde_json::Error>()));
+        assert!(error.with_causes().starts_with(&format!("{} is corrupt: ", COMMITS_FILE)));
+        assert_eq!(error.exit_code(), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_in_progress_is_a_conflict() -> Result<()> {
+        let repo = TempRepo::new();
+        let id = repo.commit_file("a.txt", "a\n", "Write a.txt")?.id;
+        repo.write(".grit/MERGE_HEAD", format!("{}\n", id))?;
+
+        let error = merge(&repo, DEFAULT_BRANCH, || Ok(tester())).unwrap_err();
+        assert!(matches!(error, GritError::Conflict(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_modifications_make_a_dirty_working_tree() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "a\n", "Write a.txt")?;
+        repo.write("a.txt", "changed\n")?;
+
+        let error = remove(&repo, "a.txt", false).unwrap_err();
+        assert!(matches!(error, GritError::DirtyWorkingTree(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_config_is_invalid_config() -> Result<()> {
+        let repo = TempRepo::new();
+        let path = repo.grit_path(CONFIG_FILE);
+        fs::write(&path, "[user\nname = ")?;
+
+        let error = ConfigFile::load(Scope::Repository, &path).unwrap_err();
+        assert!(matches!(error, GritError::InvalidConfig { .. }), "{:?}", error);
+        assert!(error.source().is_some());
+        assert_eq!(error.exit_code(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_names_and_options() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "a\n", "Write a.txt")?;
+
+        let error = create_branch(&repo, "bad..name", None).unwrap_err();
+        assert!(matches!(error, GritError::InvalidArgument(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 2);
+        assert!(matches!(parse_grace("soon"), Err(GritError::InvalidArgument(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_held_lock() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let _lock = RepositoryLock::acquire(tmp_dir.path())?;
+
+        let error = RepositoryLock::acquire(tmp_dir.path()).unwrap_err();
+        assert!(matches!(error, GritError::Locked(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_io_errors_and_round_trips() {
+        let error = GritError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert!(matches!(&error, GritError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied));
+        assert_eq!(error.to_string(), "denied");
+        assert_eq!(error.exit_code(), 10);
+
+        // Code working with io::Result, such as a write callback, hands grit errors back intact
+        let through_io = io::Error::from(GritError::Conflict("unmerged".to_string()));
+        assert!(matches!(GritError::from(through_io), GritError::Conflict(message) if message == "unmerged"));
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            GritError::NotARepository(".".into()),
+            GritError::corrupt("bad"),
+            GritError::MissingRef("missing".to_string()),
+            GritError::Conflict("conflict".to_string()),
+            GritError::DirtyWorkingTree("dirty".to_string()),
+            GritError::config("config"),
+            GritError::InvalidArgument("argument".to_string()),
+            GritError::Locked("locked".to_string()),
+            GritError::Io(io::Error::other("io")),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(GritError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        // 0 is success and 1 a negative answer, neither is ever an error
+        assert!(codes.iter().all(|&code| code > 1));
+    }
+}
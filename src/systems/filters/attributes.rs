@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::Path;
+use super::ignore::IgnorePattern;
+use crate::error::Result;
+
+/// Name of the attributes file at the root of the repository
+pub const ATTRIBUTES_FILE: &str = ".gritattributes";
+/// Bytes looked at when guessing whether content is binary
+const SNIFF_LENGTH: usize = 8000;
+
+/// Patterns of `.gritattributes` that decide whether files are binary.
+///
+/// Each line is a pattern, matched like those of `.gritignore`, followed by attributes:
+/// `binary` or `-text` mark matching files as binary, `text` or `-binary` as text. The last
+/// matching line wins, and files no line decides about are looked at.
+pub struct Attributes {
+    rules: Vec<(IgnorePattern, bool)>,
+}
+
+impl Attributes {
+    pub fn load(root: &Path) -> Result<Attributes> {
+        let path = root.join(ATTRIBUTES_FILE);
+        if !path.is_file() {
+            return Ok(Attributes { rules: Vec::new() });
+        }
+
+        let mut rules = Vec::new();
+        for (index, line) in fs::read_to_string(&path)?.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next().and_then(|pattern| IgnorePattern::parse(pattern, Path::new(""), &path, index + 1)) else {
+                continue;
+            };
+            // Negated patterns are not allowed in attribute files
+            if pattern.negated {
+                continue;
+            }
+            let binary = fields.rev().find_map(|attribute| match attribute {
+                "binary" | "-text" => Some(true),
+                "text" | "-binary" => Some(false),
+                _ => None,
+            });
+            if let Some(binary) = binary {
+                rules.push((pattern, binary));
+            }
+        }
+        Ok(Attributes { rules })
+    }
+
+    /// What the attributes say about a file, given from the repository root: `Some(true)`
+    /// for binary, `Some(false)` for text, `None` if no line matches it
+    pub fn binary(&self, rel_path: &Path) -> Option<bool> {
+        self.rules.iter().rev()
+            .find(|(pattern, _)| pattern.matches(rel_path, false))
+            .map(|(_, binary)| *binary)
+    }
+
+    /// Whether a file must be handled as bytes rather than lines of text
+    pub fn is_binary(&self, rel_path: &Path, content: &[u8]) -> bool {
+        self.binary(rel_path).unwrap_or_else(|| looks_binary(content))
+    }
+}
+
+/// Content with a NUL byte near its start is taken to be binary, as text never has one
+pub fn looks_binary(content: &[u8]) -> bool {
+    content.iter().take(SNIFF_LENGTH).any(|byte| *byte == 0)
+}
+
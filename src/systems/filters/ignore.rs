@@ -0,0 +1,299 @@
+use std::fs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::systems::repository::Repository;
+use crate::systems::init::GRIT_DIR;
+use crate::systems::config::{process_env, user_config_dir, Config};
+use crate::error::Result;
+
+/// Name of the per-directory ignore files
+pub const IGNORE_FILE: &str = ".gritignore";
+
+/// One line of an ignore file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnorePattern {
+    /// File the pattern was read from, and its line number starting at 1
+    pub source: PathBuf,
+    pub line: usize,
+    /// The pattern as written
+    pub text: String,
+    /// A `!` pattern re-includes what an earlier pattern excluded
+    pub negated: bool,
+    /// Directory the pattern is relative to, from the repository root
+    base: PathBuf,
+    glob: Vec<char>,
+    /// A trailing `/` restricts the pattern to directories
+    dir_only: bool,
+    /// A pattern with a `/` other than at its end matches from `base` only, otherwise
+    /// it matches a file or directory name at any depth
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    /// Parse one line, `None` for blank lines and comments
+    pub fn parse(line: &str, base: &Path, source: &Path, line_number: usize) -> Option<IgnorePattern> {
+        let text = trim_trailing_spaces(line);
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+
+        let (negated, mut pattern) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(text)),
+        };
+
+        let dir_only = pattern.ends_with('/');
+        pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(IgnorePattern {
+            source: source.to_path_buf(),
+            line: line_number,
+            text: text.to_string(),
+            negated,
+            base: base.to_path_buf(),
+            glob: pattern.chars().collect(),
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Whether the pattern applies to `rel_path`, given from the repository root
+    pub fn matches(&self, rel_path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = rel_path.strip_prefix(&self.base) else {
+            return false;
+        };
+
+        let subject = if self.anchored {
+            to_slash_path(relative)
+        } else {
+            relative.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+        };
+        !subject.is_empty() && glob_match(&self.glob, &subject.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Ignore rules of a repository: the global excludes file, then the `.gritignore` of every
+/// directory from the root down, later patterns taking precedence over earlier ones.
+pub struct IgnoreRules {
+    root: PathBuf,
+    global: Vec<IgnorePattern>,
+    /// `.gritignore` patterns of each directory seen so far, keyed by its path from the root
+    per_directory: HashMap<PathBuf, Vec<IgnorePattern>>,
+}
+
+impl IgnoreRules {
+    pub fn load(root: &Path) -> Result<IgnoreRules> {
+        let global = match global_excludes_file(root)? {
+            Some(path) if path.is_file() => read_patterns(&path, Path::new(""))?,
+            _ => Vec::new(),
+        };
+        Ok(IgnoreRules { root: root.to_path_buf(), global, per_directory: HashMap::new() })
+    }
+
+    /// Whether a path, given from the repository root, is excluded. `.grit` always is.
+    pub fn is_ignored(&mut self, rel_path: &Path, is_dir: bool) -> Result<bool> {
+        if rel_path.starts_with(GRIT_DIR) {
+            return Ok(true);
+        }
+        Ok(self.matching(rel_path, is_dir)?.is_some_and(|pattern| !pattern.negated))
+    }
+
+    /// The pattern deciding whether a path is ignored, which may be a negated one, or the
+    /// one excluding a parent directory since nothing inside an excluded directory can be
+    /// included again
+    pub fn matching(&mut self, rel_path: &Path, is_dir: bool) -> Result<Option<IgnorePattern>> {
+        let ancestors: Vec<&Path> = rel_path.ancestors()
+            .skip(1)
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .collect();
+
+        for ancestor in ancestors.into_iter().rev() {
+            if let Some(pattern) = self.last_match(ancestor, true)? {
+                if !pattern.negated {
+                    return Ok(Some(pattern));
+                }
+            }
+        }
+        self.last_match(rel_path, is_dir)
+    }
+
+    fn last_match(&mut self, rel_path: &Path, is_dir: bool) -> Result<Option<IgnorePattern>> {
+        let mut directories = vec![PathBuf::new()];
+        if let Some(parent) = rel_path.parent() {
+            let mut current = PathBuf::new();
+            for component in parent.iter() {
+                current.push(component);
+                directories.push(current.clone());
+            }
+        }
+
+        let mut found = self.global.iter().rev().find(|pattern| pattern.matches(rel_path, is_dir)).cloned();
+        for directory in directories {
+            if let Some(pattern) = self.directory_patterns(&directory)?.iter().rev().find(|pattern| pattern.matches(rel_path, is_dir)) {
+                found = Some(pattern.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    fn directory_patterns(&mut self, directory: &Path) -> Result<&[IgnorePattern]> {
+        if !self.per_directory.contains_key(directory) {
+            let file = self.root.join(directory).join(IGNORE_FILE);
+            let patterns = if file.is_file() {
+                read_patterns(&file, directory)?
+                    .into_iter()
+                    .map(|pattern| IgnorePattern { source: directory.join(IGNORE_FILE), ..pattern })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            self.per_directory.insert(directory.to_path_buf(), patterns);
+        }
+        Ok(&self.per_directory[directory])
+    }
+
+    /// Every file below `dir` that is not ignored, sorted, without descending into
+    /// ignored directories
+    pub fn walk_files(&mut self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        self.collect_files(dir, &mut files)?;
+        Ok(files)
+    }
+
+    fn collect_files(&mut self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(self.root.join(dir))?
+            .map(|entry| entry.map(|entry| dir.join(entry.file_name())))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+
+        for rel_path in entries {
+            // Parent directories were already found not to be ignored
+            let is_dir = self.root.join(&rel_path).is_dir();
+            let excluded = rel_path == Path::new(GRIT_DIR)
+                || self.last_match(&rel_path, is_dir)?.is_some_and(|pattern| !pattern.negated);
+            if excluded {
+                continue;
+            }
+            if is_dir {
+                self.collect_files(&rel_path, files)?;
+            } else {
+                files.push(rel_path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The pattern deciding whether `path`, given from the current directory, is ignored.
+/// Backs `grit check-ignore`.
+pub fn check_ignore(repo: &Repository, path: &str) -> Result<Option<IgnorePattern>> {
+    let rel_path = repo.relative_path(path)?;
+    let is_dir = repo.working_path(&rel_path).is_dir();
+    IgnoreRules::load(repo.root())?.matching(&rel_path, is_dir)
+}
+
+/// The global excludes file: `core.excludesfile` in the configuration, otherwise
+/// `grit/ignore` in the user's configuration directory
+fn global_excludes_file(root: &Path) -> Result<Option<PathBuf>> {
+    let config = Config::load(Some(&root.join(GRIT_DIR)), &process_env)?;
+    Ok(config.path("core.excludesfile", &process_env)
+        .or_else(|| user_config_dir(&process_env).map(|dir| dir.join("ignore"))))
+}
+
+fn read_patterns(path: &Path, base: &Path) -> Result<Vec<IgnorePattern>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content.lines()
+        .enumerate()
+        .filter_map(|(index, line)| IgnorePattern::parse(line, base, path, index + 1))
+        .collect())
+}
+
+/// Trailing spaces are not part of a pattern, unless escaped with a backslash
+fn trim_trailing_spaces(line: &str) -> &str {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+fn to_slash_path(path: &Path) -> String {
+    path.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Match a path against a glob: `*` and `?` stop at `/`, `**` spans directories,
+/// `[...]` is a character class and `\` escapes the next character
+pub fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            if let Some(after_slash) = rest.strip_prefix(&['/']) {
+                // `**/` matches zero or more leading directories
+                glob_match(after_slash, text)
+                    || text.iter().enumerate().any(|(i, c)| *c == '/' && glob_match(after_slash, &text[i + 1..]))
+            } else {
+                (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+            }
+        }
+        Some('*') => {
+            for i in 0..=text.len() {
+                if glob_match(&pattern[1..], &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => text.first().is_some_and(|c| *c != '/') && glob_match(&pattern[1..], &text[1..]),
+        Some('[') => match match_class(&pattern[1..], text.first().copied()) {
+            Some((matched, consumed)) => matched && glob_match(&pattern[1 + consumed..], &text[1..]),
+            // An unterminated class is a literal `[`
+            None => text.first() == Some(&'[') && glob_match(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]),
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Match one character against the class starting right after `[`. Returns whether it
+/// matched and how many pattern characters the class used, `None` if it is not closed.
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let (negated, start) = match class.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+
+    let mut matched = false;
+    let mut i = start;
+    loop {
+        let first = *class.get(i)?;
+        // A `]` right at the start is part of the class
+        if first == ']' && i > start {
+            break;
+        }
+        if class.get(i + 1) == Some(&'-') && class.get(i + 2).is_some_and(|last| *last != ']') {
+            let last = class[i + 2];
+            matched |= c.is_some_and(|c| first <= c && c <= last);
+            i += 3;
+        } else {
+            matched |= c == Some(first);
+            i += 1;
+        }
+    }
+
+    let matched = c.is_some_and(|c| c != '/') && matched != negated;
+    Some((matched, i + 1))
+}
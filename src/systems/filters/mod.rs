@@ -1,2 +1,4 @@
-pub mod filter;
+pub mod ignore;
+pub mod attributes;
+#[cfg(test)]
 mod test;
\ No newline at end of file
@@ -1,136 +1,121 @@
-use super::filter::{
-    FileFilter, load_file_filter, filter_paths
-};
+use super::ignore::{glob_match, IgnorePattern, IgnoreRules};
+use super::attributes::{looks_binary, Attributes};
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
-    use std::path::PathBuf;
-    use tempfile::NamedTempFile;
-
-    /// Helper function to create a temporary filter file
-    fn create_temp_filter(content: &str) -> NamedTempFile {
-        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
-        temp_file.write_all(content.as_bytes()).expect("Failed to write to temp file");
-        temp_file
+    use std::path::{Path, PathBuf};
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
     }
 
-    #[test]
-    fn test_load_file_filter_toml() {
-        let toml_content = r#"
-            allowed = ["/home/user/docs", "/home/user/projects"]
-            denied = ["/home/user/private"]
-        "#;
-
-        let temp_file = create_temp_filter(toml_content);
-        let filter = load_file_filter(temp_file.path().to_str().unwrap()).expect("Failed to load TOML filter");
-
-        assert_eq!(filter.allowed, Some(vec![
-            "/home/user/docs".to_string(),
-            "/home/user/projects".to_string(),
-        ]));
-        assert_eq!(filter.denied, Some(vec!["/home/user/private".to_string()]));
+    fn pattern(line: &str, base: &str) -> IgnorePattern {
+        IgnorePattern::parse(line, Path::new(base), Path::new(".gritignore"), 1).expect("Not a pattern")
     }
 
     #[test]
-    fn test_load_file_filter_json() {
-        let json_content = r#"
-        {
-            "allowed": ["/home/user/docs", "/home/user/projects"],
-            "denied": ["/home/user/private"]
-        }
-        "#;
-
-        let temp_file = create_temp_filter(json_content);
-        let filter = load_file_filter(temp_file.path().to_str().unwrap()).expect("Failed to load JSON filter");
-
-        assert_eq!(filter.allowed, Some(vec![
-            "/home/user/docs".to_string(),
-            "/home/user/projects".to_string(),
-        ]));
-        assert_eq!(filter.denied, Some(vec!["/home/user/private".to_string()]));
+    fn test_glob_match() {
+        assert!(glob("*.log", "debug.log"));
+        assert!(!glob("*.log", "logs/debug.log"), "* does not cross directories");
+        assert!(glob("**/build", "build"));
+        assert!(glob("**/build", "a/b/build"));
+        assert!(glob("logs/**", "logs/a/b.txt"));
+        assert!(!glob("logs/**", "logs"));
+        assert!(glob("a/**/b", "a/b"));
+        assert!(glob("a/**/b", "a/x/y/b"));
+        assert!(glob("file?.txt", "file1.txt"));
+        assert!(!glob("file?.txt", "file/.txt"));
+        assert!(glob("[abc].txt", "b.txt"));
+        assert!(glob("[a-c].txt", "c.txt"));
+        assert!(!glob("[!a-c].txt", "c.txt"));
+        assert!(glob(r"\*.txt", "*.txt"));
+        assert!(!glob(r"\*.txt", "a.txt"));
+        assert!(glob("[unclosed", "[unclosed"));
     }
 
     #[test]
-    fn test_filter_paths_allowed_only() {
-        let filter = FileFilter {
-            allowed: Some(vec![
-                "/home/user/docs".to_string(),
-                "/home/user/projects".to_string(),
-            ]),
-            denied: None,
-        };
-
-        let paths = vec![
-            PathBuf::from("/home/user/docs/1"),
-            PathBuf::from("/home/user/private/1"),
-            PathBuf::from("/home/user/projects/1"),
-        ];
-
-        let filtered = filter_paths(paths, &filter);
-        assert_eq!(filtered, vec![
-            PathBuf::from("/home/user/docs/1"),
-            PathBuf::from("/home/user/projects/1"),
-        ]);
+    fn test_parse_ignore_pattern() {
+        assert!(IgnorePattern::parse("", Path::new(""), Path::new("x"), 1).is_none());
+        assert!(IgnorePattern::parse("# comment", Path::new(""), Path::new("x"), 1).is_none());
+
+        let hash = pattern(r"\#not-a-comment", "");
+        assert!(hash.matches(&PathBuf::from("#not-a-comment"), false));
+
+        let negated = pattern("!keep.log", "");
+        assert!(negated.negated);
+        assert!(negated.matches(&PathBuf::from("dir/keep.log"), false));
+
+        let trailing = pattern("spaced   ", "");
+        assert_eq!(trailing.text, "spaced");
     }
 
     #[test]
-    fn test_filter_paths_denied_only() {
-        let filter = FileFilter {
-            allowed: None,
-            denied: Some(vec!["/home/user/private".to_string()]),
-        };
-
-        let paths = vec![
-            PathBuf::from("/home/user/docs"),
-            PathBuf::from("/home/user/private/file.txt"),
-            PathBuf::from("/home/user/projects"),
-        ];
-
-        let filtered = filter_paths(paths, &filter);
-        assert_eq!(filtered, vec![
-            PathBuf::from("/home/user/docs"),
-            PathBuf::from("/home/user/projects"),
-        ]);
+    fn test_anchoring_and_directory_patterns() {
+        let anywhere = pattern("build/", "");
+        assert!(anywhere.matches(&PathBuf::from("build"), true));
+        assert!(anywhere.matches(&PathBuf::from("src/build"), true));
+        assert!(!anywhere.matches(&PathBuf::from("build"), false), "Trailing slash only matches directories");
+
+        let anchored = pattern("/build", "");
+        assert!(anchored.matches(&PathBuf::from("build"), false));
+        assert!(!anchored.matches(&PathBuf::from("src/build"), false));
+
+        let nested = pattern("doc/*.txt", "sub");
+        assert!(nested.matches(&PathBuf::from("sub/doc/a.txt"), false));
+        assert!(!nested.matches(&PathBuf::from("doc/a.txt"), false), "Patterns are relative to their file");
+        assert!(!nested.matches(&PathBuf::from("sub/x/doc/a.txt"), false));
     }
 
     #[test]
-    fn test_filter_paths_allowed_and_denied() {
-        let filter = FileFilter {
-            allowed: Some(vec![
-                "/home/user/docs".to_string(),
-                "/home/user/projects".to_string(),
-            ]),
-            denied: Some(vec!["/home/user/docs".to_string()]), // Docs should be removed
-        };
-
-        let paths = vec![
-            PathBuf::from("/home/user/docs"),
-            PathBuf::from("/home/user/private"),
-            PathBuf::from("/home/user/projects"),
-        ];
-
-        let filtered = filter_paths(paths, &filter);
-        assert_eq!(filtered, vec![
-            PathBuf::from("/home/user/projects"),
-        ]);
+    fn test_ignore_rules_precedence() -> std::io::Result<()> {
+        let tmp_dir = tempfile::TempDir::new()?;
+        let root = tmp_dir.path();
+        std::fs::create_dir_all(root.join(".grit"))?;
+        std::fs::create_dir_all(root.join("sub/target"))?;
+        std::fs::write(root.join(".gritignore"), "*.log\n!keep.log\ntarget/\n")?;
+        std::fs::write(root.join("sub/.gritignore"), "keep.log\n!important.log\n")?;
+        std::fs::write(root.join("global_ignore"), "*.tmp\n")?;
+        std::fs::write(root.join(".grit/config"), format!("excludesfile={}\n", root.join("global_ignore").display()))?;
+
+        let mut rules = IgnoreRules::load(root)?;
+        assert!(rules.is_ignored(&PathBuf::from("debug.log"), false)?);
+        assert!(!rules.is_ignored(&PathBuf::from("keep.log"), false)?, "Negation re-includes the file");
+        assert!(rules.is_ignored(&PathBuf::from("sub/keep.log"), false)?, "Deeper files take precedence");
+        assert!(!rules.is_ignored(&PathBuf::from("sub/important.log"), false)?);
+        assert!(rules.is_ignored(&PathBuf::from("scratch.tmp"), false)?, "Global excludes apply");
+        assert!(rules.is_ignored(&PathBuf::from("sub/target/out.bin"), false)?, "Files below an ignored directory are ignored");
+        assert!(rules.is_ignored(&PathBuf::from(".grit"), true)?);
+
+        let deciding = rules.matching(&PathBuf::from("sub/keep.log"), false)?.unwrap();
+        assert_eq!((deciding.source, deciding.line, deciding.text.as_str()), (PathBuf::from("sub/.gritignore"), 1, "keep.log"));
+        Ok(())
     }
 
     #[test]
-    fn test_filter_paths_no_restrictions() {
-        let filter = FileFilter {
-            allowed: None,
-            denied: None,
-        };
-
-        let paths = vec![
-            PathBuf::from("/home/user/docs"),
-            PathBuf::from("/home/user/private"),
-            PathBuf::from("/home/user/projects"),
-        ];
-
-        let filtered = filter_paths(paths.clone(), &filter);
-        assert_eq!(filtered, paths); // Should return all paths
+    fn test_binary_detection_and_attributes() -> std::io::Result<()> {
+        assert!(looks_binary(b"PNG\0\x01"));
+        assert!(!looks_binary("caf\u{e9} \u{2014} plain text\n".as_bytes()));
+        assert!(!looks_binary(b""));
+
+        let tmp_dir = tempfile::TempDir::new()?;
+        std::fs::write(tmp_dir.path().join(".gritattributes"), concat!(
+            "# assets\n",
+            "*.png binary\n",
+            "*.dat -text\n",
+            "docs/*.dat text\n",
+            "*.txt diff\n",
+        ))?;
+        let attributes = Attributes::load(tmp_dir.path())?;
+
+        assert_eq!(attributes.binary(Path::new("img/logo.png")), Some(true));
+        assert_eq!(attributes.binary(Path::new("data.dat")), Some(true));
+        assert_eq!(attributes.binary(Path::new("docs/notes.dat")), Some(false), "Later lines win");
+        assert_eq!(attributes.binary(Path::new("a.txt")), None, "Other attributes do not decide");
+
+        assert!(attributes.is_binary(Path::new("logo.png"), b"no NUL here"));
+        assert!(!attributes.is_binary(Path::new("docs/notes.dat"), b"\0"));
+        assert!(attributes.is_binary(Path::new("a.txt"), b"\0"), "Undecided files are looked at");
+        Ok(())
     }
 }
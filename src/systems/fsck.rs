@@ -0,0 +1,254 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::systems::commits::functions::{load_all_commits, reachable, COMMITS_FILE};
+use crate::systems::commits::versioning::Version;
+use crate::systems::gc::{plural, reachable_objects, root_ids, version_files};
+use crate::systems::merge::merge_head;
+use crate::systems::objects::object::{hash_object, ObjectKind};
+use crate::systems::objects::pack::{list_packs, PackIndex};
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::repository::Repository;
+use crate::systems::objects::tree::read_entries;
+use crate::systems::refs::{list_branches, read_head, read_ref, Head};
+use crate::error::Result;
+
+/// Something `grit fsck` found wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The commit database cannot be parsed
+    CommitDatabase(String),
+    /// An object that cannot be read, or whose content no longer hashes to its id
+    CorruptObject { id: String, reason: String },
+    /// An object something refers to that the database does not have
+    MissingObject { id: String, referenced_by: String },
+    /// A commit whose record does not hold together
+    BadCommit { id: String, reason: String },
+    /// A branch, HEAD or a merge in progress naming an unknown commit
+    BadRef { name: String, id: String },
+    /// A version chain that cannot be loaded or replayed
+    BadVersions { path: PathBuf, reason: String },
+    /// A pack that no longer matches its checksum, or whose index does not fit it
+    BadPack { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::CommitDatabase(reason) => write!(f, "error: {}", reason),
+            Problem::CorruptObject { id, reason } => write!(f, "error: corrupt object {}: {}", id, reason),
+            Problem::MissingObject { id, referenced_by } => write!(f, "error: missing object {} (referenced by {})", id, referenced_by),
+            Problem::BadCommit { id, reason } => write!(f, "error: bad commit {}: {}", id, reason),
+            Problem::BadRef { name, id } => write!(f, "error: {} points at unknown commit {}", name, id),
+            Problem::BadVersions { path, reason } => write!(f, "error: bad version chain {}: {}", path.display(), reason),
+            Problem::BadPack { path, reason } => write!(f, "error: bad pack {}: {}", path.display(), reason),
+        }
+    }
+}
+
+/// What `grit fsck` checked and found.
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    pub objects: usize,
+    pub commits: usize,
+    pub chains: usize,
+    pub problems: Vec<Problem>,
+    /// Intact objects nothing leads to any more, which `grit gc` would eventually remove
+    pub dangling: Vec<(ObjectKind, String)>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Verifies the whole repository: every pack matches its checksum, every object still
+/// hashes to its id, every commit has its tree, the objects below it and its parents,
+/// every ref names a known commit, and every version chain replays to its last version.
+/// Objects no ref leads to are reported as dangling, which is not a problem.
+pub fn fsck(repo: &Repository) -> Result<FsckReport> {
+    let store = repo.objects();
+    let mut report = FsckReport::default();
+
+    // The objects of a pack whose index cannot be listed are left out, its problem says why
+    let mut ids = store.loose_ids()?;
+    for pack in list_packs(&store.pack_dir())? {
+        let listed = PackIndex::open(&pack).and_then(|index| {
+            let verified = index.verify();
+            ids.extend(index.ids()?);
+            verified
+        });
+        if let Err(e) = listed {
+            let path = pack.strip_prefix(repo.root()).map(Path::to_path_buf).unwrap_or(pack);
+            report.problems.push(Problem::BadPack { path, reason: e.with_causes() });
+        }
+    }
+    ids.sort();
+    ids.dedup();
+
+    let mut kinds = HashMap::new();
+    for id in ids {
+        report.objects += 1;
+        match store.read(&id) {
+            Ok((kind, data)) if hash_object(kind, &data) == id => { kinds.insert(id, kind); }
+            Ok((kind, data)) => {
+                let reason = format!("content hashes to {}", hash_object(kind, &data));
+                report.problems.push(Problem::CorruptObject { id, reason });
+            }
+            Err(e) => report.problems.push(Problem::CorruptObject { id, reason: e.with_causes() }),
+        }
+    }
+    let corrupt: HashSet<String> = report.problems.iter()
+        .filter_map(|problem| match problem {
+            Problem::CorruptObject { id, .. } => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut checker = Checker { store: &store, kinds: &kinds, corrupt: &corrupt, checked: HashSet::new(), problems: Vec::new() };
+
+    let commits = match repo.grit_path(COMMITS_FILE).is_file() {
+        true => load_all_commits(repo).unwrap_or_else(|e| {
+            checker.problems.push(Problem::CommitDatabase(e.with_causes()));
+            HashMap::new()
+        }),
+        false => HashMap::new(),
+    };
+    let sorted: BTreeMap<&String, _> = commits.iter().collect();
+    for (id, commit) in sorted {
+        report.commits += 1;
+        let bad = |reason: String| Problem::BadCommit { id: id.clone(), reason };
+        // Commits made before snapshots were recorded have neither a tree nor an object
+        if !commit.tree.is_empty() {
+            if !commit.verify() {
+                checker.problems.push(bad("its id does not match its content".to_string()));
+            }
+            checker.expect(id, ObjectKind::Commit, "the commit database");
+            checker.tree(&commit.tree, &format!("commit {}", id));
+        }
+        for parent in commit.parents.iter().filter(|parent| !commits.contains_key(*parent)) {
+            checker.problems.push(bad(format!("parent {} is unknown", parent)));
+        }
+    }
+
+    // Without a readable database every ref would look wrong, its error says enough
+    if !checker.problems.iter().any(|problem| matches!(problem, Problem::CommitDatabase(_))) {
+        let mut refs = Vec::new();
+        for branch in list_branches(repo)? {
+            refs.extend(read_ref(repo, &branch)?.map(|id| (format!("refs/heads/{}", branch), id)));
+        }
+        if let Head::Detached(id) = read_head(repo)? {
+            refs.push(("HEAD".to_string(), id));
+        }
+        refs.extend(merge_head(repo)?.map(|id| ("MERGE_HEAD".to_string(), id)));
+        for (name, id) in refs.into_iter().filter(|(_, id)| !commits.contains_key(id)) {
+            checker.problems.push(Problem::BadRef { name, id });
+        }
+    }
+    report.problems.extend(checker.problems);
+
+    // Chains keep the blobs they were packed into and their closed segments
+    let mut chain_objects = Vec::new();
+    for (_, path) in version_files(repo)? {
+        report.chains += 1;
+        let replayed = Version::load(repo, &path).and_then(|version| {
+            chain_objects.extend(version.objects().cloned());
+            version.verify(repo)
+        });
+        if let Err(e) = replayed {
+            let path = path.strip_prefix(repo.root()).map(Path::to_path_buf).unwrap_or(path);
+            report.problems.push(Problem::BadVersions { path, reason: e.with_causes() });
+        }
+    }
+
+    // Dangling objects: intact, but not reachable from any ref or the staging tree. A tree
+    // that cannot be read is already reported, and leaves nothing to tell dangling apart.
+    let roots = root_ids(repo)?;
+    let referenced = reachable(&commits, roots.iter().map(String::as_str)).into_iter().map(|id| &commits[id]);
+    let mut dangling: Vec<(ObjectKind, String)> = match reachable_objects(repo, &store, referenced, &roots) {
+        Ok(mut marked) => {
+            marked.extend(chain_objects);
+            kinds.into_iter()
+                .filter(|(id, _)| !marked.contains(id))
+                .map(|(id, kind)| (kind, id))
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    };
+    dangling.sort_by(|a, b| a.1.cmp(&b.1));
+    report.dangling = dangling;
+
+    Ok(report)
+}
+
+/// Text `grit fsck` prints: problems, dangling objects, then what was checked
+pub fn format_fsck(report: &FsckReport) -> String {
+    let mut out = String::new();
+    for problem in &report.problems {
+        out.push_str(&format!("{}\n", problem));
+    }
+    for (kind, id) in &report.dangling {
+        out.push_str(&format!("dangling {} {}\n", kind.as_str(), id));
+    }
+    let outcome = match report.problems.len() {
+        0 => "no problems found".to_string(),
+        count => format!("{} found", plural(count, "problem")),
+    };
+    out.push_str(&format!(
+        "Checked {}, {} and {}: {}\n",
+        plural(report.objects, "object"), plural(report.commits, "commit"), plural(report.chains, "version chain"), outcome
+    ));
+    out
+}
+
+/// Follows what commits refer to, reporting each missing or mistyped object once.
+struct Checker<'a> {
+    store: &'a ObjectStore,
+    kinds: &'a HashMap<String, ObjectKind>,
+    corrupt: &'a HashSet<String>,
+    checked: HashSet<String>,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    /// Check an object exists with the given kind, returning whether it is worth following
+    fn expect(&mut self, id: &str, kind: ObjectKind, referenced_by: &str) -> bool {
+        if !self.checked.insert(id.to_string()) || self.corrupt.contains(id) {
+            return false;
+        }
+        match self.kinds.get(id) {
+            None => {
+                self.problems.push(Problem::MissingObject { id: id.to_string(), referenced_by: referenced_by.to_string() });
+                false
+            }
+            Some(found) if *found != kind => {
+                self.problems.push(Problem::CorruptObject {
+                    id: id.to_string(),
+                    reason: format!("is a {} where {} expects a {}", found.as_str(), referenced_by, kind.as_str()),
+                });
+                false
+            }
+            Some(_) => true,
+        }
+    }
+
+    fn tree(&mut self, id: &str, referenced_by: &str) {
+        if !self.expect(id, ObjectKind::Tree, referenced_by) {
+            return;
+        }
+        let entries = match read_entries(self.store, id) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.problems.push(Problem::CorruptObject { id: id.to_string(), reason: e.with_causes() });
+                return;
+            }
+        };
+        let referenced_by = format!("tree {}", id);
+        for entry in entries {
+            match entry.kind {
+                ObjectKind::Tree => self.tree(&entry.hash, &referenced_by),
+                kind => { self.expect(&entry.hash, kind, &referenced_by); }
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use crate::test_support::TempRepo;
+use crate::systems::commits::commit::Commit;
+
+/// Helper creating a repository with two commits of `a.txt`, returning the second
+fn history(repo: &TempRepo) -> io::Result<Commit> {
+    repo.commit_file("a.txt", "one\ntwo\nthree\n", "First")?;
+    repo.commit_file("a.txt", "one\n2\nthree\nfour\n", "Second")
+}
+
+/// Loose file of an object
+fn object_file(id: &str) -> String {
+    format!(".grit/objects/{}/{}", &id[..2], &id[2..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use serde_json::Value;
+    use crate::test_support::TempRepo;
+    use crate::algorithms::fcompress::comp_bytes;
+    use crate::systems::branch::{create_branch, delete_branch};
+    use crate::systems::checkout::switch;
+    use crate::systems::commits::functions::load_all_commits;
+    use crate::systems::fsck::{format_fsck, fsck, Problem};
+    use crate::systems::objects::object::{encode_object, hash_object, ObjectKind};
+    use crate::systems::objects::pack::repack;
+    use crate::systems::refs::write_ref;
+
+    const VERSIONS: &str = ".grit/versions/Main/a.txt.json";
+
+    #[test]
+    fn test_healthy_repository_reports_dangling_commits() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        create_branch(&repo, "feature", None)?;
+        switch(&repo, "feature", false)?;
+        let abandoned = repo.commit_file("b.txt", "b\n", "Abandoned")?;
+        switch(&repo, "Main", false)?;
+        delete_branch(&repo, "feature", true)?;
+
+        let report = fsck(&repo)?;
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.commits, 3);
+        assert_eq!(report.chains, 2);
+        assert!(report.dangling.contains(&(ObjectKind::Commit, abandoned.id.clone())));
+        assert!(report.dangling.contains(&(ObjectKind::Blob, hash_object(ObjectKind::Blob, b"b\n"))));
+
+        let text = format_fsck(&report);
+        assert!(text.contains(&format!("dangling commit {}\n", abandoned.id)));
+        assert!(text.ends_with(": no problems found\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rehashing_finds_tampered_objects() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        let blob = hash_object(ObjectKind::Blob, b"one\ntwo\nthree\n");
+        repo.write(object_file(&blob), comp_bytes(&encode_object(ObjectKind::Blob, b"tampered"))?)?;
+
+        let report = fsck(&repo)?;
+        assert!(!report.is_ok());
+        assert!(matches!(&report.problems[..], [Problem::CorruptObject { id, reason }]
+            if *id == blob && reason.contains(&hash_object(ObjectKind::Blob, b"tampered"))));
+
+        repo.write(object_file(&blob), b"not even zlib")?;
+        assert!(matches!(&fsck(&repo)?.problems[..], [Problem::CorruptObject { id, .. }] if *id == blob));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_objects_and_unknown_parents() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let second = history(&repo)?;
+        let blob = hash_object(ObjectKind::Blob, b"one\n2\nthree\nfour\n");
+        fs::remove_file(repo.working_path(object_file(&blob)))?;
+
+        let problems = fsck(&repo)?.problems;
+        assert_eq!(problems, vec![Problem::MissingObject { id: blob, referenced_by: format!("tree {}", second.tree) }]);
+
+        // Drop the first commit from the database, leaving the second with an unknown parent
+        let mut commits: Value = serde_json::from_str(&repo.read(".grit/commits.json")?)?;
+        commits.as_object_mut().unwrap().remove(&second.parents[0]);
+        repo.write(".grit/commits.json", commits.to_string())?;
+        let problems = fsck(&repo)?.problems;
+        assert!(problems.contains(&Problem::BadCommit {
+            id: second.id.clone(),
+            reason: format!("parent {} is unknown", second.parents[0]),
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_refs_must_name_known_commits() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        write_ref(&repo, "ghost", &"ab".repeat(20))?;
+
+        assert_eq!(fsck(&repo)?.problems, vec![Problem::BadRef { name: "refs/heads/ghost".to_string(), id: "ab".repeat(20) }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_commit_database_is_reported() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        repo.write(".grit/commits.json", "{ \"truncated\": ")?;
+
+        assert!(load_all_commits(&repo).is_err(), "A damaged database must not read as empty");
+        let report = fsck(&repo)?;
+        assert!(matches!(&report.problems[..], [Problem::CommitDatabase(_)]), "{:?}", report.problems);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_pack_index_is_reported() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        let stats = repack(&repo)?.expect("Objects to pack");
+        repo.write("loose.txt", "stays loose\n")?;
+        repo.add(None)?;
+
+        // The index still opens, but its entries are gone
+        let index = stats.path.with_extension("idx");
+        let header = fs::read(&index)?[..12].to_vec();
+        fs::write(&index, header)?;
+
+        let report = fsck(&repo)?;
+        assert!(report.problems.iter().any(|problem| matches!(problem, Problem::BadPack { .. })), "{:?}", report.problems);
+        assert_eq!(report.objects, 1, "The loose object is still checked");
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_chains_are_replayed() -> io::Result<()> {
+        let repo = TempRepo::new();
+        history(&repo)?;
+        let original = repo.read(VERSIONS)?;
+
+        // An edit past the end of the file used to be skipped silently
+        let mut chain: Value = serde_json::from_str(&original)?;
+        chain["versions"][0]["changes"] = serde_json::json!([{ "Delete": 999 }]);
+        repo.write(VERSIONS, chain.to_string())?;
+        let problems = fsck(&repo)?.problems;
+        assert!(matches!(&problems[..], [Problem::BadVersions { path, reason }]
+            if path == Path::new(VERSIONS) && reason.contains("out of range")), "{:?}", problems);
+
+        repo.write(VERSIONS, &original[..original.len() / 2])?;
+        assert!(matches!(&fsck(&repo)?.problems[..], [Problem::BadVersions { .. }]));
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::structure::serialization::load;
+use crate::systems::commits::commit::Commit;
+use crate::systems::commits::functions::{load_all_commits, reachable, save_all_commits, COMMITS_FILE};
+use crate::systems::commits::versioning::{pack_chains, version_file, Version, VERSIONS_DIR};
+use crate::systems::merge::merge_head;
+use crate::systems::objects::object::ObjectKind;
+use crate::systems::objects::pack::PackStats;
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::objects::tree::read_entries;
+use crate::systems::refs::{current_branch, list_branches, read_head, read_ref, Head};
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Unreachable data younger than this is kept, so gc never removes what a command running
+/// at the same time has just written and not referenced yet
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Branches and tags, every file below naming a commit
+const REFS_DIR: &str = "refs";
+/// Reflogs, whose lines name the commits refs used to point at
+const LOGS_DIR: &str = "logs";
+
+/// What `grit gc` removed, or would remove with `--dry-run`.
+#[derive(Debug, Clone, Default)]
+pub struct GcSummary {
+    pub objects: Vec<String>,
+    pub commits: Vec<String>,
+    /// Version chain files, relative to the root
+    pub versions: Vec<PathBuf>,
+    /// Unreachable entries kept because they are newer than the grace period
+    pub recent: usize,
+    /// Bytes freed; for a dry run only what loose objects and version files take, as the
+    /// share of packed objects is only known once the pack is rewritten
+    pub reclaimed: u64,
+    /// The pack the remaining objects were consolidated into
+    pub pack: Option<PackStats>,
+}
+
+/// Removes everything no branch, tag, reflog entry, detached HEAD or merge in progress
+/// leads to: commits, the objects only they use, and version chains of deleted branches.
+/// Anything written less than `grace` ago is kept. The objects left are then repacked.
+pub fn gc(repo: &Repository, grace: Duration, dry_run: bool) -> Result<GcSummary> {
+    let store = repo.objects();
+    let cutoff = SystemTime::now().checked_sub(grace).unwrap_or(UNIX_EPOCH);
+    let cutoff_secs = cutoff.duration_since(UNIX_EPOCH).map_or(0, |age| age.as_secs());
+    let mut commits = if repo.grit_path(COMMITS_FILE).is_file() { load_all_commits(repo)? } else { HashMap::new() };
+
+    // A branch pointing at an unknown commit means a damaged database, where marking would
+    // miss everything that branch holds
+    for branch in list_branches(repo)? {
+        if let Some(id) = read_ref(repo, &branch)?.filter(|id| !commits.contains_key(id)) {
+            return Err(GritError::corrupt(format!(
+                "Branch '{}' points at unknown commit {}, refusing to collect garbage", branch, id
+            )));
+        }
+    }
+
+    let roots = root_ids(repo)?;
+    let referenced = reachable(&commits, roots.iter().map(String::as_str));
+    let recent_commits: Vec<&str> = commits.values()
+        .filter(|commit| commit.timestamp > cutoff_secs && !referenced.contains(commit.id.as_str()))
+        .map(|commit| commit.id.as_str())
+        .collect();
+    let live = reachable(&commits, referenced.iter().copied().chain(recent_commits.iter().copied()));
+
+    let mut summary = GcSummary { recent: recent_commits.len(), ..Default::default() };
+    let mut dead_commits: Vec<String> = commits.keys().filter(|id| !live.contains(id.as_str())).cloned().collect();
+    dead_commits.sort();
+
+    // Mark every object the live commits, the roots and the staging tree use
+    let mut reachable = reachable_objects(repo, &store, live.iter().map(|id| &commits[*id]), &roots)?;
+
+    // Chains of existing branches are still appended to, others only matter to live commits
+    let mut branches: HashSet<String> = list_branches(repo)?.into_iter().collect();
+    branches.extend(current_branch(repo)?);
+    let used: HashSet<PathBuf> = live.iter()
+        .flat_map(|id| commits[*id].versions.keys().map(|path| version_file(repo, &commits[*id].branch, Path::new(path))))
+        .collect();
+    for (branch, file) in version_files(repo)? {
+        let kept = branches.contains(&branch) || used.contains(&file);
+        if !kept && modified(&file)? <= cutoff {
+            summary.reclaimed += fs::metadata(&file)?.len();
+            summary.versions.push(file.strip_prefix(repo.root()).map(Path::to_path_buf).unwrap_or(file));
+            continue;
+        }
+        if !kept {
+            summary.recent += 1;
+        }
+        // The blobs of a chain live on as long as the chain does
+        reachable.extend(Version::load(repo, &file)?.objects().cloned());
+    }
+
+    let mut keep = Vec::new();
+    for id in store.object_ids()? {
+        if reachable.contains(&id) {
+            keep.push(id);
+            continue;
+        }
+        let (location, size) = store.location(&id)?;
+        if modified(&location)? > cutoff {
+            summary.recent += 1;
+            // Recent packed objects go into the new pack, recent loose ones stay as they are
+            if size == 0 {
+                keep.push(id);
+            }
+        } else {
+            summary.reclaimed += size;
+            summary.objects.push(id);
+        }
+    }
+
+    if dry_run {
+        summary.commits = dead_commits;
+        return Ok(summary);
+    }
+
+    let before = disk_usage(repo.grit_dir())?;
+    if !dead_commits.is_empty() {
+        commits.retain(|id, _| !dead_commits.contains(id));
+        save_all_commits(repo, &commits)?;
+    }
+    for file in summary.versions.iter().map(|file| repo.working_path(file)) {
+        fs::remove_file(&file)?;
+        remove_empty_parents(&file, &repo.grit_path(VERSIONS_DIR))?;
+    }
+    for id in &summary.objects {
+        if store.location(id)?.1 > 0 {
+            store.remove_loose(id)?;
+        }
+    }
+    // What is left of the chains goes into the pack as well
+    keep.extend(pack_chains(repo)?);
+    keep.sort();
+    keep.dedup();
+    summary.pack = store.repack_objects(&keep)?;
+    summary.reclaimed = before.saturating_sub(disk_usage(repo.grit_dir())?);
+    summary.commits = dead_commits;
+    Ok(summary)
+}
+
+/// Parses a grace period such as `30m`, `12h`, `3d`, `2w` or `now`; a bare number is seconds
+pub fn parse_grace(text: &str) -> Result<Duration> {
+    let invalid = || GritError::InvalidArgument(format!("Invalid grace period: {}", text));
+    if text == "now" {
+        return Ok(Duration::ZERO);
+    }
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let amount: u64 = text[..split].parse().map_err(|_| invalid())?;
+    let unit = match &text[split..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    amount.checked_mul(unit).map(Duration::from_secs).ok_or_else(invalid)
+}
+
+/// Text `grit gc` prints: one line per removal for a dry run, then the totals
+pub fn format_gc(summary: &GcSummary, dry_run: bool) -> String {
+    let mut out = String::new();
+    if dry_run {
+        for id in &summary.commits {
+            out.push_str(&format!("Would remove commit {}\n", id));
+        }
+        for id in &summary.objects {
+            out.push_str(&format!("Would remove object {}\n", id));
+        }
+        for file in &summary.versions {
+            out.push_str(&format!("Would remove {}\n", file.display()));
+        }
+    }
+
+    let removed = format!(
+        "{}, {} and {}",
+        plural(summary.commits.len(), "commit"),
+        plural(summary.objects.len(), "object"),
+        plural(summary.versions.len(), "version file"),
+    );
+    if dry_run {
+        out.push_str(&format!("Would remove {}, reclaiming at least {} bytes\n", removed, summary.reclaimed));
+    } else {
+        out.push_str(&format!("Removed {}, reclaimed {} bytes\n", removed, summary.reclaimed));
+    }
+    if let Some(pack) = &summary.pack {
+        let name = pack.path.file_name().unwrap_or_default().to_string_lossy();
+        out.push_str(&format!("Packed {} ({} as deltas) into {}\n", plural(pack.objects, "object"), pack.deltas, name));
+    }
+    if summary.recent > 0 {
+        out.push_str(&format!("Kept {} newer than the grace period\n", plural(summary.recent, "unreachable entry")));
+    }
+    out
+}
+
+pub(crate) fn plural(count: usize, noun: &str) -> String {
+    match (count, noun.strip_suffix('y')) {
+        (1, _) => format!("1 {}", noun),
+        (_, Some(stem)) => format!("{} {}ies", count, stem),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Object ids named by refs, reflogs, a detached HEAD and a merge in progress
+pub(crate) fn root_ids(repo: &Repository) -> Result<BTreeSet<String>> {
+    let mut files = Vec::new();
+    collect_files(&repo.grit_path(REFS_DIR), &mut files)?;
+    collect_files(&repo.grit_path(LOGS_DIR), &mut files)?;
+
+    let mut roots = BTreeSet::new();
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        roots.extend(
+            content.split_whitespace()
+                .filter(|word| word.len() == 40 && word.chars().all(|c| c.is_ascii_hexdigit()))
+                .map(str::to_ascii_lowercase),
+        );
+    }
+    if let Head::Detached(id) = read_head(repo)? {
+        roots.insert(id);
+    }
+    roots.extend(merge_head(repo)?);
+    Ok(roots)
+}
+
+/// Every object `commits`, `roots` and the staging tree lead to: the commits themselves,
+/// their trees and everything below them. Fails when a tree cannot be read, as what it
+/// holds would otherwise look unreachable.
+pub(crate) fn reachable_objects<'a>(
+    repo: &Repository,
+    store: &ObjectStore,
+    commits: impl IntoIterator<Item = &'a Commit>,
+    roots: &BTreeSet<String>,
+) -> Result<HashSet<String>> {
+    let mut reachable: HashSet<String> = roots.iter().cloned().collect();
+    for commit in commits {
+        reachable.insert(commit.id.clone());
+        mark_tree(store, &commit.tree, &mut reachable)?;
+    }
+    let tree = load(repo)?;
+    reachable.extend(tree.snapshot().into_values());
+    for stages in tree.unmerged().values() {
+        reachable.extend([&stages.base, &stages.ours, &stages.theirs].into_iter().flatten().cloned());
+    }
+    Ok(reachable)
+}
+
+/// Mark a tree and everything below it, skipping trees already marked
+fn mark_tree(store: &ObjectStore, id: &str, reachable: &mut HashSet<String>) -> Result<()> {
+    // Commits made before snapshots were recorded have no tree
+    if id.is_empty() || !reachable.insert(id.to_string()) {
+        return Ok(());
+    }
+    for entry in read_entries(store, id)? {
+        match entry.kind {
+            ObjectKind::Tree => mark_tree(store, &entry.hash, reachable)?,
+            _ => { reachable.insert(entry.hash); }
+        }
+    }
+    Ok(())
+}
+
+/// Every chain file with the branch it belongs to
+pub(crate) fn version_files(repo: &Repository) -> Result<Vec<(String, PathBuf)>> {
+    let versions_dir = repo.grit_path(VERSIONS_DIR);
+    let mut files = Vec::new();
+    collect_files(&versions_dir, &mut files)?;
+    Ok(files.into_iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|file| {
+            let branch = file.parent()?.strip_prefix(&versions_dir).ok()?.to_string_lossy().replace('\\', "/");
+            Some((branch, file))
+        })
+        .collect())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+fn disk_usage(path: &Path) -> Result<u64> {
+    let mut files = Vec::new();
+    collect_files(path, &mut files)?;
+    Ok(files.iter().map(|file| fs::metadata(file).map(|metadata| metadata.len())).sum::<io::Result<u64>>()?)
+}
+
+/// Remove the directories a deleted file leaves empty, up to `top`
+fn remove_empty_parents(file: &Path, top: &Path) -> Result<()> {
+    let mut dir = file.parent();
+    while let Some(parent) = dir {
+        if parent == top || fs::read_dir(parent)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(parent)?;
+        dir = parent.parent();
+    }
+    Ok(())
+}
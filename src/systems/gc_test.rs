@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use crate::test_support::TempRepo;
+use crate::systems::commits::commit::Commit;
+use crate::systems::checkout::switch;
+use crate::systems::branch::{create_branch, delete_branch};
+
+/// Helper leaving behind a commit only a deleted branch `feature` led to, returned with
+/// the commit `Main` points at
+fn abandoned_branch(repo: &TempRepo) -> io::Result<(Commit, Commit)> {
+    let main = repo.commit_file("a.txt", "kept\n", "Base")?;
+    create_branch(repo, "feature", None)?;
+    switch(repo, "feature", false)?;
+    let abandoned = repo.commit_file("b.txt", "only on feature\n", "Feature")?;
+    switch(repo, "Main", false)?;
+    delete_branch(repo, "feature", true)?;
+    Ok((main, abandoned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::test_support::TempRepo;
+    use crate::systems::commits::functions::load_all_commits;
+    use crate::systems::commits::versioning::Version;
+    use crate::systems::gc::{format_gc, gc, parse_grace, GcSummary, DEFAULT_GRACE};
+    use crate::systems::objects::object::{hash_object, ObjectKind};
+    
+    use crate::systems::refs::write_ref;
+    use crate::systems::status::status;
+
+    #[test]
+    fn test_gc_prunes_what_a_deleted_branch_held() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let (main, abandoned) = abandoned_branch(&repo)?;
+        let feature_blob = hash_object(ObjectKind::Blob, b"only on feature\n");
+        let kept_blob = hash_object(ObjectKind::Blob, b"kept\n");
+
+        let summary = gc(&repo, Duration::ZERO, false)?;
+        assert_eq!(summary.commits, vec![abandoned.id.clone()]);
+        assert!(summary.objects.contains(&feature_blob));
+        assert!(summary.objects.contains(&abandoned.id));
+        assert_eq!(summary.versions, vec![PathBuf::from(".grit/versions/feature/b.txt.json")]);
+        assert!(summary.reclaimed > 0);
+
+        assert!(!repo.objects().exists(&feature_blob));
+        assert!(!repo.objects().exists(&abandoned.id));
+        assert!(!load_all_commits(&repo)?.contains_key(&abandoned.id));
+        assert!(!repo.exists(".grit/versions/feature"));
+
+        // What Main holds survives, now packed
+        assert_eq!(repo.objects().loose_ids()?, Vec::<String>::new());
+        assert_eq!(repo.objects().read(&kept_blob)?.1, b"kept\n");
+        assert!(repo.objects().exists(&main.id) && repo.objects().exists(&main.tree));
+        assert!(load_all_commits(&repo)?.contains_key(&main.id));
+        assert!(status(&repo)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_changes_nothing() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let (_, abandoned) = abandoned_branch(&repo)?;
+        let loose = repo.objects().loose_ids()?;
+
+        let summary = gc(&repo, Duration::ZERO, true)?;
+        assert_eq!(summary.commits, vec![abandoned.id.clone()]);
+        assert_eq!(summary.versions.len(), 1);
+        assert!(summary.pack.is_none());
+
+        assert_eq!(repo.objects().loose_ids()?, loose);
+        assert!(load_all_commits(&repo)?.contains_key(&abandoned.id));
+        assert!(repo.exists(".grit/versions/feature/b.txt.json"));
+
+        let text = format_gc(&summary, true);
+        assert!(text.contains(&format!("Would remove commit {}\n", abandoned.id)));
+        assert!(text.contains("Would remove 1 commit, "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grace_period_keeps_recent_data() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let (_, abandoned) = abandoned_branch(&repo)?;
+
+        let summary = gc(&repo, DEFAULT_GRACE, false)?;
+        assert!(summary.commits.is_empty() && summary.objects.is_empty() && summary.versions.is_empty());
+        assert!(summary.recent > 0);
+        assert!(load_all_commits(&repo)?.contains_key(&abandoned.id));
+        assert_eq!(repo.objects().read(&hash_object(ObjectKind::Blob, b"only on feature\n"))?.1, b"only on feature\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_and_reflog_keep_commits() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let (_, abandoned) = abandoned_branch(&repo)?;
+
+        fs::create_dir_all(repo.working_path(".grit/refs/tags"))?;
+        repo.write(".grit/refs/tags/v1", format!("{}\n", abandoned.id))?;
+        assert!(gc(&repo, Duration::ZERO, false)?.commits.is_empty());
+        assert!(repo.objects().exists(&hash_object(ObjectKind::Blob, b"only on feature\n")));
+
+        fs::remove_file(repo.working_path(".grit/refs/tags/v1"))?;
+        fs::create_dir_all(repo.working_path(".grit/logs"))?;
+        repo.write(".grit/logs/HEAD", format!("{} {} Tester\tcommit: Feature\n", "0".repeat(40), abandoned.id))?;
+        assert!(gc(&repo, Duration::ZERO, false)?.commits.is_empty());
+        assert!(load_all_commits(&repo)?.contains_key(&abandoned.id));
+
+        fs::remove_file(repo.working_path(".grit/logs/HEAD"))?;
+        assert_eq!(gc(&repo, Duration::ZERO, false)?.commits, vec![abandoned.id]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_keeps_the_segments_of_version_chains() -> io::Result<()> {
+        let repo = TempRepo::new();
+        // Each change is bigger than the previous content, so each version is a keyframe
+        // and closes the segment before it
+        for content in ["a\n", "bb\n", "ccc\n"] {
+            repo.commit_file("a.txt", content, content)?;
+        }
+        let chain = Version::load(&repo, &repo.working_path(".grit/versions/Main/a.txt.json"))?;
+        assert_eq!(chain.segments.len(), 1);
+
+        gc(&repo, Duration::ZERO, false)?;
+        assert!(repo.objects().exists(&chain.segments[0]));
+        assert_eq!(chain.verify(&repo)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_refuses_branches_to_unknown_commits() -> io::Result<()> {
+        let repo = TempRepo::new();
+        abandoned_branch(&repo)?;
+        write_ref(&repo, "broken", &"ab".repeat(20))?;
+
+        assert!(gc(&repo, Duration::ZERO, false).is_err());
+        assert!(!repo.objects().loose_ids()?.is_empty(), "Nothing may be removed");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_grace_and_summary() -> io::Result<()> {
+        assert_eq!(parse_grace("now")?, Duration::ZERO);
+        assert_eq!(parse_grace("90")?, Duration::from_secs(90));
+        assert_eq!(parse_grace("30m")?, Duration::from_secs(30 * 60));
+        assert_eq!(parse_grace("2w")?, Duration::from_secs(14 * 24 * 60 * 60));
+        assert!(parse_grace("3 days").is_err());
+        assert!(parse_grace("d").is_err());
+
+        let summary = GcSummary { objects: vec!["a".into(), "b".into()], recent: 1, reclaimed: 10, ..Default::default() };
+        assert_eq!(format_gc(&summary, false), concat!(
+            "Removed 0 commits, 2 objects and 0 version files, reclaimed 10 bytes\n",
+            "Kept 1 unreachable entry newer than the grace period\n",
+        ));
+        Ok(())
+    }
+}
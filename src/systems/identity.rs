@@ -0,0 +1,97 @@
+use std::fmt;
+use crate::systems::config::{capitalize, global_config_file, process_env, Config, Env};
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// A person recorded on a commit, written `Name <email>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+}
+
+impl Signature {
+    pub fn new(name: &str, email: &str) -> Result<Signature> {
+        let (name, email) = (name.trim(), email.trim());
+        if name.is_empty() || email.is_empty() {
+            return Err(GritError::InvalidArgument("A name and an email are both needed".to_string()));
+        }
+        if let Some(c) = format!("{}{}", name, email).chars().find(|c| matches!(c, '<' | '>' | '\n' | '\r')) {
+            return Err(GritError::InvalidArgument(format!("'{}' cannot be part of a name or an email", c.escape_default())));
+        }
+        Ok(Signature { name: name.to_string(), email: email.to_string() })
+    }
+
+    /// Parses `Name <email>`, as given to `grit commit --author`
+    pub fn parse(text: &str) -> Result<Signature> {
+        let invalid = || GritError::InvalidArgument(format!("'{}' is not of the form 'Name <email>'", text));
+        let (name, rest) = text.split_once('<').ok_or_else(invalid)?;
+        let email = rest.trim_end().strip_suffix('>').ok_or_else(invalid)?;
+        Signature::new(name, email)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+/// Who wrote the changes of a commit and who recorded it. They differ when a commit is
+/// made on someone else's behalf, with `grit commit --author`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub author: Signature,
+    pub committer: Signature,
+}
+
+impl Identity {
+    /// The same person as author and committer
+    pub fn new(signature: Signature) -> Identity {
+        Identity { author: signature.clone(), committer: signature }
+    }
+
+    /// The identity configured for `repo`, with `author` replacing the configured author
+    /// when given. See `resolve_with`.
+    pub fn resolve(repo: &Repository, author: Option<Signature>) -> Result<Identity> {
+        Identity::resolve_with(repo, author, &process_env)
+    }
+
+    /// Each name and email is taken from the first of: `GRIT_AUTHOR_NAME` and
+    /// `GRIT_AUTHOR_EMAIL` (`GRIT_COMMITTER_*` for the committer), then `user.name` and
+    /// `user.email` in the configuration. With neither, the committer is the author from
+    /// the environment. Fails when either is missing rather than making one up.
+    pub fn resolve_with(repo: &Repository, author: Option<Signature>, env: Env) -> Result<Identity> {
+        let config = Config::load(Some(repo.grit_dir()), env)?;
+        let author = match author {
+            Some(author) => author,
+            None => configured(&config, "AUTHOR", env)?,
+        };
+        let committer = configured(&config, "COMMITTER", env)?;
+        Ok(Identity { author, committer })
+    }
+}
+
+/// The signature of a role, `AUTHOR` or `COMMITTER`, from the environment and config
+fn configured(config: &Config, role: &str, env: Env) -> Result<Signature> {
+    let lookup = |field: &str, key: &str| {
+        env(&format!("GRIT_{}_{}", role, field))
+            .or_else(|| config.text(key).map(str::to_string))
+            // Whoever is named only as the author also records the commit
+            .or_else(|| env(&format!("GRIT_AUTHOR_{}", field)))
+    };
+    let name = lookup("NAME", "user.name");
+    let email = lookup("EMAIL", "user.email");
+
+    let (Some(name), Some(email)) = (name, email) else {
+        let global = global_config_file(env)
+            .map(|path| format!(" ({})", path.display()))
+            .unwrap_or_default();
+        return Err(GritError::config(format!(
+            "{} identity unknown: run `grit config set --global user.name \"Your Name\"` and `grit config set --global user.email you@example.com`{}, or set GRIT_{}_NAME and GRIT_{}_EMAIL",
+            capitalize(&role.to_lowercase()), global, role, role
+        )));
+    };
+    Signature::new(&name, &email)
+        .map_err(|e| GritError::config_from(format!("Invalid {} identity", role.to_lowercase()), e))
+}
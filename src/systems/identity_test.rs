@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::error::Result;
+use crate::systems::config::{ConfigFile, Scope};
+use crate::systems::init::CONFIG_FILE;
+use crate::test_support::{tester, TempRepo};
+
+/// Helper adding settings to a config file
+fn configure(path: &Path, settings: &[(&str, &str)]) -> Result<()> {
+    let mut config = ConfigFile::load(Scope::Repository, path)?;
+    for (key, value) in settings {
+        config.set(key, value)?;
+    }
+    config.save()
+}
+
+/// Helper standing in for the process environment
+fn environment(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::error::GritError;
+    use crate::systems::identity::{Identity, Signature};
+    use crate::systems::commits::log::format_log;
+
+    #[test]
+    fn test_signature_parsing() -> Result<()> {
+        let signature = Signature::parse("Ada Lovelace <ada@example.com>")?;
+        assert_eq!(signature, Signature::new("Ada Lovelace", "ada@example.com")?);
+        assert_eq!(signature.to_string(), "Ada Lovelace <ada@example.com>");
+
+        for text in ["Ada", "Ada <ada@example.com", "<ada@example.com>", "Ada <>", "Ada <a<b>"] {
+            assert!(matches!(Signature::parse(text), Err(GritError::InvalidArgument(_))), "{}", text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity_from_repository_then_global_config() -> Result<()> {
+        let repo = TempRepo::new();
+        let home = TempDir::new()?;
+        let env = environment(&[("HOME", &home.path().display().to_string())]);
+        let env = |name: &str| env.get(name).cloned();
+
+        configure(&home.path().join(".config/grit/config"), &[("user.name", "Global"), ("user.email", "global@example.com")])?;
+        let identity = Identity::resolve_with(&repo, None, &env)?;
+        assert_eq!(identity.author.to_string(), "Global <global@example.com>");
+        assert_eq!(identity.committer, identity.author);
+
+        // The repository's config wins, one setting at a time
+        configure(&repo.grit_path(CONFIG_FILE), &[("user.name", "Local")])?;
+        let identity = Identity::resolve_with(&repo, None, &env)?;
+        assert_eq!(identity.author.to_string(), "Local <global@example.com>");
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment_and_author_override() -> Result<()> {
+        let repo = TempRepo::new();
+        configure(&repo.grit_path(CONFIG_FILE), &[("user.name", "Configured"), ("user.email", "me@example.com")])?;
+        let env = environment(&[("GRIT_AUTHOR_NAME", "From Env"), ("GRIT_COMMITTER_EMAIL", "ci@example.com")]);
+        let env = |name: &str| env.get(name).cloned();
+
+        let identity = Identity::resolve_with(&repo, None, &env)?;
+        assert_eq!(identity.author.to_string(), "From Env <me@example.com>");
+        assert_eq!(identity.committer.to_string(), "Configured <ci@example.com>");
+
+        let guest = Signature::parse("Guest <guest@example.com>")?;
+        let identity = Identity::resolve_with(&repo, Some(guest.clone()), &env)?;
+        assert_eq!(identity.author, guest);
+        assert_eq!(identity.committer.to_string(), "Configured <ci@example.com>");
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuses_a_missing_identity() -> Result<()> {
+        let repo = TempRepo::new();
+        let nothing = |_: &str| None;
+        let error = Identity::resolve_with(&repo, None, &nothing).unwrap_err();
+        assert!(matches!(&error, GritError::InvalidConfig { .. }), "{:?}", error);
+        assert!(error.to_string().contains("user.name"), "{}", error);
+
+        // An author given on the command line still needs a configured committer
+        let guest = Signature::parse("Guest <guest@example.com>")?;
+        configure(&repo.grit_path(CONFIG_FILE), &[("user.name", "Only A Name")])?;
+        assert!(Identity::resolve_with(&repo, Some(guest), &nothing).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_author_environment_names_the_committer_without_config() -> Result<()> {
+        let repo = TempRepo::new();
+        let env = environment(&[("GRIT_AUTHOR_NAME", "From Env"), ("GRIT_AUTHOR_EMAIL", "env@example.com")]);
+        let env = |name: &str| env.get(name).cloned();
+
+        let identity = Identity::resolve_with(&repo, None, &env)?;
+        assert_eq!(identity.committer.to_string(), "From Env <env@example.com>");
+        assert_eq!(identity.committer, identity.author);
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_records_author_and_committer() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.write("notes.txt", "notes\n")?;
+        repo.add(None)?;
+
+        let committer = Signature::new("Maintainer", "maintainer@example.com")?;
+        let identity = Identity { author: Signature::new("Contributor", "contributor@example.com")?, committer };
+        let commit = repo.commit("Contributed notes", &identity)?.expect("Something to commit");
+        assert_eq!(commit.author, "Contributor <contributor@example.com>");
+        assert_eq!(commit.committer, "Maintainer <maintainer@example.com>");
+        assert!(commit.verify());
+
+        let log = format_log(&repo, false)?;
+        assert!(log.contains("Author: Contributor <contributor@example.com>\nCommit: Maintainer <maintainer@example.com>\n"), "{}", log);
+        Ok(())
+    }
+
+    #[test]
+    fn test_committer_is_part_of_the_id() -> Result<()> {
+        let repo = TempRepo::new();
+        repo.write("notes.txt", "notes\n")?;
+        repo.add(None)?;
+        let mut commit = repo.commit("Notes", &tester())?.expect("Something to commit");
+
+        commit.committer = "Someone Else <else@example.com>".to_string();
+        assert!(!commit.verify());
+        // Commits from before committers were recorded keep their ids
+        commit.committer.clear();
+        assert!(!String::from_utf8_lossy(&commit.encode()).contains("committer"));
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+use crate::structure::serialization::{load, save};
+use crate::systems::checkout::{remove_file as remove_working_file, working_hash};
+use crate::systems::commits::functions::head_snapshot;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Stops tracking a file, or every file below a directory, leaving the working
+/// directory untouched. Returns the paths removed from the staging tree.
+pub fn remove_cached(repo: &Repository, path: &str) -> Result<Vec<PathBuf>> {
+    let prefix = repo.relative_path(path)?;
+    let mut tree = load(repo)?;
+
+    let removed: Vec<PathBuf> = tree.list_files().into_iter()
+        .filter(|file| matches_prefix(file, &prefix))
+        .collect();
+    if removed.is_empty() {
+        return Err(GritError::MissingRef(format!("Pathspec '{}' did not match any staged file", path)));
+    }
+
+    for file in &removed {
+        tree.remove(file);
+    }
+    save(repo, &tree)?;
+
+    Ok(sorted(removed))
+}
+
+/// Stops tracking a file, or every file below a directory, and deletes it from the working
+/// directory. Unless `force` is set, files whose working copy differs from the staged
+/// content are left alone and reported as an error. Returns the removed paths.
+pub fn remove(repo: &Repository, path: &str, force: bool) -> Result<Vec<PathBuf>> {
+    let root = repo.root();
+    let prefix = repo.relative_path(path)?;
+    let mut tree = load(repo)?;
+
+    let matching: Vec<(PathBuf, String)> = tree.snapshot().into_iter()
+        .filter(|(file, _)| matches_prefix(file, &prefix))
+        .collect();
+    if matching.is_empty() {
+        return Err(GritError::MissingRef(format!("Pathspec '{}' did not match any staged file", path)));
+    }
+
+    if !force {
+        let changed: Vec<String> = matching.iter()
+            .filter(|(file, hash)| working_hash(root, file).is_some_and(|on_disk| &on_disk != hash))
+            .map(|(file, _)| format!("\t{}", file.display()))
+            .collect();
+        if !changed.is_empty() {
+            return Err(GritError::DirtyWorkingTree(format!(
+                "The following files have local modifications:\n{}\nUse --cached to keep them, or -f to remove them anyway.",
+                changed.join("\n")
+            )));
+        }
+    }
+
+    let mut removed = Vec::new();
+    for (file, _) in matching {
+        tree.remove(&file);
+        remove_working_file(root, &file)?;
+        removed.push(file);
+    }
+    save(repo, &tree)?;
+
+    Ok(sorted(removed))
+}
+
+/// Unstages changes to a file or directory: entries go back to their content at HEAD,
+/// and files HEAD does not know are no longer tracked. Returns the paths reset.
+pub fn reset(repo: &Repository, path: &str) -> Result<Vec<PathBuf>> {
+    let prefix = repo.relative_path(path)?;
+    let head = head_snapshot(repo, &repo.objects())?;
+    let mut tree = load(repo)?;
+    let staged = tree.snapshot();
+
+    let mut reset = Vec::new();
+    for (file, hash) in staged.iter().filter(|(file, _)| matches_prefix(file, &prefix)) {
+        match head.get(file) {
+            Some(committed) if committed == hash => {}
+            Some(committed) => {
+                tree.set(file, committed.clone())?;
+                reset.push(file.clone());
+            }
+            None => {
+                tree.remove(file);
+                reset.push(file.clone());
+            }
+        }
+    }
+    // Files removed from the staging tree since HEAD are tracked again
+    for (file, committed) in head.iter().filter(|(file, _)| matches_prefix(file, &prefix)) {
+        if !staged.contains_key(file) {
+            tree.set(file, committed.clone())?;
+            reset.push(file.clone());
+        }
+    }
+
+    let known = staged.keys().chain(head.keys()).any(|file| matches_prefix(file, &prefix));
+    if !known {
+        return Err(GritError::MissingRef(format!("Pathspec '{}' did not match any tracked file", path)));
+    }
+
+    save(repo, &tree)?;
+    Ok(sorted(reset))
+}
+
+/// An empty prefix, the repository root, matches everything
+fn matches_prefix(file: &Path, prefix: &Path) -> bool {
+    prefix.as_os_str().is_empty() || file.starts_with(prefix)
+}
+
+fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort();
+    paths
+}
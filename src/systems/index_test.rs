@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::systems::add::add;
+use crate::test_support::{tester, TempRepo};
+use crate::systems::commits::commit::Commit;
+use crate::structure::serialization::load;
+
+/// Helper that lists the staged files, sorted
+fn staged_files(repo: &TempRepo) -> io::Result<Vec<PathBuf>> {
+    Ok(load(repo)?.snapshot().into_keys().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+    use crate::systems::index::{remove, remove_cached, reset};
+    use crate::systems::status::status;
+    use crate::systems::objects::tree::Change;
+
+    #[test]
+    fn test_remove_cached_keeps_the_working_file() -> io::Result<()> {
+        let repo = TempRepo::new();
+        fs::create_dir(repo.working_path("dir"))?;
+        repo.write("dir/one.txt", "1\n")?;
+        repo.write("dir/two.txt", "2\n")?;
+        repo.write("kept.txt", "kept\n")?;
+        add(&repo, None)?;
+
+        assert_eq!(remove_cached(&repo, "dir")?, vec![PathBuf::from("dir/one.txt"), PathBuf::from("dir/two.txt")]);
+        assert_eq!(staged_files(&repo)?, vec![PathBuf::from("kept.txt")]);
+        assert!(repo.exists("dir/one.txt"), "The working copy must stay on disk");
+        assert_eq!(status(&repo)?.untracked, vec![PathBuf::from("dir/")]);
+
+        assert!(remove_cached(&repo, "missing.txt").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_restores_head_entries() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("committed.txt", "v1\n")?;
+        add(&repo, None)?;
+        Commit::new(&repo, "First", &tester())?;
+
+        repo.write("committed.txt", "v2\n")?;
+        repo.write("new.txt", "new\n")?;
+        add(&repo, None)?;
+        assert_eq!(status(&repo)?.staged.len(), 2);
+
+        assert_eq!(reset(&repo, ".")?, vec![PathBuf::from("committed.txt"), PathBuf::from("new.txt")]);
+
+        let status = status(&repo)?;
+        assert!(status.staged.is_empty(), "Nothing should remain staged: {:?}", status.staged);
+        assert_eq!(status.unstaged, vec![(PathBuf::from("committed.txt"), Change::Modified)]);
+        assert_eq!(status.untracked, vec![PathBuf::from("new.txt")]);
+        assert_eq!(repo.read("committed.txt")?, "v2\n", "Reset must not touch the working directory");
+
+        // Entries dropped from the staging tree come back as well
+        remove_cached(&repo, "committed.txt")?;
+        assert_eq!(reset(&repo, "committed.txt")?, vec![PathBuf::from("committed.txt")]);
+        assert_eq!(staged_files(&repo)?, vec![PathBuf::from("committed.txt")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_deletes_the_working_file() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("clean.txt", "clean\n")?;
+        repo.write("edited.txt", "v1\n")?;
+        add(&repo, None)?;
+        Commit::new(&repo, "First", &tester())?;
+
+        assert_eq!(remove(&repo, "clean.txt", false)?, vec![PathBuf::from("clean.txt")]);
+        assert!(!repo.exists("clean.txt"));
+
+        repo.write("edited.txt", "v2\n")?;
+        assert!(remove(&repo, "edited.txt", false).is_err(), "Local modifications must not be lost silently");
+        assert!(repo.exists("edited.txt"));
+        remove(&repo, "edited.txt", true)?;
+        assert!(!repo.exists("edited.txt"));
+
+        let commit = Commit::new(&repo, "Remove both", &tester())?.expect("Deletions are a change");
+        let mut deleted = commit.deleted.clone();
+        deleted.sort();
+        assert_eq!(deleted, vec!["clean.txt", "edited.txt"]);
+        Ok(())
+    }
+}
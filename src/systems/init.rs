@@ -1,29 +1,34 @@
 use std::fs;
-use std::io::{self, Write, Read};
 use std::path::{Path, PathBuf};
+use crate::systems::config::{edit_config, process_env, ConfigFile, Scope, Value};
+use crate::systems::refs::{current_branch, set_head_branch, HEADS_DIR};
+use crate::systems::repository::Repository;
+use crate::error::Result;
 
 pub const GRIT_DIR: &str = ".grit";
 pub const CONFIG_FILE: &str = "config";
+pub const DEFAULT_BRANCH: &str = "Main";
 
-/// Initialize a new grit repository
-pub fn init_grit() -> io::Result<()> {
-    let current_dir = std::env::current_dir()?;
-    let grit_path = current_dir.join(GRIT_DIR);
+/// Initialize a new grit repository in `root`, or record where an existing one now lives
+pub fn init_grit(root: &Path) -> Result<Repository> {
+    let grit_path = root.join(GRIT_DIR);
 
     if grit_path.exists() {
-        println!("Already a grit repository.");
-        update_grit_root(&current_dir, "Main")?;
-        return Ok(());
+        update_grit_root(root)?;
+        return Repository::open(root);
     }
 
     // Create .grit directory
     fs::create_dir(&grit_path)?;
 
-    // Write the absolute path and branch to .grit/config
-    update_grit_root(&current_dir, "Main")?;
+    // Record the absolute path in .grit/config
+    update_grit_root(root)?;
 
-    println!("Initialized empty grit repository in {}", grit_path.display());
-    Ok(())
+    // HEAD starts on the default branch, whose ref is written by the first commit
+    let repo = Repository::open(root)?;
+    fs::create_dir_all(repo.grit_path(HEADS_DIR))?;
+    set_head_branch(&repo, DEFAULT_BRANCH)?;
+    Ok(repo)
 }
 
 /// Find the root directory of the grit repository
@@ -41,28 +46,15 @@ pub fn find_grit_root(start_path: &Path) -> Option<PathBuf> {
     None
 }
 
-/// Check if the current directory is inside a grit repository
-pub fn is_grit_repo() -> bool {
-    find_grit_root(&std::env::current_dir().unwrap()).is_some()
-}
-
-pub fn update_branch(branch: &str) -> io::Result<()> {
-    let current_dir = std::env::current_dir()?;
-    let grit_path = current_dir.join(GRIT_DIR);
-
-    if grit_path.exists() {
-        update_grit_root(&current_dir, branch)?;
-        println!("Branch set to {}", branch);
-        return Ok(());
-    }
-
-    Ok(())
+/// Check if a directory is inside a grit repository
+pub fn is_grit_repo(path: &Path) -> bool {
+    find_grit_root(path).is_some()
 }
 
-/// Update the `.grit/config` file with the current directory and branch
-pub fn update_grit_root(current_dir: &Path, branch: &str) -> io::Result<()> {
+/// Record the current directory as `core.path` in `.grit/config`, keeping other settings.
+/// A config file still in the legacy `key=value` format is rewritten in TOML.
+pub fn update_grit_root(current_dir: &Path) -> Result<()> {
     let grit_path = current_dir.join(GRIT_DIR);
-    let config_path = grit_path.join(CONFIG_FILE);
 
     // Ensure the .grit directory exists
     if !grit_path.exists() {
@@ -70,52 +62,53 @@ pub fn update_grit_root(current_dir: &Path, branch: &str) -> io::Result<()> {
     }
 
     let new_path = normalize_path(&current_dir.canonicalize()?).display().to_string();
-
-    // Read existing config
-    let mut old_content = String::new();
-    if let Ok(mut file) = fs::File::open(&config_path) {
-        file.read_to_string(&mut old_content)?;
-    }
-
-    let mut old_branch = "Main".to_string();
-    let mut old_path = String::new();
-
-    for line in old_content.lines() {
-        if line.starts_with("path=") {
-            old_path = line.replace("path=", "").trim().to_string();
-        } else if line.starts_with("branch=") {
-            old_branch = line.replace("branch=", "").trim().to_string();
-        }
-    }
+    let repo = Repository::open(current_dir)?;
+    let config = ConfigFile::load(Scope::Repository, repo.grit_path(CONFIG_FILE))?;
 
     // Only update if there are changes
-    if old_path != new_path || old_branch != branch {
-        let mut file = fs::File::create(&config_path)?;
-        writeln!(file, "path={}", new_path)?;
-        writeln!(file, "branch={}", branch)?;
-        println!("Updated grit repository path to {}", new_path);
-        println!("Set branch to {}", branch);
+    if config.get("core.path").and_then(Value::as_text) != Some(new_path.as_str()) || config.is_legacy() {
+        edit_config(Some(&repo), Scope::Repository, &process_env, |config| config.set("core.path", &new_path))?;
     }
 
     Ok(())
 }
 
-/// Read the current branch from `.grit/config`
-pub fn get_current_branch() -> io::Result<String> {
-    let current_dir = std::env::current_dir()?;
-    let grit_root = find_grit_root(&current_dir).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Not a grit repository"))?;
-    let config_path = grit_root.join(GRIT_DIR).join(CONFIG_FILE);
+/// Name of the checked out branch, or `HEAD` when no branch is checked out
+pub fn get_current_branch(repo: &Repository) -> Result<String> {
+    Ok(current_branch(repo)?.unwrap_or_else(|| "HEAD".to_string()))
+}
 
-    let mut content = String::new();
-    fs::File::open(&config_path)?.read_to_string(&mut content)?;
+/// The `branch=` line older versions kept in `.grit/config`. The branch now lives in
+/// HEAD, see `refs::migrate_legacy_head`.
+pub fn legacy_branch(repo: &Repository) -> Result<Option<String>> {
+    Ok(legacy_config(repo)?.and_then(|mut config| config.take_legacy_branch()))
+}
 
-    for line in content.lines() {
-        if line.starts_with("branch=") {
-            return Ok(line.replace("branch=", "").trim().to_string());
-        }
+/// Remove the `branch=` line older versions kept in `.grit/config`, returning its value
+pub fn take_legacy_branch(repo: &Repository) -> Result<Option<String>> {
+    let Some(mut config) = legacy_config(repo)? else {
+        return Ok(None);
+    };
+    let branch = config.take_legacy_branch();
+    if branch.is_some() {
+        config.save()?;
     }
+    Ok(branch)
+}
 
-    Err(io::Error::new(io::ErrorKind::InvalidData, "Branch not found in config"))
+/// The repository's config file, when it may still hold a legacy branch
+fn legacy_config(repo: &Repository) -> Result<Option<ConfigFile>> {
+    let config_path = repo.grit_path(CONFIG_FILE);
+    if !config_path.is_file() {
+        return Ok(None);
+    }
+
+    // HEAD is read all the time, only parse the config when it may hold a branch
+    let content = fs::read_to_string(&config_path)?;
+    if !content.lines().any(|line| line.starts_with("branch=")) {
+        return Ok(None);
+    }
+    Ok(Some(ConfigFile::load(Scope::Repository, config_path)?))
 }
 
 /// Normalize a path by removing redundant components and resolving `.` and `. .`
@@ -126,8 +119,8 @@ pub fn normalize_path(path: &Path) -> PathBuf {
             std::path::Component::Prefix(prefix) => {
                 // On Windows, strip the `\\?\` prefix if present
                 let prefix_str = prefix.as_os_str().to_string_lossy();
-                if prefix_str.starts_with(r"\\?\") {
-                    normalized.push(&prefix_str[4..]);
+                if let Some(stripped) = prefix_str.strip_prefix(r"\\?\") {
+                    normalized.push(stripped);
                 } else {
                     normalized.push(prefix.as_os_str());
                 }
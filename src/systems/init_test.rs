@@ -9,20 +9,27 @@ use super::init::{
 mod init_tests {
     use std::fs;
     use super::*;
-    use std::io::{Read};
+        use std::io::{Read};
     use tempfile::tempdir;
     use std::path::{Path, PathBuf};
 
+    /// Extract the repository path from the `core.path` setting of a config file
+    fn config_root(contents: &str) -> String {
+        let config: toml::Table = contents.parse().expect("Config should be TOML");
+        config["core"]["path"].as_str()
+            .expect("Config should record the repository path")
+            .to_string()
+    }
+
     #[test]
     fn test_init_grit_creates_grit_directory() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
         let grit_path = temp_dir.path().join(GRIT_DIR);
 
         assert!(!grit_path.exists(), "Grit directory should not exist before initialization");
 
-        init_grit().unwrap();
+        init_grit(temp_dir.path()).unwrap();
 
         assert!(grit_path.exists(), "Grit directory should be created after initialization");
         assert!(grit_path.is_dir(), ".grit should be a directory");
@@ -31,9 +38,8 @@ mod init_tests {
     #[test]
     fn test_init_grit_creates_config_file_with_correct_path() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
-        init_grit().unwrap();
+        init_grit(temp_dir.path()).unwrap();
 
         let config_path = temp_dir.path().join(GRIT_DIR).join(CONFIG_FILE);
 
@@ -44,7 +50,7 @@ mod init_tests {
         file.read_to_string(&mut contents).unwrap();
 
         let expected_path = temp_dir.path().canonicalize().unwrap();
-        let actual_path = PathBuf::from(contents.trim()).canonicalize().unwrap();
+        let actual_path = PathBuf::from(config_root(&contents)).canonicalize().unwrap();
 
         assert_eq!(actual_path, expected_path, "Config file path mismatch");
     }
@@ -52,9 +58,8 @@ mod init_tests {
     #[test]
     fn test_find_grit_root_returns_correct_path() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
-        init_grit().unwrap();
+        init_grit(temp_dir.path()).unwrap();
         let grit_root = find_grit_root(temp_dir.path()).unwrap();
         let dir = normalize_path(temp_dir.path());
         assert_eq!(
@@ -66,7 +71,6 @@ mod init_tests {
     #[test]
     fn test_find_grit_root_returns_none_if_not_in_repo() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
         assert!(find_grit_root(temp_dir.path()).is_none(), "Should return None if not inside a grit repo");
     }
@@ -74,21 +78,19 @@ mod init_tests {
     #[test]
     fn test_is_grit_repo_detects_repo_correctly() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
-        assert!(!is_grit_repo(), "Should return false before initializing");
+        assert!(!is_grit_repo(temp_dir.path()), "Should return false before initializing");
 
-        init_grit().unwrap();
+        init_grit(temp_dir.path()).unwrap();
 
-        assert!(is_grit_repo(), "Should return true after initializing");
+        assert!(is_grit_repo(temp_dir.path()), "Should return true after initializing");
     }
 
     #[test]
     fn test_update_grit_root_updates_config_path() {
         let temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
 
-        init_grit().unwrap();
+        init_grit(temp_dir.path()).unwrap();
 
         let config_path = temp_dir.path().join(GRIT_DIR).join(CONFIG_FILE);
         let mut file = fs::File::open(&config_path).unwrap();
@@ -98,7 +100,6 @@ mod init_tests {
 
         // Simulate moving the project
         let new_temp_dir = tempdir().unwrap();
-        std::env::set_current_dir(&new_temp_dir).unwrap();
         let new_grit_path = new_temp_dir.path().join(GRIT_DIR);
         fs::rename(temp_dir.path().join(GRIT_DIR), &new_grit_path).unwrap();
 
@@ -115,7 +116,7 @@ mod init_tests {
             "Config file should be updated with new path"
         );
         let expected_path = new_temp_dir.path().canonicalize().unwrap();
-        let actual_path = Path::new(new_contents.trim()).canonicalize().unwrap();
+        let actual_path = Path::new(&config_root(&new_contents)).canonicalize().unwrap();
 
         assert_eq!(
             actual_path, expected_path,
@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use crate::algorithms::vcompare::merge::{merge3, ConflictStyle, MergeLabels, MergeResult};
+use crate::structure::node::Unmerged;
+use crate::structure::serialization::{load, save};
+use crate::structure::ptree::ProjectTree;
+use crate::systems::atomic::write_atomic;
+use crate::systems::checkout::{remove_file, update_working_tree, working_hash, write_blob};
+use crate::systems::commits::commit::Commit;
+use crate::systems::identity::Identity;
+use crate::systems::commits::functions::{get_head_commit, head_snapshot, merge_base, resolve_commit};
+use crate::systems::filters::attributes::Attributes;
+use crate::systems::objects::object::ObjectKind;
+use crate::systems::refs::{read_ref, update_head};
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Commit being merged while conflicts wait to be resolved
+const MERGE_HEAD_FILE: &str = "MERGE_HEAD";
+/// Message of the merge commit `grit merge --continue` will create
+const MERGE_MSG_FILE: &str = "MERGE_MSG";
+
+/// What `grit merge` did.
+#[derive(Debug, Clone)]
+pub enum MergeOutcome {
+    /// Everything the branch has is already part of HEAD
+    UpToDate,
+    /// HEAD had no changes of its own and was moved to this commit
+    FastForward(Commit),
+    /// Both histories were joined by a new merge commit
+    Merged(Commit),
+    /// Files left with conflict markers; the merge is finished by `--continue`
+    Conflicts(Vec<(PathBuf, Unmerged)>),
+}
+
+/// What the merge does to a file that differs from our side.
+struct FileMerge {
+    path: PathBuf,
+    /// Content the working file gets, `None` to delete it
+    content: Option<Vec<u8>>,
+    /// The three versions, when they could not be combined
+    unmerged: Option<Unmerged>,
+}
+
+/// Merges the history of `rev`, a branch or any commit, into HEAD.
+///
+/// When HEAD is an ancestor of `rev` it simply moves forward. Otherwise every file both
+/// sides changed since their merge base goes through a three-way merge, and the result is
+/// committed with both commits as parents. Files that cannot be merged are written with
+/// conflict markers and recorded as unmerged, and no commit is made until they are added
+/// and the merge is continued.
+///
+/// `identity` is only asked for once a merge commit is made, so merges that move nothing
+/// or only move forward work without one.
+pub fn merge(repo: &Repository, rev: &str, identity: impl FnOnce() -> Result<Identity>) -> Result<MergeOutcome> {
+    if merge_head(repo)?.is_some() {
+        return Err(GritError::Conflict("A merge is already in progress. Use --continue to finish it or --abort to cancel it.".to_string()));
+    }
+
+    let theirs = resolve_commit(repo, rev)?;
+    let head = get_head_commit(repo)?;
+    let base = match &head {
+        Some(head) => merge_base(repo, head, &theirs.id)?,
+        None => None,
+    };
+
+    if base.as_ref() == Some(&theirs.id) {
+        return Ok(MergeOutcome::UpToDate);
+    }
+
+    // Fast-forwarding would drop the staged changes, a real merge would mix them in
+    let root = repo.root();
+    let store = repo.objects();
+    let ours = head_snapshot(repo, &store)?;
+    let mut tree = load(repo)?;
+    if tree.snapshot() != ours {
+        return Err(GritError::DirtyWorkingTree("Your staged changes would be mixed with the merge. Commit them first.".to_string()));
+    }
+
+    if head.is_none() || base == head {
+        update_working_tree(repo, &theirs, false)?;
+        update_head(repo, &theirs.id)?;
+        return Ok(MergeOutcome::FastForward(theirs));
+    }
+
+    // Unrelated histories are merged as if both sides had added all their files
+    let base_files = match &base {
+        Some(base) => resolve_commit(repo, base)?.snapshot(&store)?,
+        None => Default::default(),
+    };
+    let their_files = theirs.snapshot(&store)?;
+    let read = |hash: Option<&String>| -> Result<Vec<u8>> {
+        Ok(match hash {
+            Some(hash) => store.read(hash)?.1,
+            None => Vec::new(),
+        })
+    };
+    let labels = MergeLabels { ours: "HEAD", base: "merge base", theirs: rev };
+    let attributes = Attributes::load(root)?;
+
+    let paths: BTreeSet<&PathBuf> = base_files.keys().chain(ours.keys()).chain(their_files.keys()).collect();
+    let mut merges = Vec::new();
+    for path in paths {
+        let (base_hash, our_hash, their_hash) = (base_files.get(path), ours.get(path), their_files.get(path));
+        if our_hash == their_hash || base_hash == their_hash {
+            continue;
+        }
+        let stages = || Unmerged { base: base_hash.cloned(), ours: our_hash.cloned(), theirs: their_hash.cloned() };
+
+        let file_merge = if base_hash == our_hash {
+            let content = match their_hash {
+                Some(_) => Some(read(their_hash)?),
+                None => None,
+            };
+            FileMerge { path: path.clone(), content, unmerged: None }
+        } else if our_hash.is_some() && their_hash.is_some() {
+            let (base_content, our_content, their_content) = (read(base_hash)?, read(our_hash)?, read(their_hash)?);
+            // Binary files cannot be merged line by line, ours stays until the conflict is resolved
+            if [&base_content, &our_content, &their_content].iter().any(|content| attributes.is_binary(path, content)) {
+                merges.push(FileMerge { path: path.clone(), content: Some(our_content), unmerged: Some(stages()) });
+                continue;
+            }
+            let text = |content: &[u8]| String::from_utf8_lossy(content).into_owned();
+            let result = merge3(&text(&base_content), &text(&our_content), &text(&their_content), &labels, ConflictStyle::Merge);
+            let unmerged = if result.is_clean() { None } else { Some(stages()) };
+            FileMerge { path: path.clone(), content: Some(result.text.into_bytes()), unmerged }
+        } else {
+            // One side deleted what the other modified: the modified version stays on disk
+            FileMerge { path: path.clone(), content: Some(read(our_hash.or(their_hash))?), unmerged: Some(stages()) }
+        };
+        merges.push(file_merge);
+    }
+
+    // Like checkout, never overwrite changes that were not committed
+    let overwritten: Vec<String> = merges.iter()
+        .filter(|merge| working_hash(root, &merge.path).as_ref() != ours.get(&merge.path))
+        .map(|merge| format!("\t{}", merge.path.display()))
+        .collect();
+    if !overwritten.is_empty() {
+        return Err(GritError::DirtyWorkingTree(format!(
+            "Your local changes to the following files would be overwritten by merge:\n{}\nCommit them or restore them before merging.",
+            overwritten.join("\n")
+        )));
+    }
+
+    // A clean merge is committed right away, so a missing identity must stop it before
+    // anything is written
+    let clean = merges.iter().all(|merge| merge.unmerged.is_none());
+    let identity = if clean { Some(identity()?) } else { None };
+
+    let mut conflicts = Vec::new();
+    for merge in merges {
+        match &merge.content {
+            Some(content) => {
+                write_working_file(root, &merge.path, content)?;
+                if merge.unmerged.is_none() {
+                    tree.set(&merge.path, store.write(ObjectKind::Blob, content)?)?;
+                }
+            }
+            None => {
+                remove_file(root, &merge.path)?;
+                tree.remove(&merge.path);
+            }
+        }
+        if let Some(stages) = merge.unmerged {
+            tree.set_unmerged(&merge.path, stages.clone());
+            conflicts.push((merge.path, stages));
+        }
+    }
+    save(repo, &tree)?;
+
+    let message = if read_ref(repo, rev)?.is_some() {
+        format!("Merge branch '{}'", rev)
+    } else {
+        format!("Merge commit '{}'", theirs.id)
+    };
+    write_atomic(repo.grit_path(MERGE_HEAD_FILE), format!("{}\n", theirs.id))?;
+    write_atomic(repo.grit_path(MERGE_MSG_FILE), &message)?;
+
+    let Some(identity) = identity else {
+        return Ok(MergeOutcome::Conflicts(conflicts));
+    };
+    let commit = Commit::new(repo, &message, &identity)?
+        .ok_or_else(|| GritError::Conflict("The merge produced no commit".to_string()))?;
+    Ok(MergeOutcome::Merged(commit))
+}
+
+/// Creates the merge commit once every conflicted file has been resolved and added
+pub fn continue_merge(repo: &Repository, identity: &Identity) -> Result<Commit> {
+    if merge_head(repo)?.is_none() {
+        return Err(GritError::MissingRef("There is no merge in progress".to_string()));
+    }
+    let message = fs::read_to_string(repo.grit_path(MERGE_MSG_FILE)).unwrap_or_else(|_| "Merge".to_string());
+    Commit::new(repo, &message, identity)?
+        .ok_or_else(|| GritError::Conflict("The merge produced no commit".to_string()))
+}
+
+/// Cancels a merge waiting for conflicts to be resolved: every file the merge changed or
+/// left conflicted goes back to HEAD, files it brought in are deleted, and the staging tree
+/// is reset. Other local changes are kept.
+pub fn abort_merge(repo: &Repository) -> Result<()> {
+    if merge_head(repo)?.is_none() {
+        return Err(GritError::MissingRef("There is no merge in progress".to_string()));
+    }
+    let root = repo.root();
+    let store = repo.objects();
+    let head = head_snapshot(repo, &store)?;
+    let tree = load(repo)?;
+    let index = tree.snapshot();
+
+    let touched: BTreeSet<&PathBuf> = index.keys().chain(head.keys())
+        .filter(|path| index.get(*path) != head.get(*path))
+        .chain(tree.unmerged().keys())
+        .collect();
+    for path in touched {
+        match head.get(path) {
+            Some(hash) if working_hash(root, path).as_ref() != Some(hash) => write_blob(&store, root, path, hash)?,
+            Some(_) => {}
+            None => remove_file(root, path)?,
+        }
+    }
+
+    save(repo, &ProjectTree::from_snapshot(root, &head)?)?;
+    clear_merge_state(repo)
+}
+
+/// The commit being merged into HEAD, while a merge waits to be continued
+pub fn merge_head(repo: &Repository) -> Result<Option<String>> {
+    let merge_head_file = repo.grit_path(MERGE_HEAD_FILE);
+    if !merge_head_file.is_file() {
+        return Ok(None);
+    }
+    let id = fs::read_to_string(merge_head_file)?.trim().to_string();
+    Ok(if id.is_empty() { None } else { Some(id) })
+}
+
+/// Forget the merge in progress, once committed or aborted
+pub(crate) fn clear_merge_state(repo: &Repository) -> Result<()> {
+    for file in [MERGE_HEAD_FILE, MERGE_MSG_FILE].map(|name| repo.grit_path(name)) {
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_working_file(root: &Path, rel_path: &Path, content: &[u8]) -> Result<()> {
+    let abs_path = root.join(rel_path);
+    if let Some(parent) = abs_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(abs_path, content)?;
+    Ok(())
+}
+
+/// Three-way merge of files outside the repository, the changes from `base` to `theirs`
+/// being applied onto `ours`. The result replaces `ours` unless `to_stdout` is set.
+pub fn merge_file(ours: &str, base: &str, theirs: &str, style: ConflictStyle, to_stdout: bool) -> Result<MergeResult> {
+    let read = |path: &str| -> Result<String> {
+        Ok(fs::read(path).map(|content| String::from_utf8_lossy(&content).into_owned())?)
+    };
+    let labels = MergeLabels { ours, base, theirs };
+    let result = merge3(&read(base)?, &read(ours)?, &read(theirs)?, &labels, style);
+
+    if to_stdout {
+        print!("{}", result.text);
+    } else {
+        fs::write(ours, &result.text)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,248 @@
+use std::fs;
+use std::io;
+use crate::systems::add::add;
+use crate::test_support::{tester, TempRepo};
+use crate::systems::commits::commit::Commit;
+use crate::systems::checkout::switch;
+use crate::systems::branch::create_branch;
+
+/// Helper creating a history where `feature` and `Main` both changed `shared.txt`
+/// after branching from a common commit, returned first
+fn diverged(repo: &TempRepo, main_line: &str, feature_line: &str) -> io::Result<Commit> {
+    let base = repo.commit_file("shared.txt", "one\ntwo\nthree\nfour\nfive\n", "Base")?;
+    create_branch(repo, "feature", None)?;
+
+    switch(repo, "feature", false)?;
+    repo.commit_file("shared.txt", &format!("one\n{}\nthree\nfour\nfive\n", feature_line), "Feature")?;
+    switch(repo, "Main", false)?;
+    repo.commit_file("shared.txt", &format!("one\ntwo\nthree\n{}\nfive\n", main_line), "Main")?;
+    Ok(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::test_support::TempRepo;
+    use crate::error::GritError;
+    use crate::systems::commits::functions::{get_head_commit, merge_base};
+    use crate::systems::merge::{abort_merge, continue_merge, merge, merge_head, MergeOutcome};
+    use crate::systems::refs::read_ref;
+    use crate::systems::status::{format_porcelain, status};
+
+    #[test]
+    fn test_merge_base_of_diverged_branches() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let base = diverged(&repo, "FOUR", "TWO")?;
+        let main = read_ref(&repo, "Main")?.unwrap();
+        let feature = read_ref(&repo, "feature")?.unwrap();
+
+        assert_eq!(merge_base(&repo, &main, &feature)?, Some(base.id.clone()));
+        assert_eq!(merge_base(&repo, &base.id, &feature)?, Some(base.id.clone()));
+        assert_eq!(merge_base(&repo, &main, &main)?, Some(main.clone()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_forward_and_up_to_date() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("a.txt", "a\n", "First")?;
+        create_branch(&repo, "feature", None)?;
+        switch(&repo, "feature", false)?;
+        let ahead = repo.commit_file("b.txt", "b\n", "Second")?;
+        switch(&repo, "Main", false)?;
+
+        // Nothing is committed, so no identity is needed
+        let no_identity = || Err(GritError::config("No identity configured"));
+        match merge(&repo, "feature", no_identity)? {
+            MergeOutcome::FastForward(commit) => assert_eq!(commit.id, ahead.id),
+            other => panic!("Expected a fast-forward, got {:?}", other),
+        }
+        assert_eq!(read_ref(&repo, "Main")?, Some(ahead.id.clone()));
+        assert_eq!(repo.read("b.txt")?, "b\n");
+        assert!(status(&repo)?.is_clean());
+
+        assert!(matches!(merge(&repo, "feature", no_identity)?, MergeOutcome::UpToDate));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean_merge_creates_a_two_parent_commit() -> io::Result<()> {
+        let repo = TempRepo::new();
+        diverged(&repo, "FOUR", "TWO")?;
+        let main = get_head_commit(&repo)?.unwrap();
+        let feature = read_ref(&repo, "feature")?.unwrap();
+
+        let MergeOutcome::Merged(commit) = merge(&repo, "feature", || Ok(tester()))? else {
+            panic!("Both sides changed different lines, the merge must succeed");
+        };
+        assert_eq!(commit.parents, vec![main, feature]);
+        assert_eq!(commit.message, "Merge branch 'feature'");
+        assert_eq!(repo.read("shared.txt")?, "one\nTWO\nthree\nFOUR\nfive\n");
+        assert_eq!(get_head_commit(&repo)?, Some(commit.id));
+        assert_eq!(merge_head(&repo)?, None);
+        assert!(status(&repo)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean_merge_without_identity_changes_nothing() -> io::Result<()> {
+        let repo = TempRepo::new();
+        diverged(&repo, "FOUR", "TWO")?;
+        let main = get_head_commit(&repo)?;
+
+        let err = merge(&repo, "feature", || Err(GritError::config("No identity configured"))).unwrap_err();
+        assert!(matches!(err, GritError::InvalidConfig { .. }), "{:?}", err);
+        assert_eq!(repo.read("shared.txt")?, "one\ntwo\nthree\nFOUR\nfive\n");
+        assert_eq!(merge_head(&repo)?, None);
+        assert_eq!(get_head_commit(&repo)?, main);
+        assert!(status(&repo)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts_wait_for_continue() -> io::Result<()> {
+        let repo = TempRepo::new();
+        diverged(&repo, "FOUR", "TWO")?;
+        repo.commit_file("shared.txt", "one\ntwo (main)\nthree\nFOUR\nfive\n", "Main again")?;
+        let main = get_head_commit(&repo)?.unwrap();
+
+        let MergeOutcome::Conflicts(conflicts) = merge(&repo, "feature", || Ok(tester()))? else {
+            panic!("Both sides changed the second line");
+        };
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].0, PathBuf::from("shared.txt"));
+        assert_eq!(repo.read("shared.txt")?, concat!(
+            "one\n",
+            "<<<<<<< HEAD\n",
+            "two (main)\n",
+            "=======\n",
+            "TWO\n",
+            ">>>>>>> feature\n",
+            "three\n",
+            "FOUR\n",
+            "five\n",
+        ));
+
+        let current = status(&repo)?;
+        assert!(current.merging);
+        assert_eq!(current.unmerged.len(), 1);
+        assert!(current.unstaged.is_empty(), "Conflicted files are only listed as unmerged");
+        assert_eq!(format_porcelain(&current), "UU shared.txt\n");
+
+        assert!(Commit::new(&repo, "Too early", &tester()).is_err(), "Unmerged files must block commits");
+        assert!(continue_merge(&repo, &tester()).is_err());
+        assert!(merge(&repo, "feature", || Ok(tester())).is_err(), "A second merge cannot start");
+        assert_eq!(get_head_commit(&repo)?, Some(main.clone()));
+
+        repo.write("shared.txt", "one\nresolved\nthree\nfour\nfive\n")?;
+        add(&repo, Some("shared.txt"))?;
+        let current = status(&repo)?;
+        assert!(current.unmerged.is_empty() && current.merging);
+
+        let commit = continue_merge(&repo, &tester())?;
+        assert_eq!(commit.parents, vec![main, read_ref(&repo, "feature")?.unwrap()]);
+        assert_eq!(commit.message, "Merge branch 'feature'");
+        assert_eq!(merge_head(&repo)?, None);
+        assert!(status(&repo)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn test_abort_restores_head() -> io::Result<()> {
+        let repo = TempRepo::new();
+        diverged(&repo, "FOUR", "TWO")?;
+        repo.commit_file("shared.txt", "one\ntwo (main)\nthree\nFOUR\nfive\n", "Main again")?;
+        switch(&repo, "feature", false)?;
+        repo.commit_file("added.txt", "from feature\n", "Add a file")?;
+        switch(&repo, "Main", false)?;
+        let main = get_head_commit(&repo)?;
+
+        assert!(matches!(merge(&repo, "feature", || Ok(tester()))?, MergeOutcome::Conflicts(_)));
+        assert!(repo.exists("added.txt"), "Clean changes are applied while conflicts wait");
+
+        abort_merge(&repo)?;
+        assert_eq!(repo.read("shared.txt")?, "one\ntwo (main)\nthree\nFOUR\nfive\n");
+        assert!(!repo.exists("added.txt"));
+        assert_eq!(get_head_commit(&repo)?, main);
+        assert_eq!(merge_head(&repo)?, None);
+        assert!(status(&repo)?.is_clean());
+        assert!(abort_merge(&repo).is_err(), "Nothing is left to abort");
+        Ok(())
+    }
+
+    #[test]
+    fn test_modified_and_deleted_file_conflicts() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("doomed.txt", "v1\n", "Base")?;
+        create_branch(&repo, "feature", None)?;
+        switch(&repo, "feature", false)?;
+        repo.commit_file("doomed.txt", "v2\n", "Edit")?;
+        switch(&repo, "Main", false)?;
+        fs::remove_file(repo.working_path("doomed.txt"))?;
+        repo.commit_file("other.txt", "other\n", "Delete")?;
+
+        let MergeOutcome::Conflicts(conflicts) = merge(&repo, "feature", || Ok(tester()))? else {
+            panic!("The file was deleted on one side and modified on the other");
+        };
+        assert_eq!(conflicts[0].1.describe(), "deleted by us");
+        assert_eq!(repo.read("doomed.txt")?, "v2\n", "The modified version is kept on disk");
+        assert_eq!(format_porcelain(&status(&repo)?), "DU doomed.txt\n");
+
+        // Deleting it resolves the conflict in favour of our side
+        fs::remove_file(repo.working_path("doomed.txt"))?;
+        add(&repo, None)?;
+        let commit = continue_merge(&repo, &tester())?;
+        assert_eq!(commit.parents.len(), 2);
+        assert!(!repo.exists("doomed.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_refuses_to_overwrite_local_changes() -> io::Result<()> {
+        let repo = TempRepo::new();
+        diverged(&repo, "FOUR", "TWO")?;
+        repo.write("shared.txt", "uncommitted\n")?;
+
+        assert!(merge(&repo, "feature", || Ok(tester())).is_err());
+        assert_eq!(repo.read("shared.txt")?, "uncommitted\n");
+        assert_eq!(merge_head(&repo)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_forward_refuses_staged_changes() -> io::Result<()> {
+        let repo = TempRepo::new();
+        let first = repo.commit_file("a.txt", "a\n", "First")?;
+        create_branch(&repo, "feature", None)?;
+        switch(&repo, "feature", false)?;
+        repo.commit_file("b.txt", "b\n", "Second")?;
+        switch(&repo, "Main", false)?;
+
+        repo.write("staged.txt", "staged\n")?;
+        add(&repo, None)?;
+        let err = merge(&repo, "feature", || Ok(tester())).unwrap_err();
+        assert!(matches!(err, GritError::DirtyWorkingTree(_)), "{:?}", err);
+        assert_eq!(read_ref(&repo, "Main")?, Some(first.id));
+        assert!(!repo.exists("b.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_files_conflict_without_markers() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.commit_file("blob.bin", "\0base", "Base")?;
+        create_branch(&repo, "feature", None)?;
+        switch(&repo, "feature", false)?;
+        repo.commit_file("blob.bin", "\0theirs", "Theirs")?;
+        switch(&repo, "Main", false)?;
+        repo.commit_file("blob.bin", "\0ours", "Ours")?;
+
+        let MergeOutcome::Conflicts(conflicts) = merge(&repo, "feature", || Ok(tester()))? else {
+            panic!("Both sides changed a binary file");
+        };
+        assert_eq!(conflicts[0].1.describe(), "both modified");
+        assert_eq!(fs::read(repo.working_path("blob.bin"))?, b"\0ours", "Our version is kept as is");
+        Ok(())
+    }
+}
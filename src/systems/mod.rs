@@ -1,6 +1,48 @@
-pub(super) mod init;
-pub(super) mod add;
+pub mod init;
+pub mod repository;
+pub mod config;
+pub mod identity;
+pub mod atomic;
+pub mod add;
+pub mod refs;
+pub mod branch;
+pub mod checkout;
+pub mod status;
+pub mod index;
+pub mod diff;
+pub mod merge;
+pub mod gc;
+pub mod fsck;
 pub mod commits;
 pub mod filters;
+pub mod objects;
+#[cfg(test)]
 mod init_test;
-mod add_test;
\ No newline at end of file
+#[cfg(test)]
+mod add_test;
+#[cfg(test)]
+mod checkout_test;
+#[cfg(test)]
+mod branch_test;
+#[cfg(test)]
+mod status_test;
+#[cfg(test)]
+mod index_test;
+#[cfg(test)]
+mod diff_test;
+#[cfg(test)]
+mod merge_test;
+#[cfg(test)]
+mod gc_test;
+#[cfg(test)]
+mod fsck_test;
+#[cfg(test)]
+mod atomic_test;
+#[cfg(test)]
+mod error_test;
+#[cfg(test)]
+mod repository_test;
+#[cfg(test)]
+mod identity_test;
+#[cfg(test)]
+mod config_test;
@@ -0,0 +1,17 @@
+use std::io::{self, Write};
+use crate::systems::repository::Repository;
+use crate::error::Result;
+
+/// Print the content of an object, or only its kind when `show_kind` is set
+pub fn cat_file(repo: &Repository, id: &str, show_kind: bool) -> Result<()> {
+    let (kind, data) = repo.objects().read(id)?;
+
+    let mut stdout = io::stdout();
+    if show_kind {
+        writeln!(stdout, "{}", kind.as_str())?;
+    } else {
+        stdout.write_all(&data)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
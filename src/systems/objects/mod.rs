@@ -0,0 +1,7 @@
+pub mod cat;
+pub mod tree;
+pub mod store;
+pub mod pack;
+pub mod object;
+#[cfg(test)]
+mod test;
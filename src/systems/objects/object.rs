@@ -0,0 +1,69 @@
+use hex::encode;
+use sha1::{Sha1, Digest};
+use serde::{Deserialize, Serialize};
+use crate::error::{GritError, Result};
+
+/// The kinds of objects kept in the object database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectKind {
+    /// Raw file content.
+    Blob,
+    /// A directory listing pointing at blobs and other trees.
+    Tree,
+    /// A commit pointing at a tree and its parents.
+    Commit,
+}
+
+impl ObjectKind {
+    /// Name used in the object header
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+        }
+    }
+
+    /// Parse the name found in an object header
+    pub fn parse(name: &str) -> Option<ObjectKind> {
+        match name {
+            "blob" => Some(ObjectKind::Blob),
+            "tree" => Some(ObjectKind::Tree),
+            "commit" => Some(ObjectKind::Commit),
+            _ => None,
+        }
+    }
+}
+
+/// Prefix the content with its `<kind> <length>\0` header
+pub fn encode_object(kind: ObjectKind, data: &[u8]) -> Vec<u8> {
+    let mut raw = format!("{} {}\0", kind.as_str(), data.len()).into_bytes();
+    raw.extend_from_slice(data);
+    raw
+}
+
+/// Split a raw object into its kind and content, checking the declared length
+pub fn decode_object(raw: &[u8]) -> Result<(ObjectKind, Vec<u8>)> {
+    let invalid = |msg: &str| GritError::corrupt(msg);
+
+    let nul = raw.iter().position(|&b| b == 0).ok_or_else(|| invalid("Object header is not terminated"))?;
+    let header = std::str::from_utf8(&raw[..nul]).map_err(|_| invalid("Object header is not valid UTF-8"))?;
+    let (kind, length) = header.split_once(' ').ok_or_else(|| invalid("Malformed object header"))?;
+
+    let kind = ObjectKind::parse(kind).ok_or_else(|| invalid("Unknown object kind"))?;
+    let length: usize = length.parse().map_err(|_| invalid("Invalid object length"))?;
+
+    let data = &raw[nul + 1..];
+    if data.len() != length {
+        return Err(invalid("Object length does not match its header"));
+    }
+
+    Ok((kind, data.to_vec()))
+}
+
+/// Compute the id of an object: the SHA-1 of its header followed by its content
+pub fn hash_object(kind: ObjectKind, data: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(encode_object(kind, data));
+    encode(hasher.finalize())
+}
@@ -0,0 +1,322 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use sha1::{Digest, Sha1};
+use super::object::ObjectKind;
+use crate::algorithms::bdelta::{apply_delta, make_delta};
+use crate::algorithms::fcompress::{comp_bytes, decomp_bytes};
+use crate::systems::atomic::write_atomic;
+use crate::systems::commits::versioning::pack_chains;
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// Directory inside `.grit/objects` holding packs and their indexes
+pub const PACK_DIR: &str = "pack";
+
+const PACK_SIGNATURE: &[u8; 4] = b"GPCK";
+const INDEX_SIGNATURE: &[u8; 4] = b"GIDX";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: u64 = 12;
+const ID_LEN: usize = 20;
+/// An index entry is an object id followed by its offset in the pack
+const INDEX_ENTRY_LEN: u64 = ID_LEN as u64 + 8;
+
+/// Entry holding the whole object
+const ENTRY_FULL: u8 = 1;
+/// Entry holding a delta against another object of the same pack
+const ENTRY_DELTA: u8 = 2;
+
+/// Longest chain of deltas an object may have to be rebuilt through
+const MAX_DELTA_DEPTH: usize = 10;
+/// Number of preceding objects tried as the base of a delta
+const DELTA_WINDOW: usize = 10;
+
+/// What writing a pack produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackStats {
+    pub path: PathBuf,
+    pub objects: usize,
+    /// Objects stored as a delta against another one
+    pub deltas: usize,
+    /// Size of the pack file in bytes
+    pub size: u64,
+}
+
+/// Packs every object of a repository, see `ObjectStore::repack`, along with its version
+/// chains, see `pack_chains`
+pub fn repack(repo: &Repository) -> Result<Option<PackStats>> {
+    pack_chains(repo)?;
+    repo.objects().repack()
+}
+
+/// Writes objects into a single pack file with an index next to it.
+///
+/// The pack starts with `GPCK`, a format version and the object count, all big-endian.
+/// Each entry follows as its type (whole or delta), the object kind, the id of the delta
+/// base for deltas, then the length of the zlib-compressed payload and the payload itself.
+/// A SHA-1 of everything before closes the file and names it `pack-<sha1>.pack`.
+///
+/// Objects are sorted by kind and decreasing size, and each one is delta-encoded against the
+/// best of the few preceding ones when that takes less than half its size, so revisions of
+/// the same file end up stored as small deltas against each other.
+pub fn write_pack(pack_dir: &Path, objects: &[(String, ObjectKind, Vec<u8>)]) -> Result<PackStats> {
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by_key(|&i| (kind_code(objects[i].1), std::cmp::Reverse(objects[i].2.len()), objects[i].0.clone()));
+
+    // Pick a base for each object among the ones just before it
+    let mut depth = vec![0; objects.len()];
+    let mut bases: Vec<Option<(usize, Vec<u8>)>> = vec![None; objects.len()];
+    for (position, &i) in order.iter().enumerate() {
+        let (_, kind, data) = &objects[i];
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for &candidate in order[position.saturating_sub(DELTA_WINDOW)..position].iter() {
+            if objects[candidate].1 != *kind || depth[candidate] >= MAX_DELTA_DEPTH {
+                continue;
+            }
+            let delta = make_delta(&objects[candidate].2, data);
+            let smaller = best.as_ref().map_or(delta.len() < data.len() / 2, |(_, best)| delta.len() < best.len());
+            if smaller {
+                best = Some((candidate, delta));
+            }
+        }
+        if let Some((base, _)) = &best {
+            depth[i] = depth[*base] + 1;
+        }
+        bases[i] = best;
+    }
+
+    let mut pack = Vec::new();
+    pack.extend_from_slice(PACK_SIGNATURE);
+    pack.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+    let mut index: Vec<([u8; ID_LEN], u64)> = Vec::with_capacity(objects.len());
+    let mut deltas = 0;
+    for &i in &order {
+        let (id, kind, data) = &objects[i];
+        index.push((id_bytes(id)?, pack.len() as u64));
+
+        let payload = match &bases[i] {
+            Some((base, delta)) => {
+                deltas += 1;
+                pack.push(ENTRY_DELTA);
+                pack.push(kind_code(*kind));
+                pack.extend_from_slice(&id_bytes(&objects[*base].0)?);
+                comp_bytes(delta)?
+            }
+            None => {
+                pack.push(ENTRY_FULL);
+                pack.push(kind_code(*kind));
+                comp_bytes(data)?
+            }
+        };
+        pack.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        pack.extend_from_slice(&payload);
+    }
+    let checksum: [u8; ID_LEN] = Sha1::digest(&pack).into();
+    pack.extend_from_slice(&checksum);
+
+    index.sort();
+    let mut index_file = Vec::with_capacity(HEADER_LEN as usize + index.len() * INDEX_ENTRY_LEN as usize + ID_LEN);
+    index_file.extend_from_slice(INDEX_SIGNATURE);
+    index_file.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    index_file.extend_from_slice(&(index.len() as u32).to_be_bytes());
+    for (id, offset) in &index {
+        index_file.extend_from_slice(id);
+        index_file.extend_from_slice(&offset.to_be_bytes());
+    }
+    index_file.extend_from_slice(&checksum);
+
+    // Readers only look at packs with an index, so the index is written last
+    fs::create_dir_all(pack_dir)?;
+    let name = format!("pack-{}", hex::encode(checksum));
+    let path = pack_dir.join(format!("{}.pack", name));
+    write_atomic(&path, &pack)?;
+    write_atomic(pack_dir.join(format!("{}.idx", name)), &index_file)?;
+
+    Ok(PackStats { path, objects: objects.len(), deltas, size: pack.len() as u64 })
+}
+
+/// The index of a pack, searched on disk without loading it whole.
+pub struct PackIndex {
+    pack_path: PathBuf,
+    index_path: PathBuf,
+    count: u64,
+    /// Size of the pack file, which no entry may claim to run past
+    pack_size: u64,
+}
+
+impl PackIndex {
+    pub fn open(pack_path: &Path) -> Result<PackIndex> {
+        let index_path = pack_path.with_extension("idx");
+        let mut header = [0u8; HEADER_LEN as usize];
+        File::open(&index_path)?.read_exact(&mut header)?;
+        if &header[..4] != INDEX_SIGNATURE || header[4..8] != FORMAT_VERSION.to_be_bytes() {
+            return Err(invalid(&format!("{} is not a pack index", index_path.display())));
+        }
+        let count = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as u64;
+        let pack_size = fs::metadata(pack_path)?.len();
+        Ok(PackIndex { pack_path: pack_path.to_path_buf(), index_path, count, pack_size })
+    }
+
+    /// Check the pack against the SHA-1 closing it, and that the index was written for it.
+    /// Reads the whole pack, so it is left to `grit fsck` rather than done on every read.
+    pub fn verify(&self) -> Result<()> {
+        let pack = fs::read(&self.pack_path)?;
+        if pack.len() < HEADER_LEN as usize + ID_LEN || &pack[..4] != PACK_SIGNATURE {
+            return Err(invalid("Not a pack file"));
+        }
+        let (content, checksum) = pack.split_at(pack.len() - ID_LEN);
+        if Sha1::digest(content).as_slice() != checksum {
+            return Err(invalid("Pack content does not match its checksum"));
+        }
+
+        let mut index = File::open(&self.index_path)?;
+        index.seek(SeekFrom::End(-(ID_LEN as i64)))?;
+        let mut index_checksum = [0u8; ID_LEN];
+        index.read_exact(&mut index_checksum)?;
+        if index_checksum != checksum {
+            return Err(invalid("Pack index belongs to another pack"));
+        }
+        Ok(())
+    }
+
+    /// Offset of an object in the pack, found by binary search over the sorted ids
+    pub fn find(&self, id: &str) -> Result<Option<u64>> {
+        let Ok(wanted) = id_bytes(id) else {
+            return Ok(None);
+        };
+        let mut file = File::open(&self.index_path)?;
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let (id, offset) = read_index_entry(&mut file, middle)?;
+            match id.cmp(&wanted) {
+                std::cmp::Ordering::Equal => return Ok(Some(offset)),
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every object id in the pack, sorted
+    pub fn ids(&self) -> Result<Vec<String>> {
+        let mut file = File::open(&self.index_path)?;
+        (0..self.count)
+            .map(|position| read_index_entry(&mut file, position).map(|(id, _)| hex::encode(id)))
+            .collect()
+    }
+
+    /// Read an object, rebuilding it through its chain of deltas
+    pub fn read(&self, id: &str) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        let Some(offset) = self.find(id)? else {
+            return Ok(None);
+        };
+        let mut pack = File::open(&self.pack_path)?;
+        self.read_at(&mut pack, offset, 0).map(Some)
+    }
+
+    fn read_at(&self, pack: &mut File, offset: u64, depth: usize) -> Result<(ObjectKind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            return Err(invalid("Delta chain is too long"));
+        }
+        pack.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 2];
+        pack.read_exact(&mut header)?;
+        let kind = kind_from_code(header[1])?;
+
+        let base = match header[0] {
+            ENTRY_FULL => None,
+            ENTRY_DELTA => {
+                let mut base = [0u8; ID_LEN];
+                pack.read_exact(&mut base)?;
+                Some(hex::encode(base))
+            }
+            _ => return Err(invalid("Unknown pack entry type")),
+        };
+
+        let mut length = [0u8; 8];
+        pack.read_exact(&mut length)?;
+        // A damaged length must not make us allocate whatever it claims
+        let length = u64::from_be_bytes(length);
+        let available = self.pack_size.saturating_sub(ID_LEN as u64).saturating_sub(pack.stream_position()?);
+        if length > available {
+            return Err(invalid("Pack entry runs past the end of the pack"));
+        }
+        let mut payload = vec![0u8; length as usize];
+        pack.read_exact(&mut payload)?;
+        let payload = decomp_bytes(&payload).map_err(|e| GritError::corrupt_from("Pack entry cannot be decompressed", e))?;
+
+        match base {
+            None => Ok((kind, payload)),
+            Some(base) => {
+                let base_offset = self.find(&base)?.ok_or_else(|| invalid("Delta base is missing from the pack"))?;
+                let (_, base_data) = self.read_at(pack, base_offset, depth + 1)?;
+                let data = apply_delta(&base_data, &payload)
+                    .map_err(|e| GritError::corrupt_from("Pack delta cannot be applied", e))?;
+                Ok((kind, data))
+            }
+        }
+    }
+}
+
+/// Every pack in `pack_dir` that has an index, sorted by name
+pub fn list_packs(pack_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !pack_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut packs: Vec<PathBuf> = fs::read_dir(pack_dir)?
+        .filter_map(io::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pack") && path.with_extension("idx").is_file())
+        .collect();
+    packs.sort();
+    Ok(packs)
+}
+
+/// Delete a pack and its index
+pub fn remove_pack(pack_path: &Path) -> Result<()> {
+    fs::remove_file(pack_path.with_extension("idx"))?;
+    fs::remove_file(pack_path)?;
+    Ok(())
+}
+
+fn read_index_entry(file: &mut File, position: u64) -> Result<([u8; ID_LEN], u64)> {
+    file.seek(SeekFrom::Start(HEADER_LEN + position * INDEX_ENTRY_LEN))?;
+    let mut entry = [0u8; INDEX_ENTRY_LEN as usize];
+    file.read_exact(&mut entry)?;
+
+    let mut id = [0u8; ID_LEN];
+    id.copy_from_slice(&entry[..ID_LEN]);
+    let mut offset = [0u8; 8];
+    offset.copy_from_slice(&entry[ID_LEN..]);
+    Ok((id, u64::from_be_bytes(offset)))
+}
+
+fn id_bytes(id: &str) -> Result<[u8; ID_LEN]> {
+    let mut bytes = [0u8; ID_LEN];
+    hex::decode_to_slice(id, &mut bytes).map_err(|_| GritError::InvalidArgument(format!("Invalid object id: {}", id)))?;
+    Ok(bytes)
+}
+
+fn kind_code(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Blob => 1,
+        ObjectKind::Tree => 2,
+        ObjectKind::Commit => 3,
+    }
+}
+
+fn kind_from_code(code: u8) -> Result<ObjectKind> {
+    match code {
+        1 => Ok(ObjectKind::Blob),
+        2 => Ok(ObjectKind::Tree),
+        3 => Ok(ObjectKind::Commit),
+        _ => Err(invalid("Unknown object kind in pack")),
+    }
+}
+
+fn invalid(message: &str) -> GritError {
+    GritError::corrupt(message)
+}
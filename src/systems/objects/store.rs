@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::object::{decode_object, encode_object, hash_object, ObjectKind};
+use super::pack::{list_packs, remove_pack, write_pack, PackIndex, PackStats, PACK_DIR};
+use crate::algorithms::fcompress::{comp_bytes, decomp_bytes};
+use crate::systems::atomic::write_atomic;
+use crate::error::{GritError, Result};
+
+pub const OBJECTS_DIR: &str = "objects";
+
+/// Content-addressed object database stored under `.grit/objects`.
+///
+/// Every object lives in `objects/<first two hex digits>/<remaining digits>` and is
+/// zlib-compressed, so identical content is only ever stored once. `grit repack` moves loose
+/// objects into packs under `objects/pack`, which reads fall back to.
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+    objects_dir: PathBuf,
+}
+
+impl ObjectStore {
+    /// Open the object database inside the given `.grit` directory
+    pub fn open(grit_dir: &Path) -> Self {
+        ObjectStore { objects_dir: grit_dir.join(OBJECTS_DIR) }
+    }
+
+    /// Store an object and return its id; writing existing content is a no-op
+    pub fn write(&self, kind: ObjectKind, data: &[u8]) -> Result<String> {
+        let id = hash_object(kind, data);
+        let path = self.object_path(&id)?;
+
+        if path.exists() || self.packed(&id)?.is_some() {
+            return Ok(id);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let compressed = comp_bytes(&encode_object(kind, data))?;
+        write_atomic(&path, compressed)?;
+        Ok(id)
+    }
+
+    /// Store the content of a file as a blob
+    pub fn write_file(&self, file_path: &Path) -> Result<String> {
+        let content = fs::read(file_path)?;
+        self.write(ObjectKind::Blob, &content)
+    }
+
+    /// Read an object back, returning its kind and content
+    pub fn read(&self, id: &str) -> Result<(ObjectKind, Vec<u8>)> {
+        let path = self.object_path(id)?;
+        if !path.exists() {
+            return match self.packed(id)? {
+                Some(pack) => pack.read(&id.to_ascii_lowercase())?
+                    .ok_or_else(|| GritError::MissingRef(format!("Object {} not found", id))),
+                None => Err(GritError::MissingRef(format!("Object {} not found", id))),
+            };
+        }
+
+        let compressed = fs::read(path)?;
+        let raw = decomp_bytes(&compressed)
+            .map_err(|e| GritError::corrupt_from(format!("Object {} cannot be decompressed", id), e))?;
+        decode_object(&raw)
+    }
+
+    /// Check whether an object is present in the database
+    pub fn exists(&self, id: &str) -> bool {
+        self.object_path(id).is_ok_and(|path| path.exists())
+            || self.packed(id).is_ok_and(|pack| pack.is_some())
+    }
+
+    /// Ids of the objects stored as loose files, sorted
+    pub fn loose_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        if !self.objects_dir.is_dir() {
+            return Ok(ids);
+        }
+        for dir in fs::read_dir(&self.objects_dir)? {
+            let dir = dir?;
+            let prefix = dir.file_name().to_string_lossy().to_string();
+            if prefix.len() != 2 || !dir.path().is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                let id = format!("{}{}", prefix, file?.file_name().to_string_lossy());
+                if id.len() == 40 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Ids of every object, loose or packed, sorted
+    pub fn object_ids(&self) -> Result<Vec<String>> {
+        let mut ids = self.loose_ids()?;
+        for pack in list_packs(&self.pack_dir())? {
+            ids.extend(PackIndex::open(&pack)?.ids()?);
+        }
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    /// Consolidate every loose object and existing pack into a single new pack, then delete
+    /// what it replaces. Returns `None` when there is nothing to pack.
+    pub fn repack(&self) -> Result<Option<PackStats>> {
+        self.repack_objects(&self.object_ids()?)
+    }
+
+    /// Write the given objects into a single new pack, replacing every existing pack and the
+    /// loose copies of these objects. Packed objects left out are dropped, loose ones stay.
+    pub fn repack_objects(&self, ids: &[String]) -> Result<Option<PackStats>> {
+        let objects = ids.iter()
+            .map(|id| self.read(id).map(|(kind, data)| (id.clone(), kind, data)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let old_packs = list_packs(&self.pack_dir())?;
+        let stats = match objects.is_empty() {
+            true => None,
+            false => Some(write_pack(&self.pack_dir(), &objects)?),
+        };
+
+        // Only delete once the new pack is complete, so objects are never missing
+        let new_pack = stats.as_ref().map(|stats| stats.path.clone());
+        for pack in old_packs.iter().filter(|pack| Some(*pack) != new_pack.as_ref()) {
+            remove_pack(pack)?;
+        }
+        for id in ids {
+            if self.object_path(id)?.exists() {
+                self.remove_loose(id)?;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Delete the loose copy of an object, along with its directory once empty
+    pub fn remove_loose(&self, id: &str) -> Result<()> {
+        let path = self.object_path(id)?;
+        fs::remove_file(&path)?;
+        if let Some(parent) = path.parent() {
+            if fs::read_dir(parent)?.next().is_none() {
+                fs::remove_dir(parent)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Where an object is stored and how many bytes its loose file takes, 0 when packed
+    pub fn location(&self, id: &str) -> Result<(PathBuf, u64)> {
+        let path = self.object_path(id)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            return Ok((path, metadata.len()));
+        }
+        for pack in list_packs(&self.pack_dir())? {
+            if PackIndex::open(&pack)?.find(&id.to_ascii_lowercase())?.is_some() {
+                return Ok((pack, 0));
+            }
+        }
+        Err(GritError::MissingRef(format!("Object {} not found", id)))
+    }
+
+    /// Directory holding the packs
+    pub fn pack_dir(&self) -> PathBuf {
+        self.objects_dir.join(PACK_DIR)
+    }
+
+    /// The pack containing an object, if any
+    fn packed(&self, id: &str) -> Result<Option<PackIndex>> {
+        let id = id.to_ascii_lowercase();
+        for pack in list_packs(&self.pack_dir())? {
+            let index = PackIndex::open(&pack)?;
+            if index.find(&id)?.is_some() {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Location of an object on disk, rejecting ids that are not SHA-1 hex digests
+    fn object_path(&self, id: &str) -> Result<PathBuf> {
+        if id.len() != 40 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(GritError::InvalidArgument(format!("Invalid object id: {}", id)));
+        }
+        let id = id.to_ascii_lowercase();
+        Ok(self.objects_dir.join(&id[..2]).join(&id[2..]))
+    }
+}
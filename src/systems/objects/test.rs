@@ -0,0 +1,326 @@
+use super::store::ObjectStore;
+use super::tree::{changed_paths, compare_snapshots, read_entries, read_tree, write_tree, Change};
+use super::object::{decode_object, encode_object, hash_object, ObjectKind};
+use super::pack::{list_packs, write_pack, PackIndex};
+use crate::error::GritError;
+
+/// Helper building `count` revisions of a file, each changing a single line
+fn revisions(count: usize) -> Vec<(String, ObjectKind, Vec<u8>)> {
+    (0..count)
+        .map(|revision| {
+            let content: String = (0..200)
+                .map(|line| if line == revision * 7 { format!("changed in {}\n", revision) } else { format!("line {}\n", line) })
+                .collect();
+            (hash_object(ObjectKind::Blob, content.as_bytes()), ObjectKind::Blob, content.into_bytes())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    fn count_objects(grit_dir: &std::path::Path) -> usize {
+        let objects_dir = grit_dir.join("objects");
+        fs::read_dir(objects_dir)
+            .map(|dirs| {
+                dirs.filter_map(Result::ok)
+                    .map(|dir| fs::read_dir(dir.path()).map(|files| files.count()).unwrap_or(0))
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_hash_is_stable_and_kind_dependent() {
+        let blob = hash_object(ObjectKind::Blob, b"hello\n");
+        assert_eq!(blob, hash_object(ObjectKind::Blob, b"hello\n"));
+        assert_eq!(blob.len(), 40);
+
+        // Same bytes stored as a different kind must not collide
+        assert_ne!(blob, hash_object(ObjectKind::Tree, b"hello\n"));
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() -> io::Result<()> {
+        let raw = encode_object(ObjectKind::Commit, b"some\0binary\ncontent");
+        let (kind, data) = decode_object(&raw)?;
+
+        assert_eq!(kind, ObjectKind::Commit);
+        assert_eq!(data, b"some\0binary\ncontent");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_length() {
+        let mut raw = encode_object(ObjectKind::Blob, b"content");
+        raw.pop();
+
+        assert!(decode_object(&raw).is_err(), "Truncated objects should be rejected");
+    }
+
+    #[test]
+    fn test_write_and_read_object() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+
+        let id = store.write(ObjectKind::Blob, b"Hello, World!")?;
+        assert!(store.exists(&id));
+        assert_eq!(id, hash_object(ObjectKind::Blob, b"Hello, World!"));
+
+        let (kind, data) = store.read(&id)?;
+        assert_eq!(kind, ObjectKind::Blob);
+        assert_eq!(data, b"Hello, World!");
+        Ok(())
+    }
+
+    #[test]
+    fn test_identical_content_is_stored_once() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+
+        let first = store.write(ObjectKind::Blob, b"shared content")?;
+        let second = store.write(ObjectKind::Blob, b"shared content")?;
+        store.write(ObjectKind::Blob, b"other content")?;
+
+        assert_eq!(first, second);
+        assert_eq!(count_objects(tmp_dir.path()), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_objects_are_compressed_on_disk() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let content = b"repeated line\n".repeat(100);
+
+        let id = store.write(ObjectKind::Blob, &content)?;
+        let on_disk = fs::read(tmp_dir.path().join("objects").join(&id[..2]).join(&id[2..]))?;
+
+        assert!(on_disk.len() < content.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_and_invalid_ids() {
+        let tmp_dir = TempDir::new().unwrap();
+        let store = ObjectStore::open(tmp_dir.path());
+        let absent = hash_object(ObjectKind::Blob, b"never written");
+
+        assert!(!store.exists(&absent));
+        assert!(matches!(store.read(&absent), Err(GritError::MissingRef(_))));
+        assert!(!store.exists("../../etc/passwd"));
+        assert!(matches!(store.read("xyz"), Err(GritError::InvalidArgument(_))));
+    }
+
+    fn snapshot(files: &[(&str, &str)]) -> BTreeMap<PathBuf, String> {
+        files.iter().map(|(path, hash)| (PathBuf::from(path), hash_object(ObjectKind::Blob, hash.as_bytes()))).collect()
+    }
+
+    #[test]
+    fn test_tree_roundtrip() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let files = snapshot(&[("README.md", "readme"), ("src/main.rs", "main"), ("src/systems/add.rs", "add")]);
+
+        let root = write_tree(&store, &files)?;
+        assert_eq!(read_tree(&store, &root)?, files);
+
+        let entries = read_entries(&store, &root)?;
+        let names: Vec<(&str, ObjectKind)> = entries.iter().map(|e| (e.name.as_str(), e.kind)).collect();
+        assert_eq!(names, vec![("README.md", ObjectKind::Blob), ("src", ObjectKind::Tree)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_id_depends_only_on_content() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+
+        let first = write_tree(&store, &snapshot(&[("a/x.txt", "x"), ("b/x.txt", "x")]))?;
+        let second = write_tree(&store, &snapshot(&[("b/x.txt", "x"), ("a/x.txt", "x")]))?;
+        assert_eq!(first, second);
+
+        // Both directories hold the same file, so they share a single tree object
+        let entries = read_entries(&store, &first)?;
+        assert_eq!(entries[0].hash, entries[1].hash);
+
+        let changed = write_tree(&store, &snapshot(&[("a/x.txt", "x"), ("b/x.txt", "y")]))?;
+        assert_ne!(first, changed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_tree_rejects_other_kinds() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let blob = store.write(ObjectKind::Blob, b"not a tree")?;
+
+        assert!(matches!(read_tree(&store, &blob), Err(GritError::CorruptObject { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_paths() {
+        let old = snapshot(&[("kept.txt", "same"), ("edited.txt", "before"), ("removed.txt", "gone")]);
+        let new = snapshot(&[("kept.txt", "same"), ("edited.txt", "after"), ("added.txt", "new")]);
+
+        assert_eq!(changed_paths(&old, &new), vec![
+            PathBuf::from("added.txt"),
+            PathBuf::from("edited.txt"),
+            PathBuf::from("removed.txt"),
+        ]);
+        assert!(changed_paths(&old, &old).is_empty());
+    }
+
+    /// Like `snapshot`, with the content written to the store
+    fn stored_snapshot(store: &ObjectStore, files: &[(&str, &str)]) -> io::Result<BTreeMap<PathBuf, String>> {
+        files.iter()
+            .map(|(path, content)| Ok((PathBuf::from(path), store.write(ObjectKind::Blob, content.as_bytes())?)))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_snapshots_pairs_identical_content_once() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let old = stored_snapshot(&store, &[("a", "1"), ("b", "1")])?;
+        let new = stored_snapshot(&store, &[("c", "1"), ("d", "1"), ("e", "1")])?;
+
+        assert_eq!(compare_snapshots(&store, &old, &new)?, vec![
+            (PathBuf::from("c"), Change::Renamed { from: PathBuf::from("a"), similarity: 100 }),
+            (PathBuf::from("d"), Change::Renamed { from: PathBuf::from("b"), similarity: 100 }),
+            (PathBuf::from("e"), Change::Added),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_snapshots_detects_similar_renames() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let old = stored_snapshot(&store, &[
+            ("kept.txt", "same\n"),
+            ("moved.txt", "one\ntwo\nthree\nfour\n"),
+            ("dropped.txt", "nothing\nalike\n"),
+        ])?;
+        let new = stored_snapshot(&store, &[
+            ("kept.txt", "same, edited\n"),
+            ("renamed.txt", "one\ntwo\nthree\nFOUR\n"),
+            ("unrelated.txt", "completely\ndifferent\n"),
+        ])?;
+
+        assert_eq!(compare_snapshots(&store, &old, &new)?, vec![
+            (PathBuf::from("dropped.txt"), Change::Deleted),
+            (PathBuf::from("kept.txt"), Change::Modified),
+            (PathBuf::from("renamed.txt"), Change::Renamed { from: PathBuf::from("moved.txt"), similarity: 75 }),
+            (PathBuf::from("unrelated.txt"), Change::Added),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_roundtrip_uses_deltas() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut objects = revisions(20);
+        objects.push((hash_object(ObjectKind::Commit, b"tree x\n"), ObjectKind::Commit, b"tree x\n".to_vec()));
+
+        let stats = write_pack(tmp_dir.path(), &objects)?;
+        assert_eq!(stats.objects, 21);
+        assert_eq!(stats.deltas, 19, "Every revision but one is stored against another");
+        let whole: usize = objects.iter().map(|(_, _, data)| data.len()).sum();
+        assert!((stats.size as usize) < whole / 10, "{} bytes packed for {} bytes of content", stats.size, whole);
+
+        assert_eq!(list_packs(tmp_dir.path())?, vec![stats.path.clone()]);
+        let index = PackIndex::open(&stats.path)?;
+        for (id, kind, data) in &objects {
+            assert_eq!(index.read(id)?, Some((*kind, data.clone())));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_index_lookup() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let objects = revisions(5);
+        let stats = write_pack(tmp_dir.path(), &objects)?;
+        let index = PackIndex::open(&stats.path)?;
+
+        let mut ids: Vec<String> = objects.iter().map(|(id, _, _)| id.clone()).collect();
+        ids.sort();
+        assert_eq!(index.ids()?, ids);
+        for id in &ids {
+            assert!(index.find(id)?.is_some());
+        }
+        assert_eq!(index.find(&hash_object(ObjectKind::Blob, b"absent"))?, None);
+        assert_eq!(index.find("not an id")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_damaged_packs_are_detected() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let objects = revisions(1);
+        let stats = write_pack(tmp_dir.path(), &objects)?;
+        PackIndex::open(&stats.path)?.verify()?;
+        let original = fs::read(&stats.path)?;
+
+        // The length of the first entry, after its type and kind, claims far more than the pack holds
+        let mut pack = original.clone();
+        pack[14..22].copy_from_slice(&u64::MAX.to_be_bytes());
+        fs::write(&stats.path, &pack)?;
+        let index = PackIndex::open(&stats.path)?;
+        assert!(matches!(index.read(&objects[0].0), Err(GritError::CorruptObject { .. })));
+        assert!(matches!(index.verify(), Err(GritError::CorruptObject { .. })));
+
+        let mut pack = original;
+        let last = pack.len() - 25;
+        pack[last] ^= 0xff;
+        fs::write(&stats.path, &pack)?;
+        assert!(matches!(PackIndex::open(&stats.path)?.verify(), Err(GritError::CorruptObject { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repack_consolidates_loose_objects() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let store = ObjectStore::open(tmp_dir.path());
+        let mut ids = Vec::new();
+        for (_, kind, data) in revisions(10) {
+            ids.push(store.write(kind, &data)?);
+        }
+
+        let stats = store.repack()?.expect("There are objects to pack");
+        assert_eq!(stats.objects, 10);
+        assert_eq!(store.loose_ids()?, Vec::<String>::new());
+        assert_eq!(count_objects(tmp_dir.path()), 2, "Only the pack and its index are left");
+        for (id, (_, kind, data)) in ids.iter().zip(revisions(10)) {
+            assert!(store.exists(id));
+            assert_eq!(store.read(id)?, (kind, data));
+        }
+
+        // Packed content is not written again, new content goes loose until the next repack
+        store.write(ObjectKind::Blob, &revisions(1)[0].2)?;
+        let extra = store.write(ObjectKind::Blob, b"new content")?;
+        assert_eq!(store.loose_ids()?, vec![extra.clone()]);
+
+        let stats = store.repack()?.unwrap();
+        assert_eq!(stats.objects, 11);
+        assert_eq!(list_packs(&store.pack_dir())?, vec![stats.path]);
+        assert_eq!(store.read(&extra)?.1, b"new content");
+        assert_eq!(store.object_ids()?.len(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repack_of_an_empty_store() -> io::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        assert_eq!(ObjectStore::open(tmp_dir.path()).repack()?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use super::object::ObjectKind;
+use super::store::ObjectStore;
+use crate::algorithms::vcompare::compv::similarity;
+use crate::error::{GritError, Result};
+
+/// Minimum similarity for a deleted and an added file to be reported as a rename
+const RENAME_THRESHOLD: f64 = 0.5;
+/// Above this many added or deleted files, only exact renames are looked for
+const RENAME_LIMIT: usize = 1000;
+
+/// One line of a tree object: a file (blob) or a sub-directory (tree).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeEntry {
+    pub name: String,
+    pub kind: ObjectKind,
+    pub hash: String,
+}
+
+/// Store a snapshot of tracked files, mapping relative paths to blob ids, as nested tree
+/// objects and return the id of the root tree. Identical directories share the same tree.
+pub fn write_tree(store: &ObjectStore, files: &BTreeMap<PathBuf, String>) -> Result<String> {
+    let mut blobs = Vec::new();
+    let mut directories: BTreeMap<String, BTreeMap<PathBuf, String>> = BTreeMap::new();
+
+    for (path, hash) in files {
+        let mut components = path.iter();
+        let name = components.next()
+            .and_then(|c| c.to_str())
+            .ok_or_else(|| GritError::InvalidArgument(format!("Empty or non UTF-8 path in snapshot: {}", path.display())))?
+            .to_string();
+        let rest: PathBuf = components.collect();
+
+        if rest.as_os_str().is_empty() {
+            blobs.push(TreeEntry { name, kind: ObjectKind::Blob, hash: hash.clone() });
+        } else {
+            directories.entry(name).or_default().insert(rest, hash.clone());
+        }
+    }
+
+    let mut entries = blobs;
+    for (name, children) in &directories {
+        entries.push(TreeEntry { name: name.clone(), kind: ObjectKind::Tree, hash: write_tree(store, children)? });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    store.write(ObjectKind::Tree, &serde_json::to_vec(&entries)?)
+}
+
+/// Read the entries of a single tree object
+pub fn read_entries(store: &ObjectStore, id: &str) -> Result<Vec<TreeEntry>> {
+    let (kind, data) = store.read(id)?;
+    if kind != ObjectKind::Tree {
+        return Err(GritError::corrupt(format!("Object {} is not a tree", id)));
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Flatten a tree object back into the relative path to blob id mapping it was built from
+pub fn read_tree(store: &ObjectStore, id: &str) -> Result<BTreeMap<PathBuf, String>> {
+    let mut files = BTreeMap::new();
+    collect_files(store, id, PathBuf::new(), &mut files)?;
+    Ok(files)
+}
+
+fn collect_files(store: &ObjectStore, id: &str, prefix: PathBuf, files: &mut BTreeMap<PathBuf, String>) -> Result<()> {
+    for entry in read_entries(store, id)? {
+        let path = prefix.join(&entry.name);
+        match entry.kind {
+            ObjectKind::Tree => collect_files(store, &entry.hash, path, files)?,
+            _ => { files.insert(path, entry.hash); }
+        }
+    }
+    Ok(())
+}
+
+/// Paths that were added, modified or removed between two snapshots
+pub fn changed_paths(old: &BTreeMap<PathBuf, String>, new: &BTreeMap<PathBuf, String>) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = new.iter()
+        .filter(|(path, hash)| old.get(*path) != Some(hash))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+    changed.sort();
+    changed
+}
+
+/// How a path differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+    /// The content moved here from `from`, keeping `similarity` percent of its lines.
+    Renamed { from: PathBuf, similarity: u8 },
+}
+
+impl Change {
+    /// Single letter used by `status --porcelain`
+    pub fn code(&self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+            Change::Renamed { .. } => 'R',
+        }
+    }
+}
+
+/// Changes from `old` to `new`, sorted by path.
+///
+/// A deleted file whose content reappears under a new path is reported as a rename, as is
+/// one at least half of whose lines are kept by an added file. The most similar pairs are
+/// matched first and each file takes part in at most one rename.
+pub fn compare_snapshots(store: &ObjectStore, old: &BTreeMap<PathBuf, String>, new: &BTreeMap<PathBuf, String>) -> Result<Vec<(PathBuf, Change)>> {
+    let mut deleted: Vec<&PathBuf> = old.keys().filter(|path| !new.contains_key(*path)).collect();
+    let mut added = Vec::new();
+    let mut changes = Vec::new();
+
+    for (path, hash) in new {
+        match old.get(path) {
+            Some(old_hash) if old_hash == hash => {}
+            Some(_) => changes.push((path.clone(), Change::Modified)),
+            None => match deleted.iter().position(|from| &old[*from] == hash) {
+                Some(index) => {
+                    let from = deleted.remove(index).clone();
+                    changes.push((path.clone(), Change::Renamed { from, similarity: 100 }));
+                }
+                None => added.push(path),
+            },
+        }
+    }
+
+    if !added.is_empty() && !deleted.is_empty() && added.len().max(deleted.len()) <= RENAME_LIMIT {
+        let read_text = |hash: &str| -> Result<String> {
+            Ok(String::from_utf8_lossy(&store.read(hash)?.1).into_owned())
+        };
+        let deleted_texts: Vec<String> = deleted.iter().map(|path| read_text(&old[*path])).collect::<Result<_>>()?;
+
+        let mut candidates = Vec::new();
+        for (a, path) in added.iter().enumerate() {
+            let text = read_text(&new[*path])?;
+            let lines = text.lines().count();
+            for (d, deleted_text) in deleted_texts.iter().enumerate() {
+                // Empty files would match anything, and very different sizes cannot reach the threshold
+                let deleted_lines = deleted_text.lines().count();
+                let best_case = 2.0 * lines.min(deleted_lines) as f64 / (lines + deleted_lines).max(1) as f64;
+                if text.is_empty() || deleted_text.is_empty() || best_case < RENAME_THRESHOLD {
+                    continue;
+                }
+
+                let score = similarity(deleted_text, &text);
+                if score >= RENAME_THRESHOLD {
+                    candidates.push((score, a, d));
+                }
+            }
+        }
+        candidates.sort_by(|x, y| y.0.total_cmp(&x.0).then(x.1.cmp(&y.1)).then(x.2.cmp(&y.2)));
+
+        let mut added_used = vec![false; added.len()];
+        let mut deleted_used = vec![false; deleted.len()];
+        for (score, a, d) in candidates {
+            if added_used[a] || deleted_used[d] {
+                continue;
+            }
+            added_used[a] = true;
+            deleted_used[d] = true;
+            let similarity = (score * 100.0).floor() as u8;
+            changes.push((added[a].clone(), Change::Renamed { from: deleted[d].clone(), similarity }));
+        }
+
+        added = added.into_iter().zip(added_used).filter(|(_, used)| !used).map(|(path, _)| path).collect();
+        deleted = deleted.into_iter().zip(deleted_used).filter(|(_, used)| !used).map(|(path, _)| path).collect();
+    }
+
+    changes.extend(added.into_iter().map(|path| (path.clone(), Change::Added)));
+    changes.extend(deleted.into_iter().map(|path| (path.clone(), Change::Deleted)));
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(changes)
+}
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::systems::atomic::write_atomic;
+use crate::systems::init::{legacy_branch, take_legacy_branch, DEFAULT_BRANCH};
+use crate::systems::repository::Repository;
+use crate::error::{GritError, Result};
+
+/// HEAD, inside `.grit`
+pub const HEAD_FILE: &str = "HEAD";
+/// Directory of the branch refs, inside `.grit`
+pub const HEADS_DIR: &str = "refs/heads";
+const SYMBOLIC_PREFIX: &str = "ref: refs/heads/";
+
+/// What HEAD currently designates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// HEAD follows a branch, which has no ref yet until its first commit.
+    Branch(String),
+    /// HEAD points directly at a commit.
+    Detached(String),
+}
+
+/// Read HEAD. Where it still holds a bare commit id and the branch name is kept in
+/// `.grit/config`, it reads as that branch without being rewritten, see
+/// `migrate_legacy_head`.
+pub fn read_head(repo: &Repository) -> Result<Head> {
+    let content = head_content(repo)?;
+    if let Some(name) = content.strip_prefix(SYMBOLIC_PREFIX) {
+        return Ok(Head::Branch(name.to_string()));
+    }
+    if let Some((branch, _)) = legacy_head(repo)? {
+        return Ok(Head::Branch(branch));
+    }
+
+    if content.is_empty() {
+        return Ok(Head::Branch(DEFAULT_BRANCH.to_string()));
+    }
+    Ok(Head::Detached(content))
+}
+
+/// Upgrade a repository where HEAD held a bare commit id and the branch name was kept in
+/// `.grit/config`: the commit becomes the branch's ref and HEAD follows the branch.
+/// Commands that write call it while holding the repository lock.
+pub fn migrate_legacy_head(repo: &Repository) -> Result<()> {
+    let Some((branch, id)) = legacy_head(repo)? else {
+        return Ok(());
+    };
+    // A commit made since is already on the branch's ref
+    if !id.is_empty() && !ref_path(repo, &branch).is_file() {
+        write_ref(repo, &branch, &id)?;
+    }
+    set_head_branch(repo, &branch)?;
+    take_legacy_branch(repo)?;
+    Ok(())
+}
+
+/// The branch and commit id of a HEAD still in the legacy format, the id being empty
+/// before the first commit
+fn legacy_head(repo: &Repository) -> Result<Option<(String, String)>> {
+    let content = head_content(repo)?;
+    if content.starts_with(SYMBOLIC_PREFIX) {
+        return Ok(None);
+    }
+    Ok(legacy_branch(repo)?.map(|branch| (branch, content)))
+}
+
+fn head_content(repo: &Repository) -> Result<String> {
+    let head_file = repo.grit_path(HEAD_FILE);
+    Ok(if head_file.exists() {
+        fs::read_to_string(head_file)?.trim().to_string()
+    } else {
+        String::new()
+    })
+}
+
+/// The commit HEAD resolves to, `None` on a branch without commits
+pub fn head_commit(repo: &Repository) -> Result<Option<String>> {
+    match read_head(repo)? {
+        Head::Branch(name) => read_ref(repo, &name),
+        Head::Detached(id) => Ok(Some(id)),
+    }
+}
+
+/// Name of the checked out branch, `None` when HEAD is detached
+pub fn current_branch(repo: &Repository) -> Result<Option<String>> {
+    match read_head(repo)? {
+        Head::Branch(name) => Ok(Some(name)),
+        Head::Detached(_) => Ok(None),
+    }
+}
+
+/// Make HEAD follow a branch
+pub fn set_head_branch(repo: &Repository, name: &str) -> Result<()> {
+    write_head(repo, &format!("{}{}", SYMBOLIC_PREFIX, name))
+}
+
+/// Point HEAD directly at a commit
+pub fn set_head_detached(repo: &Repository, commit_id: &str) -> Result<()> {
+    write_head(repo, commit_id)
+}
+
+/// Record a new commit on whatever HEAD designates: the current branch moves forward,
+/// a detached HEAD is replaced
+pub fn update_head(repo: &Repository, commit_id: &str) -> Result<()> {
+    match read_head(repo)? {
+        Head::Branch(name) => write_ref(repo, &name, commit_id),
+        Head::Detached(_) => set_head_detached(repo, commit_id),
+    }
+}
+
+fn write_head(repo: &Repository, content: &str) -> Result<()> {
+    fs::create_dir_all(repo.grit_dir())?;
+    write_atomic(repo.grit_path(HEAD_FILE), format!("{}\n", content))
+}
+
+/// Commit id a branch points at, `None` if the branch does not exist
+pub fn read_ref(repo: &Repository, name: &str) -> Result<Option<String>> {
+    if validate_branch_name(name).is_err() {
+        return Ok(None);
+    }
+    let path = ref_path(repo, name);
+    if !path.is_file() {
+        // Until it is migrated, a legacy HEAD holds the commit of its branch
+        return Ok(legacy_head(repo)?
+            .filter(|(branch, id)| branch == name && !id.is_empty())
+            .map(|(_, id)| id));
+    }
+    let id = fs::read_to_string(path)?.trim().to_string();
+    Ok(if id.is_empty() { None } else { Some(id) })
+}
+
+/// Create or move a branch
+pub fn write_ref(repo: &Repository, name: &str, commit_id: &str) -> Result<()> {
+    validate_branch_name(name)?;
+    let path = ref_path(repo, name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(path, format!("{}\n", commit_id))
+}
+
+/// Remove a branch, along with the directories its name leaves empty
+pub fn delete_ref(repo: &Repository, name: &str) -> Result<()> {
+    validate_branch_name(name)?;
+    let path = ref_path(repo, name);
+    fs::remove_file(&path)?;
+
+    let heads = repo.grit_path(HEADS_DIR);
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent == heads || fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+/// All branch names, sorted
+pub fn list_branches(repo: &Repository) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    collect_refs(&repo.grit_path(HEADS_DIR), "", &mut names)?;
+    if let Some((branch, id)) = legacy_head(repo)? {
+        if !id.is_empty() && !names.contains(&branch) {
+            names.push(branch);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn collect_refs(dir: &Path, prefix: &str, names: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            collect_refs(&entry.path(), &format!("{}/", name), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+fn ref_path(repo: &Repository, name: &str) -> PathBuf {
+    name.split('/').fold(repo.grit_path(HEADS_DIR), |path, part| path.join(part))
+}
+
+/// Reject names that could not be stored as a ref file or would be confused with
+/// options and revisions
+pub fn validate_branch_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name != "HEAD"
+        && !name.starts_with('-')
+        && !name.starts_with('/')
+        && !name.ends_with('/')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("//")
+        && !name.split('/').any(|part| part.starts_with('.'))
+        && !name.chars().any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(GritError::InvalidArgument(format!("'{}' is not a valid branch name", name)))
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use crate::systems::add::add;
+use crate::systems::commits::commit::Commit;
+use crate::systems::commits::log::format_log;
+use crate::systems::identity::Identity;
+use crate::systems::init::{find_grit_root, init_grit, normalize_path, GRIT_DIR};
+use crate::systems::objects::store::ObjectStore;
+use crate::systems::objects::tree::Change;
+use crate::systems::status::{status, Status};
+use crate::error::{GritError, Result};
+
+/// A grit repository: a working directory and the `.grit` directory at its root.
+///
+/// Every file the commands read or write is found from here rather than from the
+/// process's current directory, so a program can work with several repositories at once.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    root: PathBuf,
+    grit_dir: PathBuf,
+    /// Where paths given by the user start from: the root, unless the repository was
+    /// discovered from one of its subdirectories
+    current_dir: PathBuf,
+}
+
+impl Repository {
+    /// Opens the repository whose working directory is `root`
+    pub fn open(root: impl AsRef<Path>) -> Result<Repository> {
+        let root = root.as_ref();
+        if !root.join(GRIT_DIR).is_dir() {
+            return Err(GritError::NotARepository(root.to_path_buf()));
+        }
+        let root = normalize_path(&root.canonicalize()?);
+        Ok(Repository { grit_dir: root.join(GRIT_DIR), current_dir: root.clone(), root })
+    }
+
+    /// Opens the repository containing `path`, looking in its parent directories. Relative
+    /// paths given to the commands are then taken from `path`.
+    pub fn discover(path: impl AsRef<Path>) -> Result<Repository> {
+        let path = path.as_ref();
+        let root = find_grit_root(path).ok_or_else(|| GritError::NotARepository(path.to_path_buf()))?;
+        let mut repo = Repository::open(root)?;
+        repo.current_dir = normalize_path(&path.canonicalize()?);
+        Ok(repo)
+    }
+
+    /// Creates a repository in `root`, or opens the one already there
+    pub fn init(root: impl AsRef<Path>) -> Result<Repository> {
+        init_grit(root.as_ref())
+    }
+
+    /// Working directory of the repository
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The `.grit` directory
+    pub fn grit_dir(&self) -> &Path {
+        &self.grit_dir
+    }
+
+    /// Directory relative paths given by the user are taken from
+    pub fn current_dir(&self) -> &Path {
+        &self.current_dir
+    }
+
+    /// A file of the working directory, from its path relative to the root
+    pub fn working_path(&self, rel_path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(rel_path)
+    }
+
+    /// A file inside `.grit`, such as `HEAD` or `refs/heads/Main`
+    pub fn grit_path(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.grit_dir.join(name)
+    }
+
+    /// The object database
+    pub fn objects(&self) -> ObjectStore {
+        ObjectStore::open(&self.grit_dir)
+    }
+
+    /// A path given by the user, relative to the current directory, expressed relative to
+    /// the root
+    pub fn relative_path(&self, path: &str) -> Result<PathBuf> {
+        let abs_path = self.current_dir.join(path);
+        let abs_path = abs_path.canonicalize().map(|path| normalize_path(&path)).unwrap_or(abs_path);
+
+        abs_path.strip_prefix(&self.root)
+            .map(Path::to_path_buf)
+            .map_err(|_| GritError::InvalidArgument(format!("'{}' is outside project directory", path)))
+    }
+
+    /// Stages a path relative to the current directory, or everything below it, returning
+    /// the files whose staged content changed
+    pub fn add(&self, path: Option<&str>) -> Result<Vec<(PathBuf, Change)>> {
+        add(self, path)
+    }
+
+    /// Who commits in this repository, from the environment and configuration. See
+    /// `Identity::resolve_with`.
+    pub fn identity(&self) -> Result<Identity> {
+        Identity::resolve(self, None)
+    }
+
+    /// Commits what is staged, `None` when nothing changed since HEAD
+    pub fn commit(&self, message: &str, identity: &Identity) -> Result<Option<Commit>> {
+        Commit::new(self, message, identity)
+    }
+
+    /// The history reachable from HEAD, as `grit log` prints it
+    pub fn log(&self, stat: bool) -> Result<String> {
+        format_log(self, stat)
+    }
+
+    /// Differences between HEAD, the staging tree and the working directory
+    pub fn status(&self) -> Result<Status> {
+        status(self)
+    }
+}
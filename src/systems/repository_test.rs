@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::Path;
+use crate::error::Result;
+use crate::systems::repository::Repository;
+
+/// Helper writing a file below `root`, creating its directories
+fn write(root: &Path, rel_path: &str, content: &str) -> Result<()> {
+    let path = root.join(rel_path);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+    use crate::error::GritError;
+    use crate::test_support::tester;
+    use crate::systems::refs::{current_branch, HEAD_FILE};
+
+    #[test]
+    fn test_open_needs_a_grit_directory() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let error = Repository::open(tmp_dir.path()).unwrap_err();
+        assert!(matches!(&error, GritError::NotARepository(path) if path == tmp_dir.path()), "{:?}", error);
+
+        let repo = Repository::init(tmp_dir.path())?;
+        let opened = Repository::open(tmp_dir.path())?;
+        assert_eq!(opened.root(), repo.root());
+        assert_eq!(opened.grit_dir(), repo.root().join(".grit"));
+        assert_eq!(opened.grit_path(HEAD_FILE), repo.root().join(".grit/HEAD"));
+        assert_eq!(current_branch(&opened)?.as_deref(), Some("Main"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_discover_from_a_subdirectory() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let repo = Repository::init(tmp_dir.path())?;
+        write(repo.root(), "src/deep/main.rs", "fn main() {}\n")?;
+
+        let found = Repository::discover(repo.root().join("src/deep"))?;
+        assert_eq!(found.root(), repo.root());
+        assert_eq!(found.current_dir(), repo.root().join("src/deep"));
+        assert_eq!(found.relative_path("main.rs")?, PathBuf::from("src/deep/main.rs"));
+        assert_eq!(found.relative_path("../..")?, PathBuf::new());
+        assert!(matches!(found.relative_path("../../.."), Err(GritError::InvalidArgument(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_without_path_stages_the_current_directory() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        Repository::init(tmp_dir.path())?;
+        write(tmp_dir.path(), "docs/guide.md", "guide\n")?;
+        write(tmp_dir.path(), "notes.txt", "notes\n")?;
+
+        let repo = Repository::discover(tmp_dir.path().join("docs"))?;
+        repo.add(None)?;
+        let status = repo.status()?;
+        assert_eq!(status.staged.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), vec![PathBuf::from("docs/guide.md")]);
+        assert_eq!(status.untracked, vec![PathBuf::from("notes.txt")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repositories_are_independent() -> Result<()> {
+        let (first_dir, second_dir) = (TempDir::new()?, TempDir::new()?);
+        let first = Repository::init(first_dir.path())?;
+        let second = Repository::init(second_dir.path())?;
+
+        write(first.root(), "a.txt", "first\n")?;
+        write(second.root(), "b.txt", "second\n")?;
+        first.add(None)?;
+        second.add(None)?;
+        let first_commit = first.commit("In the first", &tester())?.expect("Something to commit");
+        let second_commit = second.commit("In the second", &tester())?.expect("Something to commit");
+
+        assert_eq!(first_commit.files, vec!["a.txt".to_string()]);
+        assert_eq!(second_commit.files, vec!["b.txt".to_string()]);
+        assert!(first.log(false)?.contains("In the first"));
+        assert!(!first.log(false)?.contains("In the second"));
+        assert!(second.status()?.staged.is_empty());
+        Ok(())
+    }
+}
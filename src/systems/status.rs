@@ -0,0 +1,216 @@
+use std::fs;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::structure::node::{FileStat, Unmerged};
+use crate::structure::serialization::{load, TREE_FILE};
+use crate::systems::checkout::working_hash;
+use crate::systems::commits::functions::head_snapshot;
+use crate::systems::filters::ignore::IgnoreRules;
+use crate::systems::merge::merge_head;
+use crate::systems::objects::tree::{compare_snapshots, Change};
+use crate::systems::refs::{read_head, Head};
+use crate::systems::repository::Repository;
+use crate::error::Result;
+
+/// Differences between HEAD, the staging tree and the working directory.
+#[derive(Debug)]
+pub struct Status {
+    /// What HEAD designates, as printed on the first line
+    pub head: Head,
+    /// Staging tree compared with HEAD: what the next commit would record
+    pub staged: Vec<(PathBuf, Change)>,
+    /// Files a merge left conflicted, with the versions of each side
+    pub unmerged: Vec<(PathBuf, Unmerged)>,
+    /// Working directory compared with the staging tree, for staged paths only
+    pub unstaged: Vec<(PathBuf, Change)>,
+    /// Files on disk that are not staged. A directory without any staged file
+    /// is reported once, with a trailing slash.
+    pub untracked: Vec<PathBuf>,
+    /// Whether a merge waits to be continued or aborted
+    pub merging: bool,
+}
+
+impl Status {
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty() && self.unmerged.is_empty() && self.unstaged.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// Compares the working directory, `.grit/tree.json` and the HEAD commit without
+/// modifying any of them
+pub fn status(repo: &Repository) -> Result<Status> {
+    let root = repo.root();
+    let store = repo.objects();
+
+    let head = head_snapshot(repo, &store)?;
+    let tree = load(repo)?;
+    let index = tree.snapshot();
+
+    // Entries modified after the staging tree was written may have changed again within
+    // the same timestamp, so only older ones can be trusted from their stat info alone
+    let staged_at = fs::metadata(repo.grit_path(TREE_FILE))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+
+    let mut unstaged = Vec::new();
+    for (path, hash) in index.iter().filter(|(path, _)| !tree.unmerged().contains_key(*path)) {
+        let stat_unchanged = tree.get_file_stat(path)
+            .is_some_and(|stat| stat.mtime < staged_at && FileStat::of(&root.join(path)).is_ok_and(|current| current == stat));
+        if stat_unchanged {
+            continue;
+        }
+
+        match working_hash(root, path) {
+            None => unstaged.push((path.clone(), Change::Deleted)),
+            Some(on_disk) if &on_disk != hash => unstaged.push((path.clone(), Change::Modified)),
+            _ => {}
+        }
+    }
+
+    // Conflicted files are reported on their own, even those a side deleted
+    let tracked: HashSet<&Path> = index.keys().chain(tree.unmerged().keys()).map(PathBuf::as_path).collect();
+    let tracked_dirs: HashSet<&Path> = tracked.iter().flat_map(|path| path.ancestors().skip(1)).collect();
+    let mut rules = IgnoreRules::load(root)?;
+    let mut untracked = Vec::new();
+    collect_untracked(root, Path::new(""), &tracked, &tracked_dirs, &mut rules, &mut untracked)?;
+
+    Ok(Status {
+        head: read_head(repo)?,
+        staged: compare_snapshots(&store, &head, &index)?,
+        unmerged: tree.unmerged().iter().map(|(path, stages)| (path.clone(), stages.clone())).collect(),
+        unstaged,
+        untracked,
+        merging: merge_head(repo)?.is_some(),
+    })
+}
+
+/// Walk the working directory below `dir`, skipping `.grit` and ignored paths. Only
+/// directories in `tracked_dirs` have tracked files below them.
+fn collect_untracked(
+    root: &Path,
+    dir: &Path,
+    tracked: &HashSet<&Path>,
+    tracked_dirs: &HashSet<&Path>,
+    rules: &mut IgnoreRules,
+    untracked: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(root.join(dir))?
+        .map(|entry| entry.map(|entry| dir.join(entry.file_name())))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    for rel_path in entries {
+        let is_dir = root.join(&rel_path).is_dir();
+        let is_tracked = tracked.contains(rel_path.as_path()) || tracked_dirs.contains(rel_path.as_path());
+        if !is_tracked && rules.is_ignored(&rel_path, is_dir)? {
+            continue;
+        }
+        if is_dir {
+            if is_tracked {
+                collect_untracked(root, &rel_path, tracked, tracked_dirs, rules, untracked)?;
+            } else if !rules.walk_files(&rel_path)?.is_empty() {
+                let mut name = rel_path.into_os_string();
+                name.push("/");
+                untracked.push(PathBuf::from(name));
+            }
+        } else if !tracked.contains(rel_path.as_path()) {
+            untracked.push(rel_path);
+        }
+    }
+    Ok(())
+}
+
+/// Human readable report, grouped like `git status`
+pub fn format_status(status: &Status) -> String {
+    let mut out = String::new();
+    match &status.head {
+        Head::Branch(name) => out.push_str(&format!("On branch {}\n", name)),
+        Head::Detached(id) => out.push_str(&format!("HEAD detached at {}\n", &id[..7.min(id.len())])),
+    }
+    if status.merging && !status.unmerged.is_empty() {
+        out.push_str("You have unmerged paths.\n  (fix conflicts, add the files, then run \"grit merge --continue\")\n");
+    } else if status.merging {
+        out.push_str("All conflicts fixed but you are still merging.\n  (use \"grit merge --continue\" to conclude merge)\n");
+    }
+
+    if !status.staged.is_empty() {
+        out.push_str("\nChanges to be committed:\n");
+        for (path, change) in &status.staged {
+            let line = match change {
+                Change::Added => format!("new file:   {}", path.display()),
+                Change::Modified => format!("modified:   {}", path.display()),
+                Change::Deleted => format!("deleted:    {}", path.display()),
+                Change::Renamed { from, .. } => format!("renamed:    {} -> {}", from.display(), path.display()),
+            };
+            out.push_str(&format!("\t{}\n", line));
+        }
+    }
+
+    if !status.unmerged.is_empty() {
+        out.push_str("\nUnmerged paths:\n");
+        for (path, stages) in &status.unmerged {
+            out.push_str(&format!("\t{:<17}{}\n", format!("{}:", stages.describe()), path.display()));
+        }
+    }
+
+    if !status.unstaged.is_empty() {
+        out.push_str("\nChanges not staged for commit:\n");
+        for (path, change) in &status.unstaged {
+            let label = if *change == Change::Deleted { "deleted:   " } else { "modified:  " };
+            out.push_str(&format!("\t{} {}\n", label, path.display()));
+        }
+    }
+
+    if !status.untracked.is_empty() {
+        out.push_str("\nUntracked files:\n");
+        for path in &status.untracked {
+            out.push_str(&format!("\t{}\n", path.display()));
+        }
+    }
+
+    if status.is_clean() {
+        out.push_str("nothing to commit, working tree clean\n");
+    } else if status.staged.is_empty() && status.unmerged.is_empty() {
+        out.push_str("\nno changes added to commit (use \"grit add\")\n");
+    }
+    out
+}
+
+/// Stable `XY path` lines for scripts: X is the staged change, Y the unstaged one,
+/// conflicted paths are marked `UU`, `AA`, `DU` or `UD` and untracked ones `??`
+pub fn format_porcelain(status: &Status) -> String {
+    let mut lines: BTreeMap<&PathBuf, (char, char, Option<&PathBuf>)> = BTreeMap::new();
+    for (path, change) in &status.staged {
+        let from = match change {
+            Change::Renamed { from, .. } => Some(from),
+            _ => None,
+        };
+        let line = lines.entry(path).or_insert((' ', ' ', None));
+        line.0 = change.code();
+        line.2 = from;
+    }
+    for (path, change) in &status.unstaged {
+        lines.entry(path).or_insert((' ', ' ', None)).1 = change.code();
+    }
+
+    for (path, stages) in &status.unmerged {
+        let code = stages.code();
+        lines.insert(path, (code[0], code[1], None));
+    }
+
+    let mut out = String::new();
+    for (path, (x, y, from)) in lines {
+        match from {
+            Some(from) => out.push_str(&format!("{}{} {} -> {}\n", x, y, from.display(), path.display())),
+            None => out.push_str(&format!("{}{} {}\n", x, y, path.display())),
+        }
+    }
+    for path in &status.untracked {
+        out.push_str(&format!("?? {}\n", path.display()));
+    }
+    out
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::systems::add::add;
+use crate::systems::status::status;
+use crate::systems::objects::tree::Change;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+    use crate::structure::serialization::load;
+    use crate::systems::status::{format_porcelain, format_status};
+
+    #[test]
+    fn test_status_of_clean_tree() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n")?;
+        repo.commit_all("First")?;
+
+        let status = status(&repo)?;
+        assert!(status.is_clean(), "Nothing changed since the commit: {:?}", status);
+        assert!(format_status(&status).contains("nothing to commit, working tree clean"));
+        assert_eq!(format_porcelain(&status), "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_reports_every_kind_of_change() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("kept.txt", "kept\n")?;
+        repo.write("edited.txt", "v1\n")?;
+        repo.write("staged_edit.txt", "v1\n")?;
+        repo.write("old_name.txt", "moved content\n")?;
+        repo.commit_all("First")?;
+
+        repo.write("staged_edit.txt", "v2\n")?;
+        fs::rename(repo.working_path("old_name.txt"), repo.working_path("new_name.txt"))?;
+        repo.write("added.txt", "new\n")?;
+        add(&repo, None)?;
+
+        repo.write("edited.txt", "v2\n")?;
+        fs::remove_file(repo.working_path("kept.txt"))?;
+        repo.write("untracked.txt", "?\n")?;
+        fs::create_dir_all(repo.working_path("fresh/nested"))?;
+        repo.write("fresh/nested/file.txt", "?\n")?;
+
+        let status = status(&repo)?;
+        assert_eq!(status.staged, vec![
+            (PathBuf::from("added.txt"), Change::Added),
+            (PathBuf::from("new_name.txt"), Change::Renamed { from: PathBuf::from("old_name.txt"), similarity: 100 }),
+            (PathBuf::from("staged_edit.txt"), Change::Modified),
+        ]);
+        assert_eq!(status.unstaged, vec![
+            (PathBuf::from("edited.txt"), Change::Modified),
+            (PathBuf::from("kept.txt"), Change::Deleted),
+        ]);
+        assert_eq!(status.untracked, vec![PathBuf::from("fresh/"), PathBuf::from("untracked.txt")]);
+
+        assert_eq!(format_porcelain(&status), "\
+A  added.txt
+ M edited.txt
+ D kept.txt
+R  old_name.txt -> new_name.txt
+M  staged_edit.txt
+?? fresh/
+?? untracked.txt
+");
+        let long = format_status(&status);
+        assert!(long.starts_with("On branch Main\n"));
+        assert!(long.contains("\trenamed:    old_name.txt -> new_name.txt\n"));
+        assert!(long.contains("Changes not staged for commit:\n\tmodified:   edited.txt\n\tdeleted:    kept.txt\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_does_not_modify_the_staging_tree() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n")?;
+        add(&repo, None)?;
+
+        let before = repo.read(".grit/tree.json")?;
+        let first = status(&repo)?;
+        let second = status(&repo)?;
+        assert_eq!(repo.read(".grit/tree.json")?, before);
+        assert_eq!(first.staged, second.staged, "Repeated calls must report the same changes");
+        assert_eq!(first.staged, vec![(PathBuf::from("a.txt"), Change::Added)]);
+        assert_eq!(load(&repo)?.snapshot().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_and_add_skip_ignored_files() -> io::Result<()> {
+        let repo = TempRepo::new();
+        repo.write("tracked.log", "tracked before the pattern\n")?;
+        add(&repo, Some("tracked.log"))?;
+        repo.write(".gritignore", "*.log\ntarget/\n")?;
+        repo.write("debug.log", "noise\n")?;
+        fs::create_dir(repo.working_path("target"))?;
+        repo.write("target/out.bin", "build output\n")?;
+        fs::create_dir(repo.working_path("only_ignored"))?;
+        repo.write("only_ignored/x.log", "noise\n")?;
+        repo.write("main.rs", "fn main() {}\n")?;
+
+        let status = status(&repo)?;
+        assert_eq!(status.untracked, vec![PathBuf::from(".gritignore"), PathBuf::from("main.rs")]);
+
+        repo.write("tracked.log", "still tracked\n")?;
+        add(&repo, None)?;
+        let staged = load(&repo)?.snapshot();
+        let staged: Vec<&PathBuf> = staged.keys().collect();
+        assert_eq!(staged, vec![&PathBuf::from(".gritignore"), &PathBuf::from("main.rs"), &PathBuf::from("tracked.log")]);
+        assert!(add(&repo, Some("debug.log")).is_err(), "Naming an ignored file is refused");
+        Ok(())
+    }
+}
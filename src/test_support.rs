@@ -0,0 +1,80 @@
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use tempfile::TempDir;
+use crate::systems::commits::commit::Commit;
+use crate::systems::identity::{Identity, Signature};
+use crate::systems::repository::Repository;
+
+/// A fresh repository in a temporary directory, removed when dropped. Tests use it
+/// instead of the process working directory, so they can run in parallel.
+pub struct TempRepo {
+    repo: Repository,
+    _dir: TempDir,
+}
+
+impl TempRepo {
+    pub fn new() -> Self {
+        let dir = TempDir::new().expect("Failed to create temporary directory");
+        let repo = Repository::init(dir.path()).expect("Failed to initialize repository");
+        TempRepo { repo, _dir: dir }
+    }
+
+    /// Writes a file of the working directory, creating its parent directories
+    pub fn write(&self, rel_path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> io::Result<()> {
+        let path = self.working_path(rel_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)
+    }
+
+    /// Reads a file of the working directory
+    pub fn read(&self, rel_path: impl AsRef<Path>) -> io::Result<String> {
+        fs::read_to_string(self.working_path(rel_path))
+    }
+
+    /// Whether a file or directory of the working directory exists
+    pub fn exists(&self, rel_path: impl AsRef<Path>) -> bool {
+        self.working_path(rel_path).exists()
+    }
+
+    /// Stages everything and commits it as the `tester()`, failing when nothing changed
+    pub fn commit_all(&self, message: &str) -> io::Result<Commit> {
+        self.add(None)?;
+        self.commit(message, &tester())?
+            .ok_or_else(|| io::Error::other("Nothing to commit"))
+    }
+
+    /// Writes a file, then stages everything and commits it
+    pub fn commit_file(&self, rel_path: &str, content: &str, message: &str) -> io::Result<Commit> {
+        self.write(rel_path, content)?;
+        self.commit_all(message)
+    }
+}
+
+impl Deref for TempRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}
+
+/// Small deterministic random generator, so randomized tests need no extra dependency
+/// and failures can be replayed.
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    /// Next value in `0..bound`
+    pub fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
+
+/// Author and committer of the commits tests make
+pub fn tester() -> Identity {
+    Identity::new(Signature::new("Tester", "tester@example.com").expect("A valid signature"))
+}